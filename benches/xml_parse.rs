@@ -0,0 +1,85 @@
+use slicing::threemf::package::Package;
+use slicing::threemf::xml_parse::parse_model;
+use slicing::threemf::Model;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use zip::ZipArchive;
+
+// Times reading the root model part of a 3MF file with the streaming parser and with serde
+// alone, and counts what each allocates. Pass file names to use other files.
+
+const FILE: &str = "data/test_fusion_multi.3mf";
+const RUNS: u32 = 10;
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// Average time and bytes allocated per run
+fn measure<F: Fn() -> Model>(parse: F) -> (Duration, usize, Model) {
+    let model = parse();
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        parse();
+    }
+    let elapsed = start.elapsed() / RUNS;
+    let allocated = (ALLOCATED.load(Ordering::Relaxed) - allocated) / RUNS as usize;
+    (elapsed, allocated, model)
+}
+
+fn main() {
+    // cargo bench passes --bench
+    let mut files: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    if files.is_empty() {
+        files.push(FILE.to_string());
+    }
+
+    for file in files {
+        let archive = ZipArchive::new(std::fs::File::open(&file).unwrap()).unwrap();
+        let mut package = Package::new(archive).unwrap();
+        let root = package.root().unwrap();
+        let data = package.read_bytes(&root).unwrap();
+
+        let (serde_time, serde_bytes, expected) =
+            measure(|| quick_xml::de::from_reader(data.as_slice()).unwrap());
+        let (time, bytes, model) = measure(|| parse_model(&data).unwrap());
+        assert!(model == expected, "{} reads differently", file);
+
+        println!("{} ({} kB of XML)", file, data.len() / 1024);
+        println!(
+            "  serde:     {:>10.2?} {:>10} kB allocated",
+            serde_time,
+            serde_bytes / 1024
+        );
+        println!(
+            "  streaming: {:>10.2?} {:>10} kB allocated ({:.1}x faster)",
+            time,
+            bytes / 1024,
+            serde_time.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
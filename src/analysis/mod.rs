@@ -0,0 +1,7 @@
+pub use orientation::{
+    evaluate_orientation, optimize_orientation, Orientation, OrientationOptions,
+};
+pub use overhang::{analyze_overhangs, FaceClass, OverhangAnalysis};
+
+pub mod orientation;
+pub mod overhang;
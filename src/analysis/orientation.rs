@@ -0,0 +1,172 @@
+use crate::analysis::{analyze_overhangs, FaceClass};
+use crate::geometry::{Transform, Vector};
+use crate::threemf::Mesh;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct OrientationOptions {
+    // See analyze_overhangs
+    pub critical_angle: f64,
+    // Number of the largest flat faces tried as the face resting on the bed
+    pub max_faces: usize,
+    pub overhang_weight: f64,
+    pub contact_weight: f64,
+    pub height_weight: f64,
+}
+
+impl Default for OrientationOptions {
+    fn default() -> Self {
+        OrientationOptions {
+            critical_angle: 45.0,
+            max_faces: 20,
+            overhang_weight: 1.0,
+            contact_weight: 0.5,
+            height_weight: 0.25,
+        }
+    }
+}
+
+// A way of placing a mesh on the build plate
+#[derive(Debug, Clone, Serialize)]
+pub struct Orientation {
+    // Direction, in the original mesh, that ends up facing the bed
+    pub down: Vector,
+    // Rotation onto the bed, followed by the translation resting the mesh on z = 0
+    pub transform: Transform,
+    pub overhang_area: f64,
+    // Area of the faces touching the bed
+    pub contact_area: f64,
+    pub height: f64,
+    // Lower is better
+    pub cost: f64,
+    // The transformed mesh
+    #[serde(skip)]
+    pub mesh: Mesh,
+}
+
+// Places the mesh so that `down` faces the bed (-Z) and measures how well it would print
+pub fn evaluate_orientation(
+    mesh: &Mesh,
+    down: Vector,
+    options: &OrientationOptions,
+) -> Orientation {
+    let rotation = Transform::rotation_between(down, -Vector::Z);
+    let rotated = mesh.transform(&rotation);
+    let (min, max) = rotated.bounds();
+    let transform = rotation.then(&Transform::translation(Vector::new(0.0, 0.0, -min.z)));
+    let placed = mesh.transform(&transform);
+
+    let analysis = analyze_overhangs(&placed, Vector::Z, options.critical_angle);
+    let contact_area: f64 = placed
+        .triangles
+        .iter()
+        .zip(analysis.classes.iter())
+        .filter(|(_, &c)| c == FaceClass::Bed)
+        .map(|(t, _)| placed.triangle_area(t))
+        .sum();
+    let height = max.z - min.z;
+
+    // Normalise everything so the weights are independent of the model's scale
+    let area = placed.surface_area().max(f64::MIN_POSITIVE);
+    let size = placed.size().max(f64::MIN_POSITIVE);
+    let cost = options.overhang_weight * analysis.overhang_area / area
+        - options.contact_weight * contact_area / area
+        + options.height_weight * height / size;
+
+    Orientation {
+        down: down.unit(),
+        transform,
+        overhang_area: analysis.overhang_area,
+        contact_area,
+        height,
+        cost,
+        mesh: placed,
+    }
+}
+
+// Tries the mesh on each of the largest flat faces of its convex hull, and on the axis
+// directions, and returns the orientation with the lowest cost
+pub fn optimize_orientation(mesh: &Mesh, options: &OrientationOptions) -> Orientation {
+    candidate_directions(mesh, options.max_faces)
+        .into_iter()
+        .map(|down| evaluate_orientation(mesh, down, options))
+        .fold(None, |best: Option<Orientation>, o| match best {
+            Some(b) if b.cost <= o.cost => Some(b),
+            _ => Some(o),
+        })
+        .unwrap()
+}
+
+// Outward normals of the largest flat faces of the convex hull (the only faces a part can
+// rest on), followed by the axis directions
+fn candidate_directions(mesh: &Mesh, max_faces: usize) -> Vec<Vector> {
+    // A flat mesh has no hull, fall back to its own faces
+    let hull = mesh.convex_hull().unwrap_or_else(|| mesh.clone());
+
+    // Hull faces are triangulated, so group triangles by their (quantised) normal, summing
+    // area-weighted normals
+    let mut groups: HashMap<(i64, i64, i64), (Vector, f64)> = HashMap::new();
+    for triangle in hull.triangles.iter() {
+        let normal = hull.triangle_normal(triangle);
+        // Faces at vertices that aren't finite have no direction to rest on
+        let len = normal.len();
+        if len <= 0.0 || !len.is_finite() {
+            continue;
+        }
+        let n = normal / len;
+        let key = (
+            (n.x * 1000.0).round() as i64,
+            (n.y * 1000.0).round() as i64,
+            (n.z * 1000.0).round() as i64,
+        );
+        let group = groups.entry(key).or_insert((Vector::zero(), 0.0));
+        group.0 = group.0 + normal;
+        group.1 += len / 2.0;
+    }
+
+    let mut faces: Vec<(Vector, f64)> = groups.into_values().collect();
+    faces.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut directions: Vec<Vector> = Vec::new();
+    let axes = [
+        -Vector::Z,
+        Vector::Z,
+        -Vector::X,
+        Vector::X,
+        -Vector::Y,
+        Vector::Y,
+    ];
+    let faces = faces.into_iter().take(max_faces).map(|(n, _)| n.unit());
+    for direction in faces.chain(axes) {
+        if directions.iter().all(|d| d.dot(direction) < 1.0 - 1e-9) {
+            directions.push(direction);
+        }
+    }
+    directions
+}
+
+#[test]
+fn test_optimize_orientation() {
+    use crate::load::load_model;
+
+    let model = load_model("data/test_fusion.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let best = optimize_orientation(mesh, &OrientationOptions::default());
+
+    // Sitting on one of its faces, resting on z = 0
+    assert!(best.contact_area > 0.0);
+    let (min, _) = best.mesh.bounds();
+    assert!(min.z.abs() < 1e-9);
+    assert!((best.mesh.volume() - mesh.volume()).abs() < 1e-6 * mesh.volume());
+
+    // No other candidate does better
+    let upside_down = evaluate_orientation(mesh, -best.down, &OrientationOptions::default());
+    assert!(best.cost <= upside_down.cost);
+
+    // A vertex that isn't finite leaves the hull out rather than failing
+    let mut broken = mesh.clone();
+    broken.vertices[0].x = f64::NAN;
+    let best = optimize_orientation(&broken, &OrientationOptions::default());
+    assert!(best.down.len().is_finite());
+}
@@ -0,0 +1,102 @@
+use crate::geometry::{Point, Vector};
+use crate::threemf::Mesh;
+use serde::Serialize;
+
+// How a triangle prints when the mesh is built along a given up vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FaceClass {
+    // Facing down while resting on the build plate
+    Bed,
+    // Facing up, or steep enough to print without supports
+    Supported,
+    // Facing down at more than the critical angle, needs supports
+    Overhang,
+    // Zero area, no meaningful normal
+    Degenerate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverhangAnalysis {
+    pub up: Vector,
+    pub critical_angle: f64,
+    // One entry per mesh triangle, in order
+    pub classes: Vec<FaceClass>,
+    // Indices of the overhanging triangles
+    pub overhangs: Vec<usize>,
+    pub overhang_area: f64,
+}
+
+impl OverhangAnalysis {
+    pub fn count(&self, class: FaceClass) -> usize {
+        self.classes.iter().filter(|&&c| c == class).count()
+    }
+}
+
+// Classifies every triangle of the mesh for printing along `up`. `critical_angle` is how
+// far (in degrees) a downward facing surface may lean from vertical before it needs supports.
+pub fn analyze_overhangs(mesh: &Mesh, up: Vector, critical_angle: f64) -> OverhangAnalysis {
+    let up = up.unit();
+    let threshold = critical_angle.to_radians().sin();
+    let height = |p: &Point| (p - Point::zero()).dot(up);
+    let bed = mesh
+        .vertices
+        .iter()
+        .map(height)
+        .fold(f64::INFINITY, f64::min);
+    let tolerance = 1e-6 * mesh.size();
+
+    let mut analysis = OverhangAnalysis {
+        up,
+        critical_angle,
+        classes: Vec::with_capacity(mesh.triangles.len()),
+        overhangs: Vec::new(),
+        overhang_area: 0.0,
+    };
+
+    for (i, triangle) in mesh.triangles.iter().enumerate() {
+        let normal = mesh.triangle_normal(triangle);
+        let len = normal.len();
+
+        let class = if len <= 0.0 {
+            FaceClass::Degenerate
+        } else if (normal / len).dot(-up) <= threshold {
+            FaceClass::Supported
+        } else if mesh
+            .triangle_points(triangle)
+            .iter()
+            .all(|p| height(p) <= bed + tolerance)
+        {
+            FaceClass::Bed
+        } else {
+            analysis.overhangs.push(i);
+            analysis.overhang_area += len / 2.0;
+            FaceClass::Overhang
+        };
+        analysis.classes.push(class);
+    }
+
+    analysis
+}
+
+#[test]
+fn test_analyze_overhangs() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+
+    // Upright, the bottom face sits on the bed
+    let upright = analyze_overhangs(mesh, Vector::Z, 45.0);
+    assert_eq!(upright.count(FaceClass::Bed), 2);
+    assert_eq!(upright.count(FaceClass::Supported), 10);
+    assert!(upright.overhangs.is_empty());
+
+    // Tipped onto an edge, the two lower faces lean 45 degrees from vertical
+    let tipped = analyze_overhangs(mesh, Vector::new(0.0, 1.0, 1.0), 40.0);
+    assert_eq!(tipped.overhangs.len(), 4);
+    assert!((tipped.overhang_area - 8.0).abs() < 1e-9);
+
+    let json = serde_json::to_string(&tipped).unwrap();
+    assert!(json.contains("\"overhang\""));
+}
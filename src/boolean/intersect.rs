@@ -0,0 +1,410 @@
+use crate::geometry::{Point, Vector};
+use crate::threemf::Mesh;
+use robust::{orient2d, orient3d, Coord, Coord3D};
+use std::collections::HashMap;
+
+// Where the surfaces of two meshes cross, with every point identified by how it was made (an
+// edge of one mesh through a triangle of the other, two edges crossing, or an existing
+// vertex). Triangles on either side of an edge look up the same points on it, so splitting
+// them along these points keeps both meshes watertight.
+//
+// All the topological decisions are made with exact orientation predicates. Only the
+// positions of new points are rounded.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    // An edge of one mesh through the inside of a triangle of the other
+    EdgeFace(usize, (usize, usize), usize),
+    // An edge of the first mesh crossing an edge of the second
+    EdgeEdge((usize, usize), (usize, usize)),
+}
+
+// Where a point lies on a triangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Vertex(usize),
+    // On the edge from corner k to corner k + 1
+    Edge(usize),
+    Inside,
+}
+
+pub(crate) struct Intersections<'a> {
+    pub meshes: [&'a Mesh; 2],
+    // Every point, starting with the vertices of both meshes
+    pub points: Vec<Point>,
+    // Points that turned out to be the same (vertices of both meshes in the same place)
+    parent: Vec<usize>,
+    keys: HashMap<Key, usize>,
+    // Points splitting each edge of each mesh, by (lower, higher) vertex index
+    pub edge_points: [HashMap<(usize, usize), Vec<usize>>; 2],
+    // Segments of the intersection crossing each triangle of each mesh
+    pub segments: [HashMap<usize, Vec<(usize, usize)>>; 2],
+    // Triangles of the other mesh lying in the same plane as each triangle
+    pub coplanar: [HashMap<usize, Vec<usize>>; 2],
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn coord(p: Point) -> Coord3D<f64> {
+    Coord3D {
+        x: p.x,
+        y: p.y,
+        z: p.z,
+    }
+}
+
+fn sign(v: f64) -> i8 {
+    if v > 0.0 {
+        1
+    } else if v < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+// The two axes to project a plane with the given normal onto, keeping its orientation
+pub(crate) fn projection(normal: Vector) -> impl Fn(Point) -> Coord<f64> {
+    let n = [normal.x, normal.y, normal.z];
+    let axis = (0..3)
+        .max_by(|&a, &b| n[a].abs().partial_cmp(&n[b].abs()).unwrap())
+        .unwrap();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (u, v) = if n[axis] >= 0.0 { (u, v) } else { (v, u) };
+    move |p: Point| {
+        let c = [p.x, p.y, p.z];
+        Coord { x: c[u], y: c[v] }
+    }
+}
+
+impl<'a> Intersections<'a> {
+    pub fn new(first: &'a Mesh, second: &'a Mesh) -> Self {
+        let mut points = first.vertices.clone();
+        points.extend(second.vertices.iter().copied());
+        Intersections {
+            meshes: [first, second],
+            parent: (0..points.len()).collect(),
+            points,
+            keys: HashMap::new(),
+            edge_points: [HashMap::new(), HashMap::new()],
+            segments: [HashMap::new(), HashMap::new()],
+            coplanar: [HashMap::new(), HashMap::new()],
+        }
+    }
+
+    // The point standing for a vertex of one of the meshes
+    pub fn vertex(&self, mesh: usize, v: usize) -> usize {
+        self.find(mesh * self.meshes[0].vertices.len() + v)
+    }
+
+    pub fn find(&self, mut id: usize) -> usize {
+        while self.parent[id] != id {
+            id = self.parent[id];
+        }
+        id
+    }
+
+    fn merge(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+
+    fn point(&mut self, key: Key, position: Point) -> usize {
+        let next = self.points.len();
+        let id = *self.keys.entry(key).or_insert(next);
+        if id == next {
+            self.points.push(position);
+            self.parent.push(id);
+        }
+        self.find(id)
+    }
+
+    fn split_edge(&mut self, mesh: usize, e: (usize, usize), id: usize) {
+        let list = self.edge_points[mesh].entry(e).or_default();
+        if !list.contains(&id) {
+            list.push(id);
+        }
+    }
+
+    fn corners(&self, mesh: usize, t: usize) -> [usize; 3] {
+        self.meshes[mesh].triangles[t].indices()
+    }
+
+    fn corner_points(&self, mesh: usize, t: usize) -> [Point; 3] {
+        self.corners(mesh, t).map(|v| self.meshes[mesh].vertices[v])
+    }
+
+    // Side of triangle t's plane each corner of triangle s (of the other mesh) is on
+    fn sides(&self, mesh: usize, t: usize, s: usize) -> [i8; 3] {
+        let [a, b, c] = self.corner_points(mesh, t).map(coord);
+        self.corner_points(1 - mesh, s)
+            .map(|p| sign(orient3d(a, b, c, coord(p))))
+    }
+
+    // Where a point known to lie in the plane of a triangle falls on it, if it does
+    fn locate(&self, mesh: usize, t: usize, p: Point) -> Option<Location> {
+        let corners = self.corner_points(mesh, t);
+        let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        let project = projection(normal);
+        let [a, b, c] = corners.map(&project);
+        let q = project(p);
+
+        let orientation = sign(orient2d(a, b, c));
+        if orientation == 0 {
+            return None;
+        }
+        let s = [
+            sign(orient2d(a, b, q)) * orientation,
+            sign(orient2d(b, c, q)) * orientation,
+            sign(orient2d(c, a, q)) * orientation,
+        ];
+        if s.iter().any(|&x| x < 0) {
+            return None;
+        }
+        Some(match s {
+            [0, 0, _] => Location::Vertex(1),
+            [_, 0, 0] => Location::Vertex(2),
+            [0, _, 0] => Location::Vertex(0),
+            [0, _, _] => Location::Edge(0),
+            [_, 0, _] => Location::Edge(1),
+            [_, _, 0] => Location::Edge(2),
+            _ => Location::Inside,
+        })
+    }
+
+    // Attaches a vertex lying in the plane of a triangle of the other mesh to it
+    fn vertex_on_triangle(&mut self, mesh: usize, v: usize, t: usize) -> Option<usize> {
+        let other = 1 - mesh;
+        let location = self.locate(other, t, self.meshes[mesh].vertices[v])?;
+        let id = self.vertex(mesh, v);
+        let corners = self.corners(other, t);
+        match location {
+            Location::Vertex(k) => self.merge(id, self.vertex(other, corners[k])),
+            Location::Edge(k) => self.split_edge(other, edge(corners[k], corners[(k + 1) % 3]), id),
+            Location::Inside => {}
+        }
+        Some(self.find(id))
+    }
+
+    // Where an edge of one mesh meets a triangle of the other, unless the edge lies in the
+    // triangle's plane
+    fn edge_triangle(&mut self, mesh: usize, (p, q): (usize, usize), t: usize) -> Option<usize> {
+        let other = 1 - mesh;
+        let corners = self.corners(other, t);
+        let [a, b, c] = self.corner_points(other, t);
+        let (pp, pq) = (self.meshes[mesh].vertices[p], self.meshes[mesh].vertices[q]);
+
+        let dp = orient3d(coord(a), coord(b), coord(c), coord(pp));
+        let dq = orient3d(coord(a), coord(b), coord(c), coord(pq));
+        match (sign(dp), sign(dq)) {
+            (0, 0) => return None,
+            (0, _) => return self.vertex_on_triangle(mesh, p, t),
+            (_, 0) => return self.vertex_on_triangle(mesh, q, t),
+            (x, y) if x == y => return None,
+            _ => {}
+        }
+
+        // The edge goes through the plane. Its side of each of the triangle's edges says
+        // where.
+        let s = [(a, b), (b, c), (c, a)]
+            .map(|(u, v)| sign(orient3d(coord(pp), coord(pq), coord(u), coord(v))));
+        if s.contains(&1) && s.contains(&-1) {
+            return None;
+        }
+
+        let e = edge(p, q);
+        let position = pp + (pq - pp) * (dp / (dp - dq));
+        let id = match s {
+            [0, 0, _] => self.vertex(other, corners[1]),
+            [_, 0, 0] => self.vertex(other, corners[2]),
+            [0, _, 0] => self.vertex(other, corners[0]),
+            _ => match s.iter().position(|&x| x == 0) {
+                Some(k) => {
+                    let f = edge(corners[k], corners[(k + 1) % 3]);
+                    let key = if mesh == 0 {
+                        Key::EdgeEdge(e, f)
+                    } else {
+                        Key::EdgeEdge(f, e)
+                    };
+                    let id = self.point(key, position);
+                    self.split_edge(other, f, id);
+                    id
+                }
+                None => self.point(Key::EdgeFace(mesh, e, t), position),
+            },
+        };
+        self.split_edge(mesh, e, id);
+        Some(id)
+    }
+
+    // Finds how triangle `a` of the first mesh and triangle `b` of the second meet
+    pub fn intersect(&mut self, a: usize, b: usize) {
+        let (sa, sb) = (self.sides(1, b, a), self.sides(0, a, b));
+        let apart = |s: [i8; 3]| s.iter().all(|&x| x > 0) || s.iter().all(|&x| x < 0);
+        if apart(sa) || apart(sb) {
+            return;
+        }
+        if sa == [0, 0, 0] {
+            self.intersect_coplanar(a, b);
+            return;
+        }
+
+        let mut ids: Vec<usize> = Vec::new();
+        for (mesh, t, s) in [(0, a, b), (1, b, a)] {
+            let c = self.corners(mesh, t);
+            for (p, q) in [(c[0], c[1]), (c[1], c[2]), (c[2], c[0])] {
+                if let Some(id) = self.edge_triangle(mesh, (p, q), s) {
+                    ids.push(id);
+                }
+            }
+        }
+        let mut ids: Vec<usize> = ids.into_iter().map(|id| self.find(id)).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.len() < 2 {
+            return;
+        }
+
+        // Only ever two, short of rounding trouble, in which case keep the longest span
+        let mut best = (f64::NEG_INFINITY, ids[0], ids[1]);
+        for (i, &p) in ids.iter().enumerate() {
+            for &q in ids[i + 1..].iter() {
+                let d = (self.points[p] - self.points[q]).len();
+                if d > best.0 {
+                    best = (d, p, q);
+                }
+            }
+        }
+        let segment = (best.1, best.2);
+        self.segments[0].entry(a).or_default().push(segment);
+        self.segments[1].entry(b).or_default().push(segment);
+    }
+
+    // Overlapping triangles in the same plane. Each gets split along the other's edges.
+    fn intersect_coplanar(&mut self, a: usize, b: usize) {
+        let normal = {
+            let [p, q, r] = self.corner_points(0, a);
+            (q - p).cross(r - p)
+        };
+        let project = projection(normal);
+        let triangles = [a, b];
+
+        // Edges crossing each other
+        let ca = self.corners(0, a);
+        let cb = self.corners(1, b);
+        let mut crossings: Vec<(usize, usize, usize)> = Vec::new();
+        for i in 0..3 {
+            let (e0, e1) = (ca[i], ca[(i + 1) % 3]);
+            let (pe0, pe1) = (self.meshes[0].vertices[e0], self.meshes[0].vertices[e1]);
+            for j in 0..3 {
+                let (f0, f1) = (cb[j], cb[(j + 1) % 3]);
+                let (pf0, pf1) = (self.meshes[1].vertices[f0], self.meshes[1].vertices[f1]);
+                let o1 = orient2d(project(pe0), project(pe1), project(pf0));
+                let o2 = orient2d(project(pe0), project(pe1), project(pf1));
+                let o3 = orient2d(project(pf0), project(pf1), project(pe0));
+                let o4 = orient2d(project(pf0), project(pf1), project(pe1));
+                if sign(o1) * sign(o2) < 0 && sign(o3) * sign(o4) < 0 {
+                    let (e, f) = (edge(e0, e1), edge(f0, f1));
+                    let position = pe0 + (pe1 - pe0) * (o3 / (o3 - o4));
+                    let id = self.point(Key::EdgeEdge(e, f), position);
+                    self.split_edge(0, e, id);
+                    self.split_edge(1, f, id);
+                    crossings.push((i, j, id));
+                }
+            }
+        }
+
+        // Corners lying on the other triangle
+        let mut inside: [Vec<(usize, usize)>; 2] = [Vec::new(), Vec::new()];
+        for mesh in 0..2 {
+            let corners = self.corners(mesh, triangles[mesh]);
+            for (k, &v) in corners.iter().enumerate() {
+                if let Some(id) = self.vertex_on_triangle(mesh, v, triangles[1 - mesh]) {
+                    inside[mesh].push((k, id));
+                }
+            }
+        }
+
+        // The parts of each triangle's edges inside the other become segments there
+        for mesh in 0..2 {
+            let other = 1 - mesh;
+            let (t, s) = (triangles[mesh], triangles[other]);
+            let corners = self.corners(mesh, t);
+            for k in 0..3 {
+                let (from, to) = (
+                    self.meshes[mesh].vertices[corners[k]],
+                    self.meshes[mesh].vertices[corners[(k + 1) % 3]],
+                );
+                let mut along: Vec<usize> = inside[mesh]
+                    .iter()
+                    .filter(|&&(c, _)| c == k || c == (k + 1) % 3)
+                    .map(|&(_, id)| id)
+                    .collect();
+                along.extend(
+                    crossings
+                        .iter()
+                        .filter(|&&(i, j, _)| if mesh == 0 { i == k } else { j == k })
+                        .map(|&(_, _, id)| id),
+                );
+                // Corners of the other triangle lying on this edge
+                for &(_, id) in inside[other].iter() {
+                    let p = self.points[id];
+                    let o = orient2d(project(from), project(to), project(p));
+                    let d = (p - from).dot(to - from);
+                    if o == 0.0 && d > 0.0 && d < (to - from).dot(to - from) {
+                        along.push(id);
+                    }
+                }
+
+                let mut along: Vec<usize> = along.into_iter().map(|id| self.find(id)).collect();
+                along.sort_by(|&p, &q| {
+                    let (dp, dq) = (
+                        (self.points[p] - from).dot(to - from),
+                        (self.points[q] - from).dot(to - from),
+                    );
+                    dp.partial_cmp(&dq).unwrap()
+                });
+                along.dedup();
+                for pair in along.windows(2) {
+                    let middle =
+                        self.points[pair[0]] + (self.points[pair[1]] - self.points[pair[0]]) / 2.0;
+                    if self.locate(other, s, middle).is_some() {
+                        self.segments[other]
+                            .entry(s)
+                            .or_default()
+                            .push((pair[0], pair[1]));
+                    }
+                }
+            }
+        }
+
+        self.coplanar[0].entry(a).or_default().push(b);
+        self.coplanar[1].entry(b).or_default().push(a);
+    }
+
+    // Treats points that were rounded to the same position as one. The intersection is
+    // collapsed a little where that happens, but the same way for both meshes.
+    pub fn merge_coincident(&mut self) {
+        let mut seen: HashMap<[u64; 3], usize> = HashMap::new();
+        for id in 0..self.points.len() {
+            let p = self.points[id];
+            // Adding zero turns -0.0 into 0.0
+            let key = [p.x + 0.0, p.y + 0.0, p.z + 0.0].map(f64::to_bits);
+            match seen.get(&key) {
+                Some(&other) => self.merge(id, other),
+                None => {
+                    seen.insert(key, id);
+                }
+            }
+        }
+    }
+
+    // Whether a point in the plane of a triangle lies on it
+    pub fn on_triangle(&self, mesh: usize, t: usize, p: Point) -> bool {
+        self.locate(mesh, t, p).is_some()
+    }
+}
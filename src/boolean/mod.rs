@@ -0,0 +1,392 @@
+use crate::boolean::intersect::Intersections;
+use crate::boolean::retriangulate::retriangulate;
+use crate::geometry::containment::Containment;
+use crate::geometry::{Point, Triangle};
+use crate::threemf::Mesh;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+mod intersect;
+mod retriangulate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    Intersection,
+    // The first mesh with the second taken away
+    Difference,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operand {
+    First,
+    Second,
+}
+
+// Where a triangle of a boolean result came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Provenance {
+    pub operand: Operand,
+    // Index of the triangle in that operand it is part of
+    pub triangle: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BooleanResult {
+    pub mesh: Mesh,
+    // One entry per triangle of the mesh
+    pub provenance: Vec<Provenance>,
+}
+
+// Where part of one mesh's surface is relative to the other mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Inside,
+    Outside,
+    // On the other surface, facing the same way
+    OnSame,
+    // On the other surface, facing the other way
+    OnOpposite,
+}
+
+fn keep(op: BooleanOp, operand: Operand, class: Class) -> bool {
+    use Class::*;
+    match (op, operand) {
+        (BooleanOp::Union, Operand::First) => matches!(class, Outside | OnSame),
+        (BooleanOp::Intersection, Operand::First) => matches!(class, Inside | OnSame),
+        (BooleanOp::Difference, Operand::First) => matches!(class, Outside | OnOpposite),
+        // Shared faces are only taken from the first mesh
+        (BooleanOp::Union, Operand::Second) => class == Outside,
+        (BooleanOp::Intersection, Operand::Second) => class == Inside,
+        (BooleanOp::Difference, Operand::Second) => class == Inside,
+    }
+}
+
+// Pairs of triangles (one from each mesh) whose bounding boxes overlap
+fn candidate_pairs(first: &Mesh, second: &Mesh) -> Vec<(usize, usize)> {
+    let bounds = |mesh: &Mesh, t: &Triangle| {
+        let [a, b, c] = mesh.triangle_points(t);
+        (
+            Point::new(
+                a.x.min(b.x).min(c.x),
+                a.y.min(b.y).min(c.y),
+                a.z.min(b.z).min(c.z),
+            ),
+            Point::new(
+                a.x.max(b.x).max(c.x),
+                a.y.max(b.y).max(c.y),
+                a.z.max(b.z).max(c.z),
+            ),
+        )
+    };
+    let boxes: [Vec<(Point, Point)>; 2] =
+        [first, second].map(|mesh| mesh.triangles.iter().map(|t| bounds(mesh, t)).collect());
+
+    // Sweep along x, keeping the boxes of each mesh the sweep is inside of
+    let mut events: Vec<(f64, usize, usize)> = Vec::new();
+    for (mesh, list) in boxes.iter().enumerate() {
+        events.extend(list.iter().enumerate().map(|(t, b)| (b.0.x, mesh, t)));
+    }
+    events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut active: [Vec<usize>; 2] = [Vec::new(), Vec::new()];
+    let mut pairs = Vec::new();
+    for (x, mesh, t) in events {
+        let other = 1 - mesh;
+        active[other].retain(|&s| boxes[other][s].1.x >= x);
+        let (min, max) = boxes[mesh][t];
+        for &s in active[other].iter() {
+            let (omin, omax) = boxes[other][s];
+            if min.y <= omax.y && omin.y <= max.y && min.z <= omax.z && omin.z <= max.z {
+                pairs.push(if mesh == 0 { (t, s) } else { (s, t) });
+            }
+        }
+        active[mesh].push(t);
+    }
+    pairs
+}
+
+// Combines two closed meshes. The result is closed as long as both inputs are, with the
+// surfaces split exactly where they cross.
+pub fn boolean(first: &Mesh, second: &Mesh, op: BooleanOp) -> BooleanResult {
+    let mut ix = Intersections::new(first, second);
+    for (a, b) in candidate_pairs(first, second) {
+        ix.intersect(a, b);
+    }
+    ix.merge_coincident();
+
+    let mut vertices: Vec<Point> = Vec::new();
+    let mut index: HashMap<usize, usize> = HashMap::new();
+    let mut triangles: Vec<Triangle> = Vec::new();
+    let mut provenance: Vec<Provenance> = Vec::new();
+    let mut constraints: HashSet<(usize, usize)> = HashSet::new();
+
+    for (mesh, operand) in [(0, Operand::First), (1, Operand::Second)] {
+        let source = ix.meshes[mesh];
+        let other = ix.meshes[1 - mesh];
+        let bvh = other.bvh();
+
+        // Split up every triangle the other surface touches
+        let mut fragments: Vec<(usize, [usize; 3])> = Vec::new();
+        for (t, triangle) in source.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.indices();
+            let touched = ix.segments[mesh].contains_key(&t)
+                || [(a, b), (b, c), (c, a)]
+                    .iter()
+                    .any(|&(p, q)| ix.edge_points[mesh].contains_key(&(p.min(q), p.max(q))));
+            let pieces = if touched {
+                retriangulate(&ix, mesh, t, &mut constraints)
+            } else {
+                vec![[a, b, c].map(|v| ix.vertex(mesh, v))]
+            };
+            fragments.extend(
+                pieces
+                    .into_iter()
+                    .filter(|f| f[0] != f[1] && f[1] != f[2] && f[2] != f[0])
+                    .map(|f| (t, f)),
+            );
+        }
+
+        // Fragments joined by edges off the intersection are on the same side of it
+        let mut parent: Vec<usize> = (0..fragments.len()).collect();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for (i, (_, f)) in fragments.iter().enumerate() {
+            for k in 0..3 {
+                let (p, q) = (f[k], f[(k + 1) % 3]);
+                let e = (p.min(q), p.max(q));
+                if constraints.contains(&e) {
+                    continue;
+                }
+                match edges.get(&e) {
+                    Some(&j) => {
+                        let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                        parent[a.max(b)] = a.min(b);
+                    }
+                    None => {
+                        edges.insert(e, i);
+                    }
+                }
+            }
+        }
+
+        // Classify each patch by its largest fragment
+        let area = |f: &[usize; 3]| {
+            let [a, b, c] = f.map(|id| ix.points[id]);
+            (b - a).cross(c - a).len()
+        };
+        let mut representative: HashMap<usize, usize> = HashMap::new();
+        for i in 0..fragments.len() {
+            let root = find(&mut parent, i);
+            let best = representative.entry(root).or_insert(i);
+            if area(&fragments[i].1) > area(&fragments[*best].1) {
+                *best = i;
+            }
+        }
+        let mut classes: HashMap<usize, Class> = HashMap::new();
+        for (&root, &i) in representative.iter() {
+            let (t, f) = &fragments[i];
+            let [a, b, c] = f.map(|id| ix.points[id]);
+            let centroid = Point::new(
+                (a.x + b.x + c.x) / 3.0,
+                (a.y + b.y + c.y) / 3.0,
+                (a.z + b.z + c.z) / 3.0,
+            );
+            let normal = source.triangle_normal(&source.triangles[*t]);
+            let on = ix.coplanar[mesh]
+                .get(t)
+                .into_iter()
+                .flatten()
+                .find(|&&s| ix.on_triangle(1 - mesh, s, centroid));
+            let class = match on {
+                Some(&s) if other.triangle_normal(&other.triangles[s]).dot(normal) > 0.0 => {
+                    Class::OnSame
+                }
+                Some(_) => Class::OnOpposite,
+                None if bvh.containment(centroid) == Containment::Inside => Class::Inside,
+                None => Class::Outside,
+            };
+            classes.insert(root, class);
+        }
+
+        let flip = op == BooleanOp::Difference && operand == Operand::Second;
+        for i in 0..fragments.len() {
+            let class = classes[&find(&mut parent, i)];
+            if !keep(op, operand, class) {
+                continue;
+            }
+            let (t, f) = fragments[i];
+            let [a, b, c] = f.map(|id| {
+                *index.entry(id).or_insert_with(|| {
+                    vertices.push(ix.points[id]);
+                    vertices.len() - 1
+                })
+            });
+            triangles.push(if flip {
+                Triangle::new(a, c, b)
+            } else {
+                Triangle::new(a, b, c)
+            });
+            provenance.push(Provenance {
+                operand,
+                triangle: t,
+            });
+        }
+    }
+
+    close_t_junctions(&vertices, &mut triangles, &mut provenance);
+    BooleanResult {
+        mesh: Mesh::new(vertices, triangles),
+        provenance,
+    }
+}
+
+// A point of the intersection found inside one triangle can be rounded onto the edge it
+// shares with the next. That one then has the edge whole, where the first has it split.
+// Splits such triangles at the points along the other side of the edge.
+fn close_t_junctions(
+    vertices: &[Point],
+    triangles: &mut Vec<Triangle>,
+    provenance: &mut Vec<Provenance>,
+) {
+    loop {
+        let mut counts: HashMap<(usize, usize), i32> = HashMap::new();
+        for triangle in triangles.iter() {
+            let [a, b, c] = triangle.indices();
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                *counts.entry((p, q)).or_insert(0) += 1;
+                *counts.entry((q, p)).or_insert(0) -= 1;
+            }
+        }
+        let mut open: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (&(p, q), &n) in counts.iter() {
+            if n > 0 {
+                open.entry(p).or_default().push(q);
+            }
+        }
+        if open.is_empty() {
+            return;
+        }
+
+        // Open edges running back from v to u, all along the line between them
+        let along = |u: usize, v: usize| -> Option<Vec<usize>> {
+            let (from, direction) = (vertices[u], vertices[v] - vertices[u]);
+            let squared = direction.dot(direction);
+            let mut path = vec![v];
+            while path[path.len() - 1] != u {
+                let reached = (vertices[path[path.len() - 1]] - from).dot(direction);
+                let next = *open.get(&path[path.len() - 1])?.iter().find(|&&w| {
+                    let offset = vertices[w] - from;
+                    let position = offset.dot(direction);
+                    w == u
+                        || (position > 0.0
+                            && position < reached
+                            && offset.cross(direction).len() <= 1e-9 * squared)
+                })?;
+                path.push(next);
+            }
+            (path.len() > 2).then(|| path[1..path.len() - 1].to_vec())
+        };
+
+        let mut split = false;
+        for t in 0..triangles.len() {
+            let corners = triangles[t].indices();
+            for k in 0..3 {
+                let (u, v, w) = (corners[k], corners[(k + 1) % 3], corners[(k + 2) % 3]);
+                if counts[&(u, v)] <= 0 {
+                    continue;
+                }
+                let mut points = match along(u, v) {
+                    Some(points) => points,
+                    None => continue,
+                };
+                // From u to v, each piece of the edge making a triangle with w
+                points.reverse();
+                points.insert(0, u);
+                points.push(v);
+                triangles[t] = Triangle::new(points[0], points[1], w);
+                for pair in points[1..].windows(2) {
+                    triangles.push(Triangle::new(pair[0], pair[1], w));
+                    provenance.push(provenance[t]);
+                }
+                split = true;
+                break;
+            }
+        }
+        if !split {
+            return;
+        }
+    }
+}
+
+pub fn union(first: &Mesh, second: &Mesh) -> BooleanResult {
+    boolean(first, second, BooleanOp::Union)
+}
+
+pub fn intersection(first: &Mesh, second: &Mesh) -> BooleanResult {
+    boolean(first, second, BooleanOp::Intersection)
+}
+
+pub fn difference(first: &Mesh, second: &Mesh) -> BooleanResult {
+    boolean(first, second, BooleanOp::Difference)
+}
+
+#[test]
+fn test_boolean_cubes() {
+    use crate::cut::open_edges;
+    use crate::geometry::{Transform, Vector};
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let cube = &model.objects[0].mesh;
+    let moved = cube.transform(&Transform::translation(Vector::new(1.0, 0.5, 0.25)));
+
+    let overlap = 1.0 * 1.5 * 1.75;
+    for (op, volume) in [
+        (BooleanOp::Union, 16.0 - overlap),
+        (BooleanOp::Intersection, overlap),
+        (BooleanOp::Difference, 8.0 - overlap),
+    ] {
+        let result = boolean(cube, &moved, op);
+        assert!(open_edges(&result.mesh.triangles).is_empty());
+        assert!((result.mesh.volume() - volume).abs() < 1e-9);
+        assert_eq!(result.provenance.len(), result.mesh.triangles.len());
+    }
+
+    // Sharing the top face exactly, so the union has nothing left in between
+    let stacked = cube.transform(&Transform::translation(Vector::new(0.0, 0.0, 2.0)));
+    let result = union(cube, &stacked);
+    assert!(open_edges(&result.mesh.triangles).is_empty());
+    assert!((result.mesh.volume() - 16.0).abs() < 1e-9);
+    let result = intersection(cube, &stacked);
+    assert!(result.mesh.volume().abs() < 1e-9);
+}
+
+#[test]
+fn test_boolean_grazing() {
+    use crate::cut::open_edges;
+    use crate::geometry::primitives::{capsule, sphere};
+    use crate::geometry::{Transform, Vector};
+    use std::f64::consts::PI;
+
+    // A ball where a beam meets a narrowing one, crossing the second beam almost along an
+    // edge of the ball. Points found inside a triangle get rounded onto that edge.
+    let corner = Transform::translation(Vector::new(10.0, 0.0, 0.0));
+    let along = capsule(1.0, 1.0, 10.0, 32, [false, false])
+        .transform(&Transform::rotation_between(Vector::Z, Vector::X));
+    let up = capsule(1.0, 0.5, 10.0, 32, [false, true])
+        .transform(&Transform::rotation(Vector::Z, 0.618034 * PI / 16.0).then(&corner));
+    let ball = sphere(1.5, 32, 16).transform(
+        &Transform::rotation(Vector::new(0.4, 0.7, 1.3), 0.075 * PI / 16.0).then(&corner),
+    );
+    let result = union(&union(&along, &up).mesh, &ball);
+    assert!(open_edges(&result.mesh.triangles).is_empty());
+    assert_eq!(result.provenance.len(), result.mesh.triangles.len());
+}
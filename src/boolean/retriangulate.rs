@@ -0,0 +1,214 @@
+use crate::boolean::intersect::{projection, Intersections};
+use crate::geometry::polygon;
+use std::collections::{HashMap, HashSet};
+
+// Splits a triangle of one mesh along the points on its edges and the intersection segments
+// crossing it. Returns the fragments as point ids, and adds every piece of a segment to
+// `constraints` so that fragments on either side of the intersection are told apart.
+pub(crate) fn retriangulate(
+    ix: &Intersections,
+    mesh: usize,
+    t: usize,
+    constraints: &mut HashSet<(usize, usize)>,
+) -> Vec<[usize; 3]> {
+    let corners = ix.meshes[mesh].triangles[t].indices();
+    let positions = corners.map(|v| ix.meshes[mesh].vertices[v]);
+    let project = projection((positions[1] - positions[0]).cross(positions[2] - positions[0]));
+
+    // The boundary, going around the triangle through the points on each edge
+    let mut chain: Vec<(usize, usize)> = Vec::new();
+    for k in 0..3 {
+        let (a, b) = (corners[k], corners[(k + 1) % 3]);
+        let (from, to) = (positions[k], positions[(k + 1) % 3]);
+        chain.push((ix.vertex(mesh, a), k));
+        if let Some(points) = ix.edge_points[mesh].get(&(a.min(b), a.max(b))) {
+            let mut points: Vec<(f64, usize)> = points
+                .iter()
+                .map(|&id| ((ix.points[id] - from).dot(to - from), ix.find(id)))
+                .collect();
+            points.sort_by(|p, q| p.0.partial_cmp(&q.0).unwrap());
+            chain.extend(points.into_iter().map(|(_, id)| (id, k)));
+        }
+    }
+    chain.dedup_by_key(|&mut (id, _)| id);
+    while chain.len() > 1 && chain[0].0 == chain[chain.len() - 1].0 {
+        chain.pop();
+    }
+
+    // Local numbering of every point involved
+    let mut ids: Vec<usize> = Vec::new();
+    let mut local: HashMap<usize, usize> = HashMap::new();
+    let mut index = |id: usize, ids: &mut Vec<usize>| {
+        *local.entry(id).or_insert_with(|| {
+            ids.push(id);
+            ids.len() - 1
+        })
+    };
+
+    // Which edges of the triangle each boundary point is on. Corners are on two.
+    let ends = corners.map(|v| ix.vertex(mesh, v));
+    let mut on_edges: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &(id, k) in chain.iter() {
+        on_edges.entry(id).or_default().push(k);
+    }
+    for (k, &id) in ends.iter().enumerate() {
+        on_edges.entry(id).or_default().push((k + 2) % 3);
+    }
+
+    let boundary: Vec<usize> = chain.iter().map(|&(id, _)| index(id, &mut ids)).collect();
+    let mut graph: HashSet<(usize, usize)> = HashSet::new();
+    for i in 0..boundary.len() {
+        let (a, b) = (boundary[i], boundary[(i + 1) % boundary.len()]);
+        graph.insert((a.min(b), a.max(b)));
+    }
+
+    let mut inside = false;
+    for &(p, q) in ix.segments[mesh].get(&t).into_iter().flatten() {
+        let (p, q) = (ix.find(p), ix.find(q));
+        if p == q {
+            continue;
+        }
+        let shared = match (on_edges.get(&p), on_edges.get(&q)) {
+            (Some(ep), Some(eq)) => ep.iter().find(|k| eq.contains(k)).copied(),
+            _ => None,
+        };
+        match shared {
+            // Along the boundary, where it already is an edge (or a run of them)
+            Some(k) => {
+                // The run of boundary points from corner k to corner k + 1
+                let mut run: Vec<usize> = chain
+                    .iter()
+                    .filter(|&&(_, e)| e == k)
+                    .map(|&(id, _)| id)
+                    .collect();
+                if run.first() != Some(&ends[k]) {
+                    run.insert(0, ends[k]);
+                }
+                if run.last() != Some(&ends[(k + 1) % 3]) {
+                    run.push(ends[(k + 1) % 3]);
+                }
+                let (i, j) = (
+                    run.iter().position(|&id| id == p),
+                    run.iter().position(|&id| id == q),
+                );
+                if let (Some(i), Some(j)) = (i, j) {
+                    for pair in run[i.min(j)..=i.max(j)].windows(2) {
+                        constraints.insert((pair[0].min(pair[1]), pair[0].max(pair[1])));
+                    }
+                }
+            }
+            None => {
+                let (a, b) = (index(p, &mut ids), index(q, &mut ids));
+                graph.insert((a.min(b), a.max(b)));
+                constraints.insert((p.min(q), p.max(q)));
+                inside = true;
+            }
+        }
+    }
+
+    let points: Vec<[f64; 2]> = ids
+        .iter()
+        .map(|&id| {
+            let c = project(ix.points[id]);
+            [c.x, c.y]
+        })
+        .collect();
+    let global = |triangles: Vec<[usize; 3]>| -> Vec<[usize; 3]> {
+        triangles.into_iter().map(|f| f.map(|i| ids[i])).collect()
+    };
+    if !inside {
+        return global(polygon::triangulate(&points, &boundary, &[]));
+    }
+
+    let faces = trace_faces(&points, &graph);
+    let outside: HashSet<(usize, usize)> = (0..boundary.len())
+        .map(|i| (boundary[(i + 1) % boundary.len()], boundary[i]))
+        .collect();
+
+    // Each point's connected piece of the graph
+    let mut parent: Vec<usize> = (0..points.len()).collect();
+    fn find(parent: &[usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            i = parent[i];
+        }
+        i
+    }
+    for &(a, b) in graph.iter() {
+        let (a, b) = (find(&parent, a), find(&parent, b));
+        parent[a.max(b)] = a.min(b);
+    }
+
+    let areas: Vec<f64> = faces
+        .iter()
+        .map(|f| polygon::signed_area(&points, f))
+        .collect();
+    let outers: Vec<usize> = (0..faces.len()).filter(|&f| areas[f] > 0.0).collect();
+    let mut holes: HashMap<usize, Vec<usize>> = HashMap::new();
+    for f in (0..faces.len()).filter(|&f| areas[f] < 0.0) {
+        let face = &faces[f];
+        if outside.contains(&(face[0], face[1 % face.len()])) {
+            continue;
+        }
+        // A loop of segments not joined to the rest, inside one of the other faces
+        let piece = find(&parent, face[0]);
+        let probe = points[face[0]];
+        let owner = outers
+            .iter()
+            .filter(|&&o| find(&parent, faces[o][0]) != piece)
+            .filter(|&&o| polygon::contains(&points, &faces[o], probe))
+            .min_by(|&&a, &&b| areas[a].partial_cmp(&areas[b]).unwrap());
+        if let Some(&o) = owner {
+            holes.entry(o).or_default().push(f);
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for &o in outers.iter() {
+        let face_holes: Vec<&[usize]> = holes
+            .get(&o)
+            .into_iter()
+            .flatten()
+            .map(|&h| faces[h].as_slice())
+            .collect();
+        triangles.extend(polygon::triangulate(&points, &faces[o], &face_holes));
+    }
+    global(triangles)
+}
+
+// The faces of a planar graph, each going around with the face on its left. Bounded faces
+// come out counter-clockwise and the outside of each connected piece clockwise.
+fn trace_faces(points: &[[f64; 2]], graph: &HashSet<(usize, usize)>) -> Vec<Vec<usize>> {
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+    for &(a, b) in graph.iter() {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+    for (v, list) in neighbours.iter_mut().enumerate() {
+        let angle = |w: &usize| (points[*w][1] - points[v][1]).atan2(points[*w][0] - points[v][0]);
+        list.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+    }
+
+    let mut edges: Vec<(usize, usize)> =
+        graph.iter().flat_map(|&(a, b)| [(a, b), (b, a)]).collect();
+    edges.sort_unstable();
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
+    let mut faces = Vec::new();
+    for &start in edges.iter() {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut face = Vec::new();
+        let (mut u, mut v) = start;
+        while visited.insert((u, v)) {
+            face.push(u);
+            // Turn as far right as possible, keeping the face on the left
+            let list = &neighbours[v];
+            let i = list.iter().position(|&w| w == u).unwrap();
+            let w = list[(i + list.len() - 1) % list.len()];
+            u = v;
+            v = w;
+        }
+        faces.push(face);
+    }
+    faces
+}
@@ -2,11 +2,13 @@ use crate::error::Error;
 use serde::Deserialize;
 use std::str::FromStr;
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Unit {
     Meter,
     Centimeter,
+    // 3MF files without a unit are in millimeters
+    #[default]
     Millimeter,
     Foot,
     Inch,
@@ -24,6 +26,18 @@ impl Unit {
             _ => Self::Unknown,
         }
     }
+
+    // The unit's name in a 3MF file
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Meter => Some("meter"),
+            Self::Centimeter => Some("centimeter"),
+            Self::Millimeter => Some("millimeter"),
+            Self::Foot => Some("foot"),
+            Self::Inch => Some("inch"),
+            Self::Unknown => None,
+        }
+    }
 }
 
 impl FromStr for Unit {
@@ -0,0 +1,338 @@
+use crate::analysis::{evaluate_orientation, OrientationOptions};
+use crate::cut::{cut_mesh, Side, PLANE_TOLERANCE};
+use crate::geometry::{Plane, Point, Transform, Vector};
+use crate::threemf::Mesh;
+use serde::Serialize;
+
+// Cutting a model with several planes, keeping track of how the pieces go back together
+
+// A cut face found on a piece: the plane's index, the piece's side and the face's triangles
+type Face = (usize, Side, Vec<[Point; 3]>);
+
+#[derive(Debug, Clone)]
+pub struct AssemblyOptions {
+    // Gap left between pieces when they are laid out for printing
+    pub spacing: f64,
+}
+
+impl Default for AssemblyOptions {
+    fn default() -> Self {
+        AssemblyOptions { spacing: 5.0 }
+    }
+}
+
+// A flat face left on a piece by one of the cuts
+#[derive(Debug, Clone, Serialize)]
+pub struct CutFace {
+    // Index of the cutting plane
+    pub cut: usize,
+    // Side of the plane the piece is on
+    pub side: Side,
+    pub area: f64,
+    // Pieces on the other side of the plane that this face touches
+    pub mates: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PieceEntry {
+    pub id: usize,
+    pub volume: f64,
+    // Bounds in the original position, as (min, max)
+    pub bounds: (Point, Point),
+    pub faces: Vec<CutFace>,
+    // Moves the laid out piece back to where it was in the model
+    pub transform: Transform,
+}
+
+// Everything needed to put the pieces back together, meant to be written out as JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub cuts: Vec<Plane>,
+    pub pieces: Vec<PieceEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    // The pieces laid out for printing, each resting on its largest cut face, in a row along X
+    pub pieces: Vec<Mesh>,
+    pub manifest: Manifest,
+}
+
+// Cuts a closed mesh with each plane in turn. A plane only cuts the pieces it goes through, so
+// a plane meant for one part of the model can be placed without splitting the rest.
+pub fn cut_assembly(mesh: &Mesh, planes: &[Plane], options: &AssemblyOptions) -> Assembly {
+    let tolerance = PLANE_TOLERANCE * mesh.size().max(f64::MIN_POSITIVE);
+
+    // Pieces in their original position, with the cuts that went through their parents
+    let mut pieces: Vec<(Mesh, Vec<(usize, Side)>)> = vec![(mesh.clone(), Vec::new())];
+    for (k, plane) in planes.iter().enumerate() {
+        let mut next = Vec::with_capacity(pieces.len());
+        for (piece, cuts) in pieces {
+            let distances = piece.vertices.iter().map(|&p| plane.distance(p));
+            let (low, high) = distances.fold((0.0f64, 0.0f64), |(l, h), d| (l.min(d), h.max(d)));
+            if low >= -tolerance || high <= tolerance {
+                next.push((piece, cuts));
+                continue;
+            }
+
+            let result = cut_mesh(&piece, plane);
+            for (side, parts) in [(Side::Above, result.above), (Side::Below, result.below)] {
+                for part in parts {
+                    let mut cuts = cuts.clone();
+                    cuts.push((k, side));
+                    next.push((part, cuts));
+                }
+            }
+        }
+        pieces = next;
+    }
+
+    // Faces each piece has on the planes that cut it. A cut from further up the tree may have
+    // been cut away from the piece entirely.
+    let faces: Vec<Vec<Face>> = pieces
+        .iter()
+        .map(|(piece, cuts)| {
+            cuts.iter()
+                .map(|&(k, side)| (k, side, cut_face(piece, &planes[k], side, tolerance)))
+                .filter(|(_, _, triangles)| !triangles.is_empty())
+                .collect()
+        })
+        .collect();
+
+    let mut entries = Vec::with_capacity(pieces.len());
+    let mut laid_out = Vec::with_capacity(pieces.len());
+    let mut x = 0.0;
+    for (id, (piece, _)) in pieces.iter().enumerate() {
+        let mut piece_faces = Vec::new();
+        for (k, side, triangles) in faces[id].iter() {
+            let plane = &planes[*k];
+            let mates = (0..pieces.len())
+                .filter(|&other| {
+                    faces[other].iter().any(|(j, s, theirs)| {
+                        j == k && *s == side.opposite() && overlap(plane, triangles, theirs)
+                    })
+                })
+                .collect();
+            piece_faces.push(CutFace {
+                cut: *k,
+                side: *side,
+                area: triangles.iter().map(|&t| triangle_area(t)).sum(),
+                mates,
+            });
+        }
+
+        // Rest the piece on its largest cut face, which is flat, then move it along the row
+        let down = piece_faces
+            .iter()
+            .max_by(|a, b| a.area.total_cmp(&b.area))
+            .map(|face| match face.side {
+                Side::Above => -planes[face.cut].normal,
+                Side::Below => planes[face.cut].normal,
+            })
+            .unwrap_or(-Vector::Z);
+        let orientation = evaluate_orientation(piece, down, &OrientationOptions::default());
+        let (min, max) = orientation.mesh.bounds();
+        let shift = Transform::translation(Vector::new(x - min.x, -min.y, 0.0));
+        x += max.x - min.x + options.spacing;
+        let placement = orientation.transform.then(&shift);
+
+        entries.push(PieceEntry {
+            id,
+            volume: piece.volume(),
+            bounds: piece.bounds(),
+            faces: piece_faces,
+            transform: placement.inverse().unwrap_or_else(Transform::identity),
+        });
+        laid_out.push(piece.transform(&placement));
+    }
+
+    Assembly {
+        pieces: laid_out,
+        manifest: Manifest {
+            cuts: planes.to_vec(),
+            pieces: entries,
+        },
+    }
+}
+
+fn triangle_area([a, b, c]: [Point; 3]) -> f64 {
+    (b - a).cross(c - a).len() / 2.0
+}
+
+// Triangles of a piece lying in the plane and facing away from the piece's side
+fn cut_face(piece: &Mesh, plane: &Plane, side: Side, tolerance: f64) -> Vec<[Point; 3]> {
+    let outward = match side {
+        Side::Above => -plane.normal,
+        Side::Below => plane.normal,
+    };
+    piece
+        .triangles
+        .iter()
+        .map(|t| piece.triangle_points(t))
+        .filter(|points| {
+            let [a, b, c] = *points;
+            points.iter().all(|&p| plane.distance(p).abs() <= tolerance)
+                && (b - a).cross(c - a).dot(outward) > 0.0
+        })
+        .collect()
+}
+
+// Whether two sets of triangles in a plane cover some of the same area, judged by the middle
+// of each triangle of one set landing strictly inside a triangle of the other. Triangles with
+// no area cover nothing.
+fn overlap(plane: &Plane, ours: &[[Point; 3]], theirs: &[[Point; 3]]) -> bool {
+    let (u, v) = plane.basis();
+    let flat = |p: Point| [(p - plane.point).dot(u), (p - plane.point).dot(v)];
+    let flatten = |triangles: &[[Point; 3]]| {
+        let mut flat: Vec<[[f64; 2]; 3]> = triangles
+            .iter()
+            .map(|t| t.map(flat))
+            .filter(|&[a, b, c]| {
+                let area = cross(a, b, c).abs();
+                let longest = [(a, b), (b, c), (c, a)]
+                    .iter()
+                    .map(|(p, q)| (q[0] - p[0]).powi(2) + (q[1] - p[1]).powi(2))
+                    .fold(0.0, f64::max);
+                area > 1e-12 * longest
+            })
+            .collect();
+        // By the left of their bounds, so a point only needs the triangles starting before it
+        flat.sort_by(|s, t| left(s).total_cmp(&left(t)));
+        flat
+    };
+    let (ours, theirs) = (flatten(ours), flatten(theirs));
+
+    let covers = |a: &[[[f64; 2]; 3]], b: &[[[f64; 2]; 3]]| {
+        a.iter().any(|&[p, q, r]| {
+            let m = [(p[0] + q[0] + r[0]) / 3.0, (p[1] + q[1] + r[1]) / 3.0];
+            let end = b.partition_point(|t| left(t) < m[0]);
+            b[..end].iter().any(|&[a, b, c]| {
+                let s = [cross(a, b, m), cross(b, c, m), cross(c, a, m)];
+                s.iter().all(|&x| x > 0.0) || s.iter().all(|&x| x < 0.0)
+            })
+        })
+    };
+    covers(&ours, &theirs) || covers(&theirs, &ours)
+}
+
+// Twice the signed area of the triangle pqr
+fn cross(p: [f64; 2], q: [f64; 2], r: [f64; 2]) -> f64 {
+    (q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0])
+}
+
+fn left(t: &[[f64; 2]; 3]) -> f64 {
+    t[0][0].min(t[1][0]).min(t[2][0])
+}
+
+#[test]
+fn test_cut_assembly() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let planes = [
+        Plane::new(Vector::Z, Point::zero()),
+        Plane::new(Vector::X, Point::zero()),
+        // Misses the cube
+        Plane::new(Vector::Y, Point::new(0.0, 5.0, 0.0)),
+    ];
+    let assembly = cut_assembly(mesh, &planes, &AssemblyOptions::default());
+    let manifest = &assembly.manifest;
+
+    assert_eq!(assembly.pieces.len(), 4);
+    for (piece, entry) in assembly.pieces.iter().zip(manifest.pieces.iter()) {
+        assert!((entry.volume - 2.0).abs() < 1e-9);
+        // Laid out on the bed, and back in place with the transform
+        assert!(piece.bounds().0.z.abs() < 1e-9);
+        let (min, max) = piece.transform(&entry.transform).bounds();
+        assert!((min - entry.bounds.0).len() < 1e-9 && (max - entry.bounds.1).len() < 1e-9);
+
+        // A quarter of the cube has a cut face on each plane through it, each touching the
+        // quarter next to it across that plane
+        assert_eq!(entry.faces.len(), 2);
+        for face in entry.faces.iter() {
+            assert!((face.area - 2.0).abs() < 1e-9);
+            assert_eq!(face.mates.len(), 1);
+            let mate = &manifest.pieces[face.mates[0]];
+            assert!(mate
+                .faces
+                .iter()
+                .any(|f| f.cut == face.cut && f.mates == vec![entry.id]));
+        }
+    }
+
+    let json = serde_json::to_string(manifest).unwrap();
+    assert!(json.contains("\"mates\""));
+
+    // Faces only mate where they cover some of the same area
+    let plane = Plane::new(Vector::Z, Point::zero());
+    let p = |x: f64, y: f64| Point::new(x, y, 0.0);
+    let square = [
+        [p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)],
+        [p(0.0, 0.0), p(1.0, 1.0), p(0.0, 1.0)],
+    ];
+    assert!(overlap(&plane, &square, &square));
+    assert!(!overlap(
+        &plane,
+        &square,
+        &[[p(5.0, 5.0), p(6.0, 5.0), p(6.0, 6.0)]]
+    ));
+    // Next to it, along an edge
+    assert!(!overlap(
+        &plane,
+        &square,
+        &[[p(1.0, 0.0), p(2.0, 0.0), p(2.0, 1.0)]]
+    ));
+    // A triangle with no area, across it
+    assert!(!overlap(
+        &plane,
+        &square,
+        &[[p(0.0, 0.0), p(1.0, 1.0), p(2.0, 2.0)]]
+    ));
+}
+
+#[test]
+fn test_cut_assembly_frame() {
+    use crate::cut::{assert_pieces_make_up, frame};
+
+    // The first cut leaves four separate corner posts in front of the plate, and the second
+    // splits what is behind them
+    let mesh = &frame();
+    let planes = [
+        Plane::new(Vector::Y, Point::new(0.0, -2.0, 0.0)),
+        Plane::new(Vector::X, Point::zero()),
+    ];
+    let assembly = cut_assembly(mesh, &planes, &AssemblyOptions::default());
+    let manifest = &assembly.manifest;
+
+    assert_eq!(assembly.pieces.len(), 6);
+    assert_pieces_make_up(mesh, assembly.pieces.iter(), 0.0);
+
+    for entry in manifest.pieces.iter() {
+        for face in entry.faces.iter() {
+            // Faces meet their mates across the same cut, both ways round
+            for &m in face.mates.iter() {
+                let mate = &manifest.pieces[m];
+                assert!(mate.faces.iter().any(|f| f.cut == face.cut
+                    && f.side != face.side
+                    && f.mates.contains(&entry.id)));
+            }
+        }
+    }
+
+    // Each post sits on half of one of the back pieces' faces
+    let posts: Vec<&PieceEntry> = manifest
+        .pieces
+        .iter()
+        .filter(|p| p.faces.len() == 1)
+        .collect();
+    assert_eq!(posts.len(), 4);
+    for post in posts {
+        let face = &post.faces[0];
+        assert_eq!((face.cut, face.side, face.mates.len()), (0, Side::Above, 1));
+        let back = &manifest.pieces[face.mates[0]];
+        let under = back.faces.iter().find(|f| f.cut == 0).unwrap();
+        assert_eq!(under.mates.len(), 2);
+        assert!((under.area - 2.0 * face.area).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::cut::{CutResult, PlaneCut, Section, Side};
+use crate::geometry::polygon;
+use crate::geometry::{Plane, Point};
+use crate::threemf::Mesh;
+
+// Grid resolution used when searching a section for dowel positions
+const PLACEMENT_STEPS: usize = 48;
+// Number of cross-sections, evenly spaced down to the bottom of a hole, that must have room
+// for the dowel
+const DEPTH_CHECKS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DowelStyle {
+    // Holes in both pieces, for separate pins
+    Holes,
+    // A peg on the given side fitting into a hole on the other
+    Peg(Side),
+}
+
+#[derive(Debug, Clone)]
+pub struct DowelOptions {
+    pub style: DowelStyle,
+    // Diameter of the pin or peg
+    pub diameter: f64,
+    // How far a dowel reaches into each piece
+    pub depth: f64,
+    // Gap left around the dowel in its holes, on the radius and at the bottom
+    pub clearance: f64,
+    // Maximum number of dowels in each separate region of the cross-section
+    pub count: usize,
+    // Minimum wall left between a hole and the outside of the piece or another hole
+    pub margin: f64,
+    // Number of sides used for the round outlines
+    pub segments: usize,
+}
+
+impl Default for DowelOptions {
+    fn default() -> Self {
+        DowelOptions {
+            style: DowelStyle::Holes,
+            diameter: 5.0,
+            depth: 8.0,
+            clearance: 0.15,
+            count: 2,
+            margin: 2.0,
+            segments: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DowelCut {
+    pub cut: CutResult,
+    // Where each dowel's axis crosses the cutting plane
+    pub dowels: Vec<Point>,
+}
+
+// Cuts a mesh along a plane like `cut_mesh`, adding alignment dowels to the cut faces.
+// Dowels are placed as far from the edges of the cross-section as possible, and only where
+// there is enough material around them at the cut and at a few depths down their holes (see
+// DEPTH_CHECKS). A feature thinner than the spacing of those can still be missed.
+pub fn cut_with_dowels(mesh: &Mesh, plane: &Plane, options: &DowelOptions) -> DowelCut {
+    let mut cut = PlaneCut::new(mesh, plane);
+
+    let pin_radius = options.diameter / 2.0;
+    let hole_radius = pin_radius + options.clearance;
+    let hole_depth = options.depth + options.clearance;
+    let hole_sides: Vec<Side> = match options.style {
+        DowelStyle::Holes => vec![Side::Above, Side::Below],
+        DowelStyle::Peg(side) => vec![side.opposite()],
+    };
+
+    // The cap, and cross-sections down to the bottom of each hole, must hold the dowel
+    let mut checks: Vec<Section> = vec![cut.cap(Side::Above).clone()];
+    for side in hole_sides.iter() {
+        for k in 1..=DEPTH_CHECKS {
+            let depth = hole_depth * k as f64 / DEPTH_CHECKS as f64;
+            let offset = match side {
+                Side::Above => plane.normal * depth,
+                Side::Below => plane.normal * -depth,
+            };
+            let at = Plane::new(plane.normal, plane.point + offset);
+            checks.push(PlaneCut::new(mesh, &at).section);
+        }
+    }
+
+    let centers = place_dowels(&cut.section, &checks, hole_radius, options);
+    let dowels: Vec<Point> = centers.iter().map(|&q| cut.section.unproject(q)).collect();
+
+    for &center in centers.iter() {
+        let hole = circle(&cut.section, center, hole_radius, options.segments);
+        for &side in hole_sides.iter() {
+            cut.add_relief(side, &hole, &[], -hole_depth);
+        }
+        if let DowelStyle::Peg(side) = options.style {
+            let peg = circle(&cut.section, center, pin_radius, options.segments);
+            cut.add_relief(side, &peg, &[], options.depth);
+        }
+    }
+
+    DowelCut {
+        cut: cut.finish(),
+        dowels,
+    }
+}
+
+// Greedily picks dowel centers (in the section's 2D frame), each one as far as possible from
+// the section's boundary and from the dowels already placed
+fn place_dowels(
+    section: &Section,
+    checks: &[Section],
+    radius: f64,
+    options: &DowelOptions,
+) -> Vec<[f64; 2]> {
+    let points = section.points_2d();
+    let check_points: Vec<Vec<[f64; 2]>> = checks.iter().map(|c| c.points_2d()).collect();
+    let needed = radius + options.margin;
+    let mut placed: Vec<[f64; 2]> = Vec::new();
+
+    for (outer, holes) in polygon::group_loops(&points, &section.loops) {
+        let ring = &section.loops[outer];
+        let (mut lo, mut hi) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for &i in ring.iter() {
+            for k in 0..2 {
+                lo[k] = lo[k].min(points[i][k]);
+                hi[k] = hi[k].max(points[i][k]);
+            }
+        }
+        let step = (hi[0] - lo[0]).max(hi[1] - lo[1]) / PLACEMENT_STEPS as f64;
+        if step <= 0.0 {
+            continue;
+        }
+
+        // Grid points with room for a dowel, with their distance to the boundary
+        let mut candidates: Vec<([f64; 2], f64)> = Vec::new();
+        for i in 0..=PLACEMENT_STEPS {
+            for j in 0..=PLACEMENT_STEPS {
+                let q = [lo[0] + step * i as f64, lo[1] + step * j as f64];
+                if !polygon::contains(&points, ring, q)
+                    || holes
+                        .iter()
+                        .any(|&h| polygon::contains(&points, &section.loops[h], q))
+                {
+                    continue;
+                }
+
+                let clearance = std::iter::once(outer)
+                    .chain(holes.iter().copied())
+                    .map(|l| polygon::boundary_distance(&points, &section.loops[l], q))
+                    .fold(f64::INFINITY, f64::min);
+                let p = section.unproject(q);
+                let supported = checks
+                    .iter()
+                    .zip(check_points.iter())
+                    .all(|(check, points)| {
+                        let r = check.project(p);
+                        check.contains_in(points, r)
+                            && check.boundary_distance_in(points, r) >= needed
+                    });
+                if clearance >= needed && supported {
+                    candidates.push((q, clearance));
+                }
+            }
+        }
+
+        let first = placed.len();
+        for _ in 0..options.count {
+            let room = |&(q, clearance): &([f64; 2], f64)| {
+                placed[first..]
+                    .iter()
+                    .map(|c| ((q[0] - c[0]).powi(2) + (q[1] - c[1]).powi(2)).sqrt() - radius)
+                    .fold(clearance, f64::min)
+                    - radius
+            };
+            let best = candidates
+                .iter()
+                .map(|c| (c.0, room(c)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            match best {
+                Some((q, room)) if room >= options.margin => placed.push(q),
+                _ => break,
+            }
+        }
+    }
+
+    placed
+}
+
+fn circle(section: &Section, center: [f64; 2], radius: f64, segments: usize) -> Vec<Point> {
+    (0..segments.max(3))
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / segments.max(3) as f64;
+            section.unproject([
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ])
+        })
+        .collect()
+}
+
+#[test]
+fn test_cut_with_dowels() {
+    use crate::cut::open_edges;
+    use crate::geometry::Vector;
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let plane = Plane::new(Vector::Z, Point::new(0.0, 0.0, 0.0));
+
+    let options = DowelOptions {
+        style: DowelStyle::Peg(Side::Above),
+        diameter: 0.4,
+        depth: 0.3,
+        clearance: 0.05,
+        count: 2,
+        margin: 0.3,
+        segments: 16,
+    };
+    let result = cut_with_dowels(mesh, &plane, &options);
+    let cut = &result.cut;
+
+    // Only one dowel fits with that much wall around it
+    assert_eq!(result.dowels.len(), 1);
+    assert!((result.dowels[0] - Point::zero()).len() < 1e-9);
+    assert_eq!(cut.num_pieces(), 2);
+    for piece in cut.above.iter().chain(cut.below.iter()) {
+        assert!(open_edges(&piece.triangles).is_empty());
+    }
+
+    // A regular polygon's area is n r^2 sin(2 pi / n) / 2
+    let area = |r: f64| 8.0 * r * r * (std::f64::consts::TAU / 16.0).sin();
+    let peg = area(0.2) * 0.3;
+    let hole = area(0.25) * 0.35;
+    assert!((cut.above[0].volume() - (4.0 + peg)).abs() < 1e-9);
+    assert!((cut.below[0].volume() - (4.0 - hole)).abs() < 1e-9);
+}
+
+#[test]
+fn test_dowels_in_frame() {
+    use crate::cut::{assert_pieces_make_up, cut_mesh, frame};
+    use crate::geometry::Vector;
+
+    // Across a plate with cut-outs, where most of the cross-section is thin walls and posts
+    let mesh = &frame();
+    let plane = Plane::new(Vector::Y, Point::new(0.0, -6.0, 0.0));
+    let options = DowelOptions {
+        diameter: 1.5,
+        depth: 2.5,
+        clearance: 0.1,
+        margin: 0.5,
+        ..DowelOptions::default()
+    };
+    let result = cut_with_dowels(mesh, &plane, &options);
+    let cut = &result.cut;
+    assert!(!result.dowels.is_empty());
+
+    // No hole breaks out of the piece anywhere along its length
+    let hole_radius = options.diameter / 2.0 + options.clearance;
+    let hole_depth = options.depth + options.clearance;
+    for k in -16..=16 {
+        let offset = Vector::Y * (hole_depth * k as f64 / 16.0);
+        let section = cut_mesh(mesh, &Plane::new(Vector::Y, plane.point + offset)).section;
+        for &dowel in result.dowels.iter() {
+            let q = section.project(dowel + offset);
+            assert!(section.contains(q) && section.boundary_distance(q) > hole_radius);
+        }
+    }
+
+    // Each dowel takes a hole out of both pieces
+    let n = options.segments as f64;
+    let hole = n / 2.0 * hole_radius.powi(2) * (std::f64::consts::TAU / n).sin() * hole_depth;
+    let removed = 2.0 * hole * result.dowels.len() as f64;
+    assert_pieces_make_up(mesh, cut.above.iter().chain(cut.below.iter()), removed);
+}
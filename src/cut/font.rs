@@ -0,0 +1,241 @@
+use crate::geometry::polygon;
+use std::collections::{HashMap, HashSet};
+
+// A built-in 5x7 block font, so labels need no system fonts. Each glyph is seven rows of
+// five cells, top row first, with the leftmost cell in the highest bit.
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+// Empty columns between glyphs
+const SPACING: usize = 1;
+// How far outlines are pulled in from the cell edges, as a fraction of a cell. Cells touching
+// only at a corner end up apart.
+const INSET: f64 = 0.08;
+
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+];
+
+fn glyph(c: char) -> Option<&'static [u8; 7]> {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().find(|(g, _)| *g == c).map(|(_, rows)| rows)
+}
+
+// Size of a line of text in cells, as (width, height)
+pub fn text_size(text: &str) -> (f64, f64) {
+    let n = text.chars().count();
+    let width = (n * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+    (width as f64, GLYPH_HEIGHT as f64)
+}
+
+// Whether each cell of a line of text is filled, indexed [column][row] from the bottom left
+fn cells(text: &str) -> Vec<[bool; GLYPH_HEIGHT]> {
+    let (width, _) = text_size(text);
+    let mut columns = vec![[false; GLYPH_HEIGHT]; width as usize];
+    for (i, c) in text.chars().enumerate() {
+        // Characters the font does not have are left blank
+        let rows = match glyph(c) {
+            Some(rows) => rows,
+            None => continue,
+        };
+        for (r, bits) in rows.iter().enumerate() {
+            for k in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - k)) != 0 {
+                    columns[i * (GLYPH_WIDTH + SPACING) + k][GLYPH_HEIGHT - 1 - r] = true;
+                }
+            }
+        }
+    }
+    columns
+}
+
+// A polygon as its outer loop and its holes
+pub type Outline = (Vec<[f64; 2]>, Vec<Vec<[f64; 2]>>);
+
+// Outlines of a line of text, in cells from its bottom left corner. Outer loops are
+// counter-clockwise and holes clockwise. No two outlines touch.
+pub fn text_outlines(text: &str) -> Vec<Outline> {
+    let columns = cells(text);
+    let filled = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < columns.len()
+            && (y as usize) < GLYPH_HEIGHT
+            && columns[x as usize][y as usize]
+    };
+
+    // Cell edges between filled and empty cells, with the filled cell on the left
+    let mut outgoing: HashMap<(i64, i64), Vec<(i64, i64)>> = HashMap::new();
+    for x in 0..columns.len() as i64 {
+        for y in 0..GLYPH_HEIGHT as i64 {
+            if !filled(x, y) {
+                continue;
+            }
+            let sides = [
+                ((x, y), (x + 1, y), filled(x, y - 1)),
+                ((x + 1, y), (x + 1, y + 1), filled(x + 1, y)),
+                ((x + 1, y + 1), (x, y + 1), filled(x, y + 1)),
+                ((x, y + 1), (x, y), filled(x - 1, y)),
+            ];
+            for (from, to, shared) in sides {
+                if !shared {
+                    outgoing.entry(from).or_default().push(to);
+                }
+            }
+        }
+    }
+
+    // Follow the edges around. Where two cells meet only at a corner, turn left to stay
+    // around the same cell, which pairs up the edges in and out of every corner.
+    let next = |(from, to): ((i64, i64), (i64, i64))| {
+        let d = (to.0 - from.0, to.1 - from.1);
+        let choices = &outgoing[&to];
+        [(-d.1, d.0), d, (d.1, -d.0)]
+            .iter()
+            .find_map(|t| {
+                choices
+                    .iter()
+                    .find(|&&n| (n.0 - to.0, n.1 - to.1) == *t)
+                    .map(|&n| (to, n))
+            })
+            .unwrap()
+    };
+    let mut edges: Vec<((i64, i64), (i64, i64))> = outgoing
+        .iter()
+        .flat_map(|(&from, list)| list.iter().map(move |&to| (from, to)))
+        .collect();
+    edges.sort_unstable();
+    let mut visited: HashSet<((i64, i64), (i64, i64))> = HashSet::new();
+    let mut loops: Vec<Vec<(i64, i64)>> = Vec::new();
+    for &first in edges.iter() {
+        let mut ring = Vec::new();
+        let mut edge = first;
+        while visited.insert(edge) {
+            ring.push(edge.0);
+            edge = next(edge);
+        }
+        if !ring.is_empty() {
+            loops.push(ring);
+        }
+    }
+
+    // Drop the corners in the middle of straight runs, then pull every edge in towards the
+    // filled side. Edges are all horizontal or vertical, so moving each corner along both of
+    // its edges' inward normals is exact.
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    let mut rings: Vec<Vec<usize>> = Vec::new();
+    for ring in loops.iter() {
+        let n = ring.len();
+        let corners: Vec<usize> = (0..n)
+            .filter(|&i| {
+                let (a, b, c) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+                (b.0 - a.0) * (c.1 - b.1) != (b.1 - a.1) * (c.0 - b.0)
+            })
+            .collect();
+        let m = corners.len();
+        let mut indices = Vec::with_capacity(m);
+        for k in 0..m {
+            let (a, b, c) = (
+                ring[corners[(k + m - 1) % m]],
+                ring[corners[k]],
+                ring[corners[(k + 1) % m]],
+            );
+            let unit = |d: i64| d.signum() as f64;
+            let (d1, d2) = (
+                (unit(b.0 - a.0), unit(b.1 - a.1)),
+                (unit(c.0 - b.0), unit(c.1 - b.1)),
+            );
+            // Left normals of the edges in and out
+            let (n1, n2) = ((-d1.1, d1.0), (-d2.1, d2.0));
+            indices.push(points.len());
+            points.push([
+                b.0 as f64 + INSET * (n1.0 + n2.0),
+                b.1 as f64 + INSET * (n1.1 + n2.1),
+            ]);
+        }
+        rings.push(indices);
+    }
+
+    polygon::group_loops(&points, &rings)
+        .into_iter()
+        .map(|(outer, holes)| {
+            let ring = |l: usize| rings[l].iter().map(|&i| points[i]).collect::<Vec<_>>();
+            (ring(outer), holes.into_iter().map(ring).collect())
+        })
+        .collect()
+}
+
+#[test]
+fn test_text_outlines() {
+    let area = |ring: &[[f64; 2]]| {
+        let indices: Vec<usize> = (0..ring.len()).collect();
+        polygon::signed_area(ring, &indices) / 2.0
+    };
+
+    // One outline of 20 cells around a hole
+    assert_eq!(text_size("#"), (5.0, 7.0));
+    let hash = text_outlines("#");
+    assert_eq!(hash.len(), 1);
+    let (outer, holes) = &hash[0];
+    assert_eq!(holes.len(), 1);
+    assert!(area(&holes[0]) < 0.0);
+    let filled = area(outer) + area(&holes[0]);
+    assert!(filled > 15.0 && filled < 20.0);
+
+    // Diagonal cells come apart, so "/" is five separate squares
+    let slash = text_outlines("/");
+    assert_eq!(slash.len(), 5);
+    for (outer, holes) in slash.iter() {
+        assert!(holes.is_empty());
+        assert!((area(outer) - (1.0 - 2.0 * INSET).powi(2)).abs() < 1e-12);
+    }
+
+    // Unknown characters are blank, and lowercase is drawn as uppercase
+    assert_eq!(text_size("AB"), (11.0, 7.0));
+    assert!(text_outlines("\u{e9} \u{e9}").is_empty());
+    assert_eq!(text_outlines("ab"), text_outlines("AB"));
+}
@@ -0,0 +1,196 @@
+use crate::cut::profile::{cut_profile_side, Profile};
+use crate::cut::{cut_mesh, CutResult, PlaneCut, Side};
+use crate::geometry::obb::oriented_bounding_box;
+use crate::geometry::{Plane, Vector};
+use crate::threemf::Mesh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JointKind {
+    // A tail flaring out from its root, so the pieces can only slide apart along the joint
+    Dovetail,
+    // A straight rectangular tongue
+    TongueAndGroove,
+    // A round head on a narrower neck, like a jigsaw piece
+    PuzzleKey,
+}
+
+#[derive(Debug, Clone)]
+pub struct JointOptions {
+    pub kind: JointKind,
+    // Width of the tongue, tail or neck where it leaves the cut plane
+    pub width: f64,
+    // How far the joint reaches past the cut plane. A puzzle key is never shallower than it
+    // is wide, so its head can be rounder than its neck; see `joint_depth`.
+    pub depth: f64,
+    // How far (in degrees) the sides of a dovetail lean out
+    pub angle: f64,
+    // Gap left between the two pieces all along the joint
+    pub clearance: f64,
+    // The piece the tongue, tail or key sticks out of
+    pub side: Side,
+    // Direction the joint runs in across the cut. Defaults to the longest extent of the
+    // cross-section.
+    pub direction: Option<Vector>,
+    // Number of sides used for the head of a puzzle key
+    pub segments: usize,
+}
+
+impl Default for JointOptions {
+    fn default() -> Self {
+        JointOptions {
+            kind: JointKind::Dovetail,
+            width: 10.0,
+            depth: 6.0,
+            angle: 15.0,
+            clearance: 0.2,
+            side: Side::Below,
+            direction: None,
+            segments: 24,
+        }
+    }
+}
+
+impl JointOptions {
+    // How far the joint actually reaches past the cut plane: `depth`, raised to `width` for a
+    // puzzle key
+    pub fn joint_depth(&self) -> f64 {
+        match self.kind {
+            JointKind::PuzzleKey => self.depth.max(self.width),
+            _ => self.depth,
+        }
+    }
+
+    // The joint's outline across the cut, as (across, along the plane's normal) coordinates,
+    // for a tongue sticking out of the piece below
+    fn outline(&self) -> Vec<[f64; 2]> {
+        let (w, d) = (self.width / 2.0, self.joint_depth());
+        let mut points = match self.kind {
+            JointKind::Dovetail => {
+                let flare = w + d * self.angle.to_radians().tan();
+                vec![[-w, 0.0], [-flare, d], [flare, d], [w, 0.0]]
+            }
+            JointKind::TongueAndGroove => vec![[-w, 0.0], [-w, d], [w, d], [w, 0.0]],
+            JointKind::PuzzleKey => {
+                // The head is half as wide again as the neck, or as wide as fits in the
+                // depth
+                let radius = (1.5 * w).min((d * d + w * w) / (2.0 * d));
+                let center = d - radius;
+                let start = (w / radius).acos();
+                let sweep = std::f64::consts::PI + 2.0 * start;
+                let n = self.segments.max(3);
+                (0..=n)
+                    .map(|i| {
+                        let angle = std::f64::consts::PI + start - sweep * i as f64 / n as f64;
+                        [radius * angle.cos(), center + radius * angle.sin()]
+                    })
+                    .collect()
+            }
+        };
+        if self.kind == JointKind::PuzzleKey && points[0][1] > 1e-9 * d {
+            points.insert(0, [-w, 0.0]);
+            points.push([w, 0.0]);
+        }
+
+        // Flat runs out to either side, which the cut extends to infinity
+        let reach = points.iter().map(|p| p[0].abs()).fold(0.0, f64::max) + self.width;
+        points.insert(0, [-reach, 0.0]);
+        points.push([reach, 0.0]);
+        points
+    }
+}
+
+// Cuts a mesh along a plane like `cut_mesh`, with an interlocking joint running across the
+// cross-section. Each piece's cut face is moved back by half the clearance, so the pieces
+// stay closed and leave an even gap between them.
+pub fn cut_with_joint(mesh: &Mesh, plane: &Plane, options: &JointOptions) -> CutResult {
+    let section = PlaneCut::new(mesh, plane).section;
+    if section.is_empty() {
+        return cut_mesh(mesh, plane);
+    }
+
+    let n = plane.normal;
+    // Give the section some thickness, since a flat set of points has no hull to fit a box to
+    let lift = n * (mesh.size() * 1e-3);
+    let mut points = section.points.clone();
+    points.extend(section.points.iter().map(|&p| p + lift));
+    let obb = oriented_bounding_box(&points);
+
+    let in_plane = |d: Vector| d - n * d.dot(n);
+    let along = match options.direction {
+        Some(d) if in_plane(d).len() > 0.0 => in_plane(d).unit(),
+        _ => in_plane(obb.axes[0]).unit(),
+    };
+    let center = obb.center - n * plane.distance(obb.center);
+
+    let mut points = options.outline();
+    if options.side == Side::Above {
+        for p in points.iter_mut() {
+            p[1] = -p[1];
+        }
+    }
+    let profile = Profile {
+        origin: center,
+        x: n.cross(along),
+        y: n,
+        points,
+    };
+
+    let half = options.clearance / 2.0;
+    CutResult {
+        above: cut_profile_side(mesh, &profile.offset(half), Side::Above),
+        below: cut_profile_side(mesh, &profile.offset(-half), Side::Below),
+        section,
+    }
+}
+
+#[test]
+fn test_cut_with_joint() {
+    use crate::cut::open_edges;
+    use crate::geometry::Point;
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let plane = Plane::new(Vector::Z, Point::zero());
+
+    let mut options = JointOptions {
+        width: 0.6,
+        depth: 0.4,
+        clearance: 0.0,
+        direction: Some(Vector::Y),
+        ..Default::default()
+    };
+    let cut = cut_with_joint(mesh, &plane, &options);
+
+    // The tail adds its cross-section times the cube's width to the piece below
+    let flare = 0.3 + 0.4 * 15f64.to_radians().tan();
+    let tail = (0.3 + flare) * 0.4 * 2.0;
+    assert_eq!(cut.num_pieces(), 2);
+    assert!((cut.below[0].volume() - (4.0 + tail)).abs() < 1e-9);
+    assert!((cut.above[0].volume() - (4.0 - tail)).abs() < 1e-9);
+
+    for kind in [
+        JointKind::Dovetail,
+        JointKind::TongueAndGroove,
+        JointKind::PuzzleKey,
+    ] {
+        options.kind = kind;
+        options.clearance = 0.02;
+        let cut = cut_with_joint(mesh, &plane, &options);
+
+        assert_eq!(cut.num_pieces(), 2);
+        for piece in cut.above.iter().chain(cut.below.iter()) {
+            assert!(open_edges(&piece.triangles).is_empty());
+        }
+        let volume = cut.above[0].volume() + cut.below[0].volume();
+        assert!(volume < 8.0 - 0.02 * 4.0);
+    }
+
+    // A key asked to be shallower than it is wide comes out as deep as it is wide
+    options.kind = JointKind::PuzzleKey;
+    assert_eq!(options.joint_depth(), 0.6);
+    let cut = cut_with_joint(mesh, &plane, &options);
+    let (_, top) = cut.below[0].bounds();
+    // The head's sides are sampled, so its top can fall just short of the full depth
+    assert!(top.z > 0.58 && top.z < 0.6 - 0.01 + 1e-9);
+}
@@ -0,0 +1,407 @@
+use crate::cut::font::{text_outlines, text_size};
+use crate::cut::{CutResult, PlaneCut, Section, Side};
+use crate::geometry::{Plane, Point};
+use crate::threemf::Mesh;
+use serde::Serialize;
+
+// Grid resolution used when searching a cap for the spot with room for the biggest label
+const PLACEMENT_STEPS: usize = 24;
+// Halvings used to find the biggest label that fits at a spot
+const SIZE_STEPS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+    // Raised out of the cap
+    Emboss,
+    // Sunk into the cap, which keeps the cut faces flat so the pieces still meet
+    Deboss,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelOptions {
+    pub style: LabelStyle,
+    // Height of the text. Labels that don't fit are shrunk.
+    pub height: f64,
+    // Labels that would have to be smaller than this are left out
+    pub min_height: f64,
+    // How far the text stands out of or sinks into the cap
+    pub depth: f64,
+    // Minimum distance between the text and the edge of the cap
+    pub margin: f64,
+}
+
+impl Default for LabelOptions {
+    fn default() -> Self {
+        LabelOptions {
+            style: LabelStyle::Deboss,
+            height: 8.0,
+            min_height: 2.0,
+            depth: 0.6,
+            margin: 1.0,
+        }
+    }
+}
+
+// Where a label ended up
+#[derive(Debug, Clone, Serialize)]
+pub struct Label {
+    pub side: Side,
+    // The piece it is on and the piece across the cut from it, see `cut_with_labels`
+    pub piece: usize,
+    pub mate: usize,
+    pub text: String,
+    // Middle of the text, on the cutting plane
+    pub center: Point,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelCut {
+    pub cut: CutResult,
+    pub labels: Vec<Label>,
+}
+
+// Cuts a mesh along a plane like `cut_mesh`, and writes a label on the cut face of each piece
+// for each piece it meets across the plane. Pieces are numbered as they come in the result,
+// those above the plane first, and `text` gives the label for a piece and its mate. Text reads
+// normally when looking at the cut face from outside its piece. Empty labels, and labels that
+// don't fit, are left out.
+pub fn cut_with_labels<F>(
+    mesh: &Mesh,
+    plane: &Plane,
+    mut text: F,
+    options: &LabelOptions,
+) -> LabelCut
+where
+    F: FnMut(usize, usize) -> String,
+{
+    let mut cut = PlaneCut::new(mesh, plane);
+    let above = cut.vertex_pieces(Side::Above);
+    let below = cut.vertex_pieces(Side::Below);
+    let first = |side: Side| match side {
+        Side::Above => 0,
+        Side::Below => above.1,
+    };
+
+    // The loops of each side's cap, by the piece they are on and the piece they face
+    let mut faces: Vec<(Side, usize, usize, Vec<Vec<usize>>)> = Vec::new();
+    for (side, ours, theirs) in [(Side::Above, &above, &below), (Side::Below, &below, &above)] {
+        let cap = cut.cap(side);
+        for l in cap.loops.iter() {
+            let v = cap.source[l[0]];
+            let (piece, mate) = match (ours.0[v], theirs.0[v]) {
+                (Some(piece), Some(mate)) => (first(side) + piece, first(side.opposite()) + mate),
+                _ => continue,
+            };
+            match faces
+                .iter_mut()
+                .find(|f| (f.0, f.1, f.2) == (side, piece, mate))
+            {
+                Some(face) => face.3.push(l.clone()),
+                None => faces.push((side, piece, mate, vec![l.clone()])),
+            }
+        }
+    }
+
+    let mut labels = Vec::new();
+    for (side, piece, mate, loops) in faces {
+        let face = cut.cap(side).with_loops(loops);
+        let text = text(piece, mate);
+        if let Some((center, height)) = add_label(&mut cut, side, &face, &text, options) {
+            labels.push(Label {
+                side,
+                piece,
+                mate,
+                text,
+                center,
+                height,
+            });
+        }
+    }
+
+    LabelCut {
+        cut: cut.finish(),
+        labels,
+    }
+}
+
+// Writes a label on `face`, part of a side's cap, returning its center and height
+fn add_label(
+    cut: &mut PlaneCut,
+    side: Side,
+    face: &Section,
+    text: &str,
+    options: &LabelOptions,
+) -> Option<(Point, f64)> {
+    let outlines = text_outlines(text);
+    if outlines.is_empty() {
+        return None;
+    }
+    let (width, height) = text_size(text);
+    let (center, angle, scale) = place_label(face, width, height, options)?;
+
+    let (cos, sin) = (angle.cos(), angle.sin());
+    let place = |ring: &[[f64; 2]]| -> Vec<Point> {
+        ring.iter()
+            .map(|p| {
+                let (x, y) = ((p[0] - width / 2.0) * scale, (p[1] - height / 2.0) * scale);
+                face.unproject([center[0] + x * cos - y * sin, center[1] + x * sin + y * cos])
+            })
+            .collect()
+    };
+    let relief = match options.style {
+        LabelStyle::Emboss => options.depth,
+        LabelStyle::Deboss => -options.depth,
+    };
+    for (outer, holes) in outlines.iter() {
+        let holes: Vec<Vec<Point>> = holes.iter().map(|h| place(h)).collect();
+        cut.add_relief(side, &place(outer), &holes, relief);
+    }
+
+    Some((face.unproject(center), height * scale))
+}
+
+// Whether a segment touches the box [-a, a] x [-b, b] (Liang-Barsky)
+fn segment_meets_box(p: [f64; 2], q: [f64; 2], a: f64, b: f64) -> bool {
+    let d = [q[0] - p[0], q[1] - p[1]];
+    let (mut from, mut to) = (0.0f64, 1.0f64);
+    for (delta, near, far) in [(d[0], -a - p[0], a - p[0]), (d[1], -b - p[1], b - p[1])] {
+        if delta == 0.0 {
+            if near > 0.0 || far < 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let (t1, t2) = (near / delta, far / delta);
+        from = from.max(t1.min(t2));
+        to = to.min(t1.max(t2));
+        if from > to {
+            return false;
+        }
+    }
+    true
+}
+
+// Finds the biggest label that fits in the cap, as (center, angle, size of a font cell). Text
+// runs along the cap's first axis or, if that gives a bigger label, its second.
+fn place_label(
+    section: &Section,
+    width: f64,
+    height: f64,
+    options: &LabelOptions,
+) -> Option<([f64; 2], f64, f64)> {
+    let points = section.points_2d();
+    let edges: Vec<(usize, usize)> = section
+        .loops
+        .iter()
+        .flat_map(|l| (0..l.len()).map(move |k| (l[k], l[(k + 1) % l.len()])))
+        .collect();
+
+    let (mut lo, mut hi) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for p in section.loops.iter().flatten().map(|&i| points[i]) {
+        for k in 0..2 {
+            lo[k] = lo[k].min(p[k]);
+            hi[k] = hi[k].max(p[k]);
+        }
+    }
+    let step = (hi[0] - lo[0]).max(hi[1] - lo[1]) / PLACEMENT_STEPS as f64;
+    if step <= 0.0 || !step.is_finite() {
+        return None;
+    }
+
+    let largest = options.height / height;
+    // Sizes closer than the search can tell apart count as the same, and then the spot
+    // nearest the middle of the cap wins
+    let tolerance = largest * 0.5f64.powi(SIZE_STEPS as i32 - 1);
+    let middle = [(lo[0] + hi[0]) / 2.0, (lo[1] + hi[1]) / 2.0];
+    let off_center = |q: [f64; 2]| (q[0] - middle[0]).powi(2) + (q[1] - middle[1]).powi(2);
+    let mut best: Option<([f64; 2], f64, f64)> = None;
+    for angle in [0.0, std::f64::consts::FRAC_PI_2] {
+        let (cos, sin) = (f64::cos(angle), f64::sin(angle));
+        for i in 0..=PLACEMENT_STEPS {
+            for j in 0..=PLACEMENT_STEPS {
+                let q = [lo[0] + step * i as f64, lo[1] + step * j as f64];
+                if !section.contains(q) {
+                    continue;
+                }
+                // The section in the label's frame
+                let local: Vec<[f64; 2]> = points
+                    .iter()
+                    .map(|p| {
+                        let (x, y) = (p[0] - q[0], p[1] - q[1]);
+                        [x * cos + y * sin, -x * sin + y * cos]
+                    })
+                    .collect();
+                // Nothing of the section's outline inside the label (and its margin) means
+                // the whole label is inside, like its center
+                let fits = |scale: f64| {
+                    let (a, b) = (
+                        width * scale / 2.0 + options.margin,
+                        height * scale / 2.0 + options.margin,
+                    );
+                    !edges
+                        .iter()
+                        .any(|&(s, t)| segment_meets_box(local[s], local[t], a, b))
+                };
+
+                let (mut low, mut high) = (0.0, largest);
+                if !fits(low) || best.is_some_and(|b| !fits((b.2 - tolerance).max(low))) {
+                    continue;
+                }
+                if fits(high) {
+                    low = high;
+                } else {
+                    for _ in 0..SIZE_STEPS {
+                        let half = (low + high) / 2.0;
+                        if fits(half) {
+                            low = half;
+                        } else {
+                            high = half;
+                        }
+                    }
+                }
+                let better = |b: ([f64; 2], f64, f64)| {
+                    low > b.2 + tolerance
+                        || (low > b.2 - tolerance && off_center(q) < off_center(b.0))
+                };
+                if best.is_none_or(better) {
+                    best = Some((q, angle, low));
+                }
+            }
+        }
+    }
+
+    best.filter(|&(_, _, scale)| scale * height >= options.min_height)
+}
+
+#[test]
+fn test_cut_with_labels() {
+    use crate::cut::open_edges;
+    use crate::geometry::polygon;
+    use crate::geometry::Vector;
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let plane = Plane::new(Vector::Z, Point::zero());
+
+    let mut options = LabelOptions {
+        style: LabelStyle::Emboss,
+        height: 8.0,
+        min_height: 0.5,
+        depth: 0.05,
+        margin: 0.1,
+    };
+    let result = cut_with_labels(
+        mesh,
+        &plane,
+        |piece, _| ["A1", "#2"][piece].to_string(),
+        &options,
+    );
+    let cut = &result.cut;
+
+    // The piece above the plane is 0 and the one below it 1, each the other's mate
+    let pairs: Vec<(usize, usize)> = result.labels.iter().map(|l| (l.piece, l.mate)).collect();
+    assert_eq!(pairs, [(0, 1), (1, 0)]);
+
+    // Shrunk until the text, 11 cells wide, fits in the 2 x 2 cap with its margin
+    for label in result.labels.iter() {
+        assert!((label.height - 1.8 / 11.0 * 7.0).abs() < 1e-6);
+        assert!((label.center - Point::zero()).len() < 1e-9);
+    }
+
+    let area = |text: &str| {
+        let size = result.labels[0].height / 7.0;
+        let ring = |r: &Vec<[f64; 2]>| {
+            let indices: Vec<usize> = (0..r.len()).collect();
+            polygon::signed_area(r, &indices) / 2.0
+        };
+        text_outlines(text)
+            .iter()
+            .map(|(outer, holes)| ring(outer) + holes.iter().map(ring).sum::<f64>())
+            .sum::<f64>()
+            * size
+            * size
+    };
+    assert_eq!(cut.num_pieces(), 2);
+    for piece in cut.above.iter().chain(cut.below.iter()) {
+        assert!(open_edges(&piece.triangles).is_empty());
+    }
+    assert!((cut.above[0].volume() - (4.0 + area("A1") * 0.05)).abs() < 1e-9);
+    assert!((cut.below[0].volume() - (4.0 + area("#2") * 0.05)).abs() < 1e-9);
+
+    // Debossed, and too big to leave out once it can't shrink enough
+    options.style = LabelStyle::Deboss;
+    let result = cut_with_labels(
+        mesh,
+        &plane,
+        |piece, _| ["", "7"][piece].to_string(),
+        &options,
+    );
+    assert_eq!(result.labels.len(), 1);
+    assert!(result.cut.below[0].volume() < 4.0);
+    options.min_height = 1.9;
+    let result = cut_with_labels(mesh, &plane, |_, _| "7".to_string(), &options);
+    assert!(result.labels.is_empty());
+}
+
+#[test]
+fn test_labels_in_frame() {
+    use crate::cut::{assert_pieces_make_up, frame};
+    use crate::geometry::polygon;
+    use crate::geometry::Vector;
+
+    // In front of the plate are four corner posts, so the cut leaves four pieces above it,
+    // each meeting the plate below
+    let mesh = &frame();
+    let plane = Plane::new(Vector::Y, Point::new(0.0, -2.0, 0.0));
+    let options = LabelOptions {
+        min_height: 0.5,
+        margin: 0.3,
+        ..LabelOptions::default()
+    };
+    let text = |piece, mate| format!("{}{}", piece, mate);
+    let result = cut_with_labels(mesh, &plane, text, &options);
+    let cut = &result.cut;
+    assert_eq!((cut.above.len(), cut.below.len()), (4, 1));
+    let pieces: Vec<&Mesh> = cut.above.iter().chain(cut.below.iter()).collect();
+
+    // Every post has its own label, and the plate has one facing each post
+    let mut labelled: Vec<(usize, usize)> =
+        result.labels.iter().map(|l| (l.piece, l.mate)).collect();
+    labelled.sort();
+    let posts = (0..4).map(|k| (k, 4));
+    let plate = (0..4).map(|k| (4, k));
+    assert_eq!(labelled, posts.chain(plate).collect::<Vec<_>>());
+
+    let mut removed = 0.0;
+    for label in result.labels.iter() {
+        assert_eq!(label.text, text(label.piece, label.mate));
+        let side = if label.piece < 4 {
+            Side::Above
+        } else {
+            Side::Below
+        };
+        assert_eq!(label.side, side);
+
+        // On the face where the piece meets its mate
+        let inside = |piece: &Mesh| {
+            let (min, max) = piece.bounds();
+            let c = label.center;
+            (min.x..=max.x).contains(&c.x) && (min.z..=max.z).contains(&c.z)
+        };
+        assert!(inside(pieces[label.piece]) && inside(pieces[label.mate]));
+
+        let size = label.height / 7.0;
+        let ring = |r: &Vec<[f64; 2]>| {
+            let indices: Vec<usize> = (0..r.len()).collect();
+            polygon::signed_area(r, &indices) / 2.0
+        };
+        let area: f64 = text_outlines(&label.text)
+            .iter()
+            .map(|(outer, holes)| ring(outer) + holes.iter().map(ring).sum::<f64>())
+            .sum();
+        removed += area * size * size * options.depth;
+    }
+    assert_pieces_make_up(mesh, pieces, removed);
+}
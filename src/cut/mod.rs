@@ -0,0 +1,531 @@
+use crate::geometry::polygon;
+use crate::geometry::{Plane, Point, Triangle};
+use crate::threemf::mesh::connected_triangles;
+use crate::threemf::{BaseMaterials, ColorGroup, Item, Mesh, Model, Object};
+use serde::Serialize;
+use std::collections::HashMap;
+
+pub use assembly::{cut_assembly, Assembly, AssemblyOptions, CutFace, Manifest, PieceEntry};
+pub use dowel::{cut_with_dowels, DowelCut, DowelOptions, DowelStyle};
+pub use joint::{cut_with_joint, JointKind, JointOptions};
+pub use label::{cut_with_labels, Label, LabelCut, LabelOptions, LabelStyle};
+pub use partition::{partition, Cell, PartitionOptions};
+pub use profile::{cut_profile, Profile};
+pub use section::Section;
+
+pub mod assembly;
+pub mod dowel;
+pub mod font;
+pub mod joint;
+pub mod label;
+pub mod partition;
+pub mod profile;
+pub mod section;
+
+// Vertices closer to a cutting plane than this (relative to the mesh size) are treated as
+// lying on it
+pub const PLANE_TOLERANCE: f64 = 1e-9;
+
+// The closed pieces produced by cutting a mesh with a plane
+#[derive(Debug, Clone)]
+pub struct CutResult {
+    // Pieces on the side the plane's normal points to
+    pub above: Vec<Mesh>,
+    // Pieces on the opposite side
+    pub below: Vec<Mesh>,
+    // The cross-section along the plane, seen from above
+    pub section: Section,
+}
+
+impl CutResult {
+    pub fn num_pieces(&self) -> usize {
+        self.above.len() + self.below.len()
+    }
+}
+
+// One side of a cutting plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    // The side the plane's normal points to
+    Above,
+    Below,
+}
+
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Above => Side::Below,
+            Side::Below => Side::Above,
+        }
+    }
+}
+
+// A mesh refined along a plane, so that every triangle lies entirely on one side of it.
+// Vertices created on the plane are shared by both sides.
+pub(crate) struct Split {
+    pub vertices: Vec<Point>,
+    pub on_plane: Vec<bool>,
+    pub above: Vec<Triangle>,
+    pub below: Vec<Triangle>,
+}
+
+pub(crate) fn split_mesh(mesh: &Mesh, plane: &Plane) -> Split {
+    let tolerance = PLANE_TOLERANCE * mesh.size().max(f64::MIN_POSITIVE);
+
+    let distances: Vec<f64> = mesh.vertices.iter().map(|p| plane.distance(*p)).collect();
+    let sides: Vec<i8> = distances
+        .iter()
+        .map(|&d| {
+            if d.abs() <= tolerance {
+                0
+            } else if d > 0.0 {
+                1
+            } else {
+                -1
+            }
+        })
+        .collect();
+
+    let mut split = Split {
+        vertices: mesh.vertices.clone(),
+        on_plane: sides.iter().map(|&s| s == 0).collect(),
+        above: Vec::new(),
+        below: Vec::new(),
+    };
+
+    // New vertices are cached by edge so that neighbouring triangles share them
+    let mut edge_points: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for triangle in mesh.triangles.iter() {
+        let idx = triangle.indices();
+        let s = idx.map(|i| sides[i]);
+
+        if s == [0, 0, 0] {
+            // Lying in the plane. It closes off whichever side its normal faces away from.
+            if mesh.triangle_normal(triangle).dot(plane.normal) > 0.0 {
+                split.below.push(triangle.clone());
+            } else {
+                split.above.push(triangle.clone());
+            }
+            continue;
+        }
+        if s.iter().all(|&x| x >= 0) {
+            split.above.push(triangle.clone());
+            continue;
+        }
+        if s.iter().all(|&x| x <= 0) {
+            split.below.push(triangle.clone());
+            continue;
+        }
+
+        // The plane crosses the triangle. Clip it into a polygon on each side. Corners keep
+        // their property, and new corners take the property of the edge's end on their side.
+        let properties = triangle.properties();
+        let property = |k: usize| properties.map(|p| p[k]).unwrap_or(0);
+        let mut above: Vec<(usize, usize)> = Vec::with_capacity(4);
+        let mut below: Vec<(usize, usize)> = Vec::with_capacity(4);
+        for k in 0..3 {
+            let (a, b) = (idx[k], idx[(k + 1) % 3]);
+            if s[k] >= 0 {
+                above.push((a, property(k)));
+            }
+            if s[k] <= 0 {
+                below.push((a, property(k)));
+            }
+            if s[k] * s[(k + 1) % 3] < 0 {
+                let key = (a.min(b), a.max(b));
+                let m = *edge_points.entry(key).or_insert_with(|| {
+                    let (p, q) = (mesh.vertices[key.0], mesh.vertices[key.1]);
+                    let t = distances[key.0] / (distances[key.0] - distances[key.1]);
+                    split.vertices.push(p + (q - p) * t);
+                    split.on_plane.push(true);
+                    split.vertices.len() - 1
+                });
+                let (up, down) = if s[k] > 0 {
+                    (k, (k + 1) % 3)
+                } else {
+                    ((k + 1) % 3, k)
+                };
+                above.push((m, property(up)));
+                below.push((m, property(down)));
+            }
+        }
+
+        // The clipped polygons are convex, so a fan is enough
+        for (polygon, out) in [(above, &mut split.above), (below, &mut split.below)] {
+            for k in 1..polygon.len().saturating_sub(1) {
+                let [a, b, c] = [polygon[0], polygon[k], polygon[k + 1]];
+                out.push(
+                    Triangle::new(a.0, b.0, c.0)
+                        .with_properties(triangle.pid, properties.map(|_| [a.1, b.1, c.1])),
+                );
+            }
+        }
+    }
+
+    split
+}
+
+// Directed edges that are not matched by an opposite edge, i.e. the boundary of an open
+// set of triangles
+pub(crate) fn open_edges(triangles: &[Triangle]) -> Vec<(usize, usize)> {
+    let mut counts: HashMap<(usize, usize), i32> = HashMap::new();
+    for triangle in triangles.iter() {
+        let [a, b, c] = triangle.indices();
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            match counts.get_mut(&(to, from)) {
+                Some(n) if *n > 0 => *n -= 1,
+                _ => *counts.entry((from, to)).or_insert(0) += 1,
+            }
+        }
+    }
+
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for (&edge, &n) in counts.iter() {
+        for _ in 0..n {
+            edges.push(edge);
+        }
+    }
+    edges.sort_unstable();
+    edges
+}
+
+// The section closing off one side of a split. `plane` must face away from that side.
+pub(crate) fn cap_section(split: &Split, triangles: &[Triangle], plane: Plane) -> Section {
+    // The cap runs along the side's boundary in the opposite direction
+    let edges: Vec<(usize, usize)> = open_edges(triangles)
+        .into_iter()
+        .filter(|&(a, b)| split.on_plane[a] && split.on_plane[b])
+        .map(|(a, b)| (b, a))
+        .collect();
+    Section::from_edges(plane, &split.vertices, &edges)
+}
+
+// Triangles covering a section, in terms of the mesh vertices it was built from
+pub(crate) fn cap_triangles(section: &Section) -> Vec<Triangle> {
+    section
+        .triangulate()
+        .into_iter()
+        .map(|[a, b, c]| Triangle::new(section.source[a], section.source[b], section.source[c]))
+        .collect()
+}
+
+// A planar cut in progress. Features can be added to the caps before the pieces are built.
+pub(crate) struct PlaneCut {
+    vertices: Vec<Point>,
+    above: Vec<Triangle>,
+    below: Vec<Triangle>,
+    // Caps facing away from each side, so the above cap faces along -normal
+    above_cap: Section,
+    below_cap: Section,
+    // The cross-section before any features were added
+    pub section: Section,
+}
+
+impl PlaneCut {
+    pub fn new(mesh: &Mesh, plane: &Plane) -> Self {
+        let split = split_mesh(mesh, plane);
+        let below_cap = cap_section(&split, &split.below, plane.clone());
+        let above_cap = cap_section(&split, &split.above, Plane::new(-plane.normal, plane.point));
+
+        PlaneCut {
+            section: below_cap.clone(),
+            vertices: split.vertices,
+            above: split.above,
+            below: split.below,
+            above_cap,
+            below_cap,
+        }
+    }
+
+    pub fn cap(&self, side: Side) -> &Section {
+        match side {
+            Side::Above => &self.above_cap,
+            Side::Below => &self.below_cap,
+        }
+    }
+
+    // The piece each vertex of one side ends up on, numbered in the order `finish` gives that
+    // side's pieces, and how many pieces the side has. Features added to the caps don't change
+    // them.
+    pub fn vertex_pieces(&self, side: Side) -> (Vec<Option<usize>>, usize) {
+        let mut triangles = match side {
+            Side::Above => self.above.clone(),
+            Side::Below => self.below.clone(),
+        };
+        triangles.extend(cap_triangles(self.cap(side)));
+        let pieces = connected_triangles(self.vertices.len(), &triangles);
+        let mut piece_of = vec![None; self.vertices.len()];
+        for (k, piece) in pieces.iter().enumerate() {
+            for &t in piece.iter() {
+                for v in triangles[t].indices() {
+                    piece_of[v] = Some(k);
+                }
+            }
+        }
+        (piece_of, pieces.len())
+    }
+
+    // Sinks a pocket into (negative height) or raises a boss out of (positive height) one
+    // side's cap. Height is measured along the cap's outward normal. The outline and its holes
+    // are projected onto the plane, and must lie strictly inside the cap without touching
+    // other features.
+    pub fn add_relief(&mut self, side: Side, outline: &[Point], holes: &[Vec<Point>], height: f64) {
+        let (cap, triangles) = match side {
+            Side::Above => (&mut self.above_cap, &mut self.above),
+            Side::Below => (&mut self.below_cap, &mut self.below),
+        };
+        let normal = cap.plane.normal;
+
+        let mut flat: Vec<[f64; 2]> = Vec::new();
+        let mut loops: Vec<Vec<usize>> = Vec::new();
+        let mut tops: Vec<usize> = Vec::new();
+        let rings = std::iter::once(outline).chain(holes.iter().map(|h| h.as_slice()));
+        for (k, ring) in rings.enumerate() {
+            // Counter-clockwise around the cap's normal for the outline, clockwise for holes
+            let mut ring: Vec<Point> = ring
+                .iter()
+                .map(|&p| p - normal * cap.plane.distance(p))
+                .collect();
+            let indices: Vec<usize> = (0..ring.len()).collect();
+            let mut points: Vec<[f64; 2]> = ring.iter().map(|&p| cap.project(p)).collect();
+            if (polygon::signed_area(&points, &indices) < 0.0) == (k == 0) {
+                ring.reverse();
+                points.reverse();
+            }
+
+            let n = ring.len();
+            let base = self.vertices.len();
+            let top = base + n;
+            self.vertices.extend(ring.iter().copied());
+            self.vertices
+                .extend(ring.iter().map(|&p| p + normal * height));
+
+            // The cap goes around each ring the other way, so holes in the relief are
+            // islands of the cap
+            cap.add_loop(
+                ring.iter().rev().copied().collect(),
+                (base..top).rev().collect(),
+            );
+
+            for i in 0..n {
+                let j = (i + 1) % n;
+                triangles.push(Triangle::new(base + i, base + j, top + j));
+                triangles.push(Triangle::new(base + i, top + j, top + i));
+            }
+            loops.push((flat.len()..flat.len() + n).collect());
+            flat.extend(points);
+            tops.extend(top..top + n);
+        }
+
+        let holes: Vec<&[usize]> = loops[1..].iter().map(|l| l.as_slice()).collect();
+        for [a, b, c] in polygon::triangulate(&flat, &loops[0], &holes) {
+            triangles.push(Triangle::new(tops[a], tops[b], tops[c]));
+        }
+    }
+
+    pub fn finish(self) -> CutResult {
+        let mut below = self.below;
+        below.extend(cap_triangles(&self.below_cap));
+        let mut above = self.above;
+        above.extend(cap_triangles(&self.above_cap));
+
+        CutResult {
+            above: Mesh::new(self.vertices.clone(), above).components(),
+            below: Mesh::new(self.vertices, below).components(),
+            section: self.section,
+        }
+    }
+}
+
+// Cuts a closed mesh along a plane, capping the cut so that every resulting piece is closed
+pub fn cut_mesh(mesh: &Mesh, plane: &Plane) -> CutResult {
+    PlaneCut::new(mesh, plane).finish()
+}
+
+// Cuts every item of a model's build along a plane, where it is placed. The pieces take ids
+// after the property groups, above the plane before below it, and keep the name, metadata and
+// properties of the object they came from. The caps take the object's default property, or
+// for an assembly without one, that of the first of its components that has one.
+// Property groups used from other parts of the package are copied into the result with new ids.
+pub fn cut_model(model: &Model, plane: &Plane) -> Model {
+    let mut cut = Model {
+        unit: model.unit,
+        metadata: model.metadata.clone(),
+        materials: model.materials.clone(),
+        colors: model.colors.clone(),
+        ..Model::empty()
+    };
+    // Ids given to the groups copied from other parts, by part and id there
+    let mut copied: HashMap<(String, usize), usize> = HashMap::new();
+    for item in model.build.items.iter() {
+        let path = item.path.as_deref();
+        let object = match model.object(path, item.objectid) {
+            Some(object) => object,
+            None => continue,
+        };
+        let mut group = |part: Option<&str>, pid: usize| match part {
+            None => pid,
+            Some(part) => *copied
+                .entry((part.to_string(), pid))
+                .or_insert_with(|| copy_group(&mut cut, model.part(Some(part)), pid)),
+        };
+        let mesh = match model.item_mesh_with(item, &mut group) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        let (pid, pindex) = match default_property(model, path, object) {
+            Some((part, pid, pindex)) => (Some(group(part, pid)), pindex),
+            None => (None, None),
+        };
+
+        let result = cut_mesh(&mesh, plane);
+        for piece in result.above.into_iter().chain(result.below) {
+            let id = cut.next_resource_id();
+            cut.objects.push(Object {
+                pid,
+                pindex,
+                ..object.piece(id, piece)
+            });
+            cut.build.items.push(Item {
+                objectid: id,
+                partnumber: item.partnumber.clone(),
+                ..Item::default()
+            });
+        }
+    }
+    cut
+}
+
+// An object's default property as (part the group is in, group, index), looking through its
+// components depth first when it has none. The components must not form a loop.
+fn default_property<'a>(
+    model: &'a Model,
+    path: Option<&'a str>,
+    object: &'a Object,
+) -> Option<(Option<&'a str>, usize, Option<usize>)> {
+    if let Some(pid) = object.pid {
+        let part = path.filter(|p| model.parts.contains_key(*p));
+        return Some((part, pid, object.pindex));
+    }
+    object.components.iter().find_map(|component| {
+        let path = component.path.as_deref().or(path);
+        let object = model.object(path, component.objectid)?;
+        default_property(model, path, object)
+    })
+}
+
+// Copies a property group from another part into the model under a new id, which is returned.
+// A group that can't be found keeps its id, so the reference stays as broken as it was.
+fn copy_group(model: &mut Model, part: &Model, pid: usize) -> usize {
+    let id = model.next_resource_id();
+    if let Some(group) = part.materials.iter().find(|m| m.id == pid) {
+        model.materials.push(BaseMaterials {
+            id,
+            ..group.clone()
+        });
+    } else if let Some(group) = part.colors.iter().find(|c| c.id == pid) {
+        model.colors.push(ColorGroup {
+            id,
+            ..group.clone()
+        });
+    } else {
+        return pid;
+    }
+    id
+}
+
+// The plate with cut-outs and four corner posts that the cut tests use to go through thin walls
+// and into separate pieces
+#[cfg(test)]
+pub(crate) fn frame() -> Mesh {
+    crate::load::load_model("data/Frontplate.3mf")
+        .unwrap()
+        .objects[0]
+        .mesh
+        .clone()
+}
+
+// Checks that pieces cut from a mesh are closed, and make up all of it but `removed`
+#[cfg(test)]
+pub(crate) fn assert_pieces_make_up<'a>(
+    mesh: &Mesh,
+    pieces: impl IntoIterator<Item = &'a Mesh>,
+    removed: f64,
+) {
+    let mut volume = 0.0;
+    for piece in pieces {
+        assert!(open_edges(&piece.triangles).is_empty());
+        volume += piece.volume();
+    }
+    assert!((volume - (mesh.volume() - removed)).abs() < 1e-6);
+}
+
+#[test]
+fn test_cut_cube() {
+    use crate::geometry::Vector;
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+
+    // Slanted cut through the middle of the cube
+    let plane = Plane::new(Vector::new(0.3, 0.2, 1.0), Point::new(0.0, 0.0, 0.1));
+    let cut = cut_mesh(mesh, &plane);
+
+    assert_eq!(cut.above.len(), 1);
+    assert_eq!(cut.below.len(), 1);
+    for piece in cut.above.iter().chain(cut.below.iter()) {
+        assert!(open_edges(&piece.triangles).is_empty());
+    }
+    let volume = cut.above[0].volume() + cut.below[0].volume();
+    assert!((volume - mesh.volume()).abs() < 1e-9);
+    assert!(cut.section.area() > 4.0);
+}
+
+#[test]
+fn test_cut_model() {
+    use crate::geometry::Vector;
+    use crate::load::load_model;
+    use crate::threemf::validate;
+
+    // The object's colour group has id 2, which the second piece would otherwise be given
+    let model = load_model("data/Frontplate.3mf").unwrap();
+    let cut = cut_model(&model, &Plane::new(Vector::Y, Point::new(0.0, -6.0, 0.0)));
+    assert_eq!(cut.objects.len(), 3);
+    assert_eq!(cut.colors, model.colors);
+    for object in cut.objects.iter() {
+        assert!(object.id != 2);
+        assert_eq!((object.pid, object.pindex), (Some(2), Some(0)));
+    }
+
+    let findings = validate(&cut.to_raw_data().unwrap());
+    assert!(!findings.iter().any(|f| f.is_error()), "{:?}", findings);
+}
+
+#[test]
+fn test_cut_caps() {
+    use crate::geometry::Vector;
+    use crate::load::load_model;
+
+    // Round holes, with points on the plane halfway along straight runs of the outline
+    let model = load_model("data/test_fusion.3mf").unwrap();
+    for object in model.objects.iter() {
+        let mesh = &object.mesh;
+        let (min, max) = mesh.bounds();
+        let middle = Point::new(0.0, 0.0, (min.z + max.z) / 2.0);
+        let cut = cut_mesh(mesh, &Plane::new(Vector::Z, middle));
+        for piece in cut.above.iter().chain(cut.below.iter()) {
+            assert!(open_edges(&piece.triangles).is_empty());
+            assert!(piece.triangles.iter().all(|t| piece.triangle_area(t) > 0.0));
+        }
+        let volume: f64 = cut
+            .above
+            .iter()
+            .chain(cut.below.iter())
+            .map(|p| p.volume())
+            .sum();
+        assert!((volume - mesh.volume()).abs() < 1e-6 * mesh.volume().abs());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::cut::{cut_mesh, Side, PLANE_TOLERANCE};
+use crate::geometry::Plane;
+use crate::threemf::Mesh;
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct PartitionOptions {
+    // Pieces smaller than this, as a fraction of the model's volume, are dropped
+    pub min_volume: f64,
+}
+
+impl Default for PartitionOptions {
+    fn default() -> Self {
+        PartitionOptions { min_volume: 1e-3 }
+    }
+}
+
+// A closed piece of the model lying in one cell of the partition
+#[derive(Debug, Clone, Serialize)]
+pub struct Cell {
+    // The side of every plane the piece is on, in the order the planes were given
+    pub signs: Vec<Side>,
+    pub volume: f64,
+    #[serde(skip)]
+    pub mesh: Mesh,
+}
+
+// Splits a closed mesh by every plane at once. Each piece ends up on one side of every plane, so
+// unlike cutting with the planes one after another, the result does not depend on their order.
+// Where the model's shape leaves several separate pieces in the same cell, each is returned on
+// its own with the same signs.
+pub fn partition(mesh: &Mesh, planes: &[Plane], options: &PartitionOptions) -> Vec<Cell> {
+    let min_volume = options.min_volume * mesh.volume().abs();
+    let tolerance = PLANE_TOLERANCE * mesh.size().max(f64::MIN_POSITIVE);
+
+    let mut cells = Vec::new();
+    split(
+        mesh.clone(),
+        planes,
+        &mut Vec::with_capacity(planes.len()),
+        min_volume,
+        tolerance,
+        &mut cells,
+    );
+    cells
+}
+
+fn split(
+    piece: Mesh,
+    planes: &[Plane],
+    signs: &mut Vec<Side>,
+    min_volume: f64,
+    tolerance: f64,
+    cells: &mut Vec<Cell>,
+) {
+    let volume = piece.volume();
+    if volume < min_volume {
+        return;
+    }
+    let plane = match planes.get(signs.len()) {
+        Some(plane) => plane,
+        None => {
+            cells.push(Cell {
+                signs: signs.clone(),
+                volume,
+                mesh: piece,
+            });
+            return;
+        }
+    };
+
+    // A piece the plane doesn't go through (or only touches) is on one side of it
+    let distances = piece.vertices.iter().map(|&p| plane.distance(p));
+    let (low, high) = distances.fold((0.0f64, 0.0f64), |(l, h), d| (l.min(d), h.max(d)));
+    let parts = if low >= -tolerance {
+        vec![(Side::Above, vec![piece])]
+    } else if high <= tolerance {
+        vec![(Side::Below, vec![piece])]
+    } else {
+        let result = cut_mesh(&piece, plane);
+        vec![(Side::Above, result.above), (Side::Below, result.below)]
+    };
+
+    for (side, pieces) in parts {
+        for part in pieces {
+            signs.push(side);
+            split(part, planes, signs, min_volume, tolerance, cells);
+            signs.pop();
+        }
+    }
+}
+
+#[test]
+fn test_partition() {
+    use crate::cut::open_edges;
+    use crate::geometry::{Point, Vector};
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let mut planes = vec![
+        Plane::new(Vector::X, Point::zero()),
+        Plane::new(Vector::Y, Point::zero()),
+        Plane::new(Vector::Z, Point::zero()),
+        // Only touches the top face
+        Plane::new(Vector::Z, Point::new(0.0, 0.0, 1.0)),
+        // Leaves slivers of an 8000th of the cube, too small to keep
+        Plane::new(Vector::X, Point::new(0.999, 0.0, 0.0)),
+    ];
+
+    let cells = partition(mesh, &planes, &PartitionOptions::default());
+    assert_eq!(cells.len(), 8);
+    let mut signs: Vec<Vec<Side>> = cells.iter().map(|c| c.signs[..3].to_vec()).collect();
+    signs.sort_by_key(|s| format!("{:?}", s));
+    signs.dedup();
+    assert_eq!(signs.len(), 8);
+    for cell in cells.iter() {
+        assert!(open_edges(&cell.mesh.triangles).is_empty());
+        assert_eq!(cell.signs[3], Side::Below);
+        assert_eq!(cell.signs[4], Side::Below);
+        let expected = if cell.signs[0] == Side::Above {
+            0.999
+        } else {
+            1.0
+        };
+        assert!((cell.volume - expected).abs() < 1e-9);
+    }
+
+    // The same cells come out whatever order the planes are in
+    planes.reverse();
+    let reversed = partition(mesh, &planes, &PartitionOptions::default());
+    let key = |signs: &[Side]| format!("{:?}", signs);
+    let mut a: Vec<(String, f64)> = cells
+        .iter()
+        .map(|c| {
+            (
+                key(&c.signs.iter().rev().copied().collect::<Vec<_>>()),
+                c.volume,
+            )
+        })
+        .collect();
+    let mut b: Vec<(String, f64)> = reversed.iter().map(|c| (key(&c.signs), c.volume)).collect();
+    a.sort_by(|x, y| x.0.cmp(&y.0));
+    b.sort_by(|x, y| x.0.cmp(&y.0));
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert_eq!(x.0, y.0);
+        assert!((x.1 - y.1).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_partition_frame() {
+    use crate::cut::{assert_pieces_make_up, frame};
+    use crate::geometry::{Point, Vector};
+
+    // Near its front face the plate is only four corner posts, two on each side of x = 0
+    let mesh = &frame();
+    let planes = [
+        Plane::new(Vector::Y, Point::new(0.0, -2.0, 0.0)),
+        Plane::new(Vector::X, Point::zero()),
+    ];
+    let cells = partition(mesh, &planes, &PartitionOptions { min_volume: 0.0 });
+    assert_eq!(cells.len(), 6);
+    assert_pieces_make_up(mesh, cells.iter().map(|c| &c.mesh), 0.0);
+
+    // The two posts in each front cell come out as separate pieces, one above the other, on
+    // the side of x = 0 their signs say
+    for signs in [[Side::Above, Side::Above], [Side::Above, Side::Below]] {
+        let posts: Vec<&Cell> = cells.iter().filter(|c| c.signs == signs).collect();
+        assert_eq!(posts.len(), 2);
+        assert!((posts[0].volume - posts[1].volume).abs() < 1e-6);
+        let bounds: Vec<(Point, Point)> = posts.iter().map(|c| c.mesh.bounds()).collect();
+        assert!(bounds[0].1.z < bounds[1].0.z || bounds[1].1.z < bounds[0].0.z);
+        for (min, max) in bounds {
+            let x = (min.x + max.x) / 2.0;
+            assert_eq!(x > 0.0, signs[1] == Side::Above);
+            assert!(min.y >= -2.0 - 1e-9);
+        }
+    }
+}
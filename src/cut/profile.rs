@@ -0,0 +1,369 @@
+use crate::cut::{open_edges, split_mesh, CutResult, Section, Side, PLANE_TOLERANCE};
+use crate::geometry::polygon;
+use crate::geometry::{Plane, Point, Triangle, Vector};
+use crate::threemf::Mesh;
+
+// A cutting surface made of planar strips: a polyline in the (x, y) plane swept along x cross y.
+// The polyline should run towards +x, with its first and last segments pointing that way too;
+// those two segments are extended to infinity. The side the y axis points to is above.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub origin: Point,
+    // Orthonormal
+    pub x: Vector,
+    pub y: Vector,
+    pub points: Vec<[f64; 2]>,
+}
+
+impl Profile {
+    // A polyline drawn around `origin`, with y along `up`, swept along `direction`. None if the
+    // directions are parallel or the polyline does not run towards +x at both ends.
+    pub fn new(
+        origin: Point,
+        direction: Vector,
+        up: Vector,
+        points: Vec<[f64; 2]>,
+    ) -> Option<Self> {
+        let sweep = direction.unit();
+        let y = up - sweep * up.dot(sweep);
+        if direction.len() <= 0.0 || y.len() <= 0.0 || points.len() < 2 {
+            return None;
+        }
+        let y = y.unit();
+
+        let profile = Profile {
+            origin,
+            x: y.cross(sweep),
+            y,
+            points,
+        };
+        let steps = profile.points.windows(2);
+        let (first, last) = (
+            &profile.points[..2],
+            &profile.points[profile.points.len() - 2..],
+        );
+        if steps.clone().any(|w| w[0] == w[1])
+            || first[1][0] <= first[0][0]
+            || last[1][0] <= last[0][0]
+        {
+            return None;
+        }
+        Some(profile)
+    }
+
+    // A flat cut along `plane` that steps up by `height` (or down, when negative) where it
+    // crosses the line through the plane's point along `direction`
+    pub fn step(plane: &Plane, direction: Vector, height: f64) -> Option<Self> {
+        let points = if height == 0.0 {
+            vec![[-1.0, 0.0], [1.0, 0.0]]
+        } else {
+            let h = height.abs();
+            vec![[-h, 0.0], [0.0, 0.0], [0.0, height], [h, height]]
+        };
+        Profile::new(plane.point, direction, plane.normal, points)
+    }
+
+    // A cut along `plane` with `teeth` triangular teeth, each `pitch` wide and rising
+    // `amplitude` above the plane, centered on the plane's point and running along `direction`
+    pub fn zigzag(
+        plane: &Plane,
+        direction: Vector,
+        teeth: usize,
+        pitch: f64,
+        amplitude: f64,
+    ) -> Option<Self> {
+        let start = -(teeth as f64) * pitch / 2.0;
+        let mut points = vec![[start - pitch, 0.0]];
+        for k in 0..=2 * teeth {
+            let y = if k % 2 == 1 { amplitude } else { 0.0 };
+            points.push([start + k as f64 * pitch / 2.0, y]);
+        }
+        points.push([-start + pitch, 0.0]);
+        Profile::new(plane.point, direction, plane.normal, points)
+    }
+
+    fn sweep(&self) -> Vector {
+        self.x.cross(self.y)
+    }
+
+    fn to_2d(&self, p: Point) -> [f64; 2] {
+        let d = p - self.origin;
+        [d.dot(self.x), d.dot(self.y)]
+    }
+
+    fn to_3d(&self, q: [f64; 2]) -> Point {
+        self.origin + self.x * q[0] + self.y * q[1]
+    }
+
+    // Unit direction of each segment, in 2D
+    fn directions(&self) -> Vec<[f64; 2]> {
+        self.points
+            .windows(2)
+            .map(|w| {
+                let d = [w[1][0] - w[0][0], w[1][1] - w[0][1]];
+                let len = d[0].hypot(d[1]);
+                [d[0] / len, d[1] / len]
+            })
+            .collect()
+    }
+
+    // Distance along the polyline to the start of each segment
+    fn arcs(&self) -> Vec<f64> {
+        let mut arcs = vec![0.0];
+        for w in self.points.windows(2) {
+            let last = *arcs.last().unwrap();
+            arcs.push(last + (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1]));
+        }
+        arcs
+    }
+
+    // The plane of each segment, facing the above side
+    pub fn planes(&self) -> Vec<Plane> {
+        self.directions()
+            .iter()
+            .zip(self.points.iter())
+            .map(|(d, &p)| Plane::new(self.x * -d[1] + self.y * d[0], self.to_3d(p)))
+            .collect()
+    }
+
+    // The same profile moved sideways by `distance`, towards the above side
+    pub fn offset(&self, distance: f64) -> Profile {
+        let directions = self.directions();
+        let normals: Vec<[f64; 2]> = directions.iter().map(|d| [-d[1], d[0]]).collect();
+        let last = directions.len() - 1;
+
+        let points = (0..self.points.len())
+            .map(|i| {
+                let p = self.points[i];
+                let (before, after) = (normals[i.saturating_sub(1)], normals[i.min(last)]);
+                // Mitre the corner so both neighbouring segments move by the full distance
+                let sum = [before[0] + after[0], before[1] + after[1]];
+                let scale = 2.0 * distance / (sum[0] * sum[0] + sum[1] * sum[1]);
+                [p[0] + sum[0] * scale, p[1] + sum[1] * scale]
+            })
+            .collect();
+
+        Profile {
+            points,
+            ..self.clone()
+        }
+    }
+
+    // Whether a 2D point is above the polyline, `reach` being well beyond anything tested
+    fn is_above(&self, q: [f64; 2], reach: f64) -> bool {
+        let directions = self.directions();
+        let (first, last) = (self.points[0], *self.points.last().unwrap());
+        let (d0, d1) = (directions[0], *directions.last().unwrap());
+
+        let start = [first[0] - d0[0] * reach, first[1] - d0[1] * reach];
+        let end = [last[0] + d1[0] * reach, last[1] + d1[1] * reach];
+        let mut ring = vec![start];
+        ring.extend(self.points.iter().copied());
+        ring.push(end);
+        ring.push([end[0], end[1] + reach]);
+        ring.push([start[0], start[1] + reach]);
+
+        let indices: Vec<usize> = (0..ring.len()).collect();
+        polygon::contains(&ring, &indices, q)
+    }
+
+    // Segment closest to a 2D point, how far along the polyline the point is, and how far
+    // from it
+    fn locate(&self, q: [f64; 2]) -> (usize, f64, f64) {
+        let directions = self.directions();
+        let arcs = self.arcs();
+        let last = directions.len() - 1;
+
+        (0..directions.len())
+            .map(|j| {
+                let (p, d) = (self.points[j], directions[j]);
+                let along = (q[0] - p[0]) * d[0] + (q[1] - p[1]) * d[1];
+                let length = arcs[j + 1] - arcs[j];
+                let clamped = match j {
+                    _ if last == 0 => along,
+                    0 => along.min(length),
+                    _ if j == last => along.max(0.0),
+                    _ => along.clamp(0.0, length),
+                };
+                let closest = [p[0] + d[0] * clamped, p[1] + d[1] * clamped];
+                let distance = (q[0] - closest[0]).hypot(q[1] - closest[1]);
+                (j, arcs[j] + clamped, distance)
+            })
+            .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+            .unwrap()
+    }
+
+    // Flattens a point on the surface into (distance along the polyline, distance along the
+    // sweep)
+    fn unfold(&self, p: Point) -> [f64; 2] {
+        let (_, arc, _) = self.locate(self.to_2d(p));
+        [arc, (p - self.origin).dot(self.sweep())]
+    }
+
+    fn fold(&self, q: [f64; 2]) -> Point {
+        let directions = self.directions();
+        let arcs = self.arcs();
+        let j = (0..directions.len())
+            .rev()
+            .find(|&j| arcs[j] <= q[0])
+            .unwrap_or(0);
+        let (p, d) = (self.points[j], directions[j]);
+        let along = q[0] - arcs[j];
+        self.to_3d([p[0] + d[0] * along, p[1] + d[1] * along]) + self.sweep() * q[1]
+    }
+}
+
+// Cuts a closed mesh along the surface swept by a profile, capping every piece. Each strip of
+// the surface is cut like a plane. The section is the whole cut surface laid out flat, in
+// (distance along the polyline, distance along the sweep) coordinates rather than the mesh's,
+// facing the side y points to.
+pub fn cut_profile(mesh: &Mesh, profile: &Profile) -> CutResult {
+    let refined = refine(mesh, profile);
+    let (below, section) = profile_side(&refined, profile, Side::Below);
+    CutResult {
+        above: profile_side(&refined, profile, Side::Above).0,
+        below,
+        section,
+    }
+}
+
+// The closed pieces of a mesh on one side of a profile
+pub(crate) fn cut_profile_side(mesh: &Mesh, profile: &Profile, side: Side) -> Vec<Mesh> {
+    profile_side(&refine(mesh, profile), profile, side).0
+}
+
+// Splits triangles along every segment's plane, so that none crosses the surface
+fn refine(mesh: &Mesh, profile: &Profile) -> Mesh {
+    let mut refined = mesh.clone();
+    for plane in profile.planes().iter() {
+        let split = split_mesh(&refined, plane);
+        let mut triangles = split.above;
+        triangles.extend(split.below);
+        refined = Mesh::new(split.vertices, triangles);
+    }
+    refined
+}
+
+// The pieces on one side, and their cap unfolded
+fn profile_side(refined: &Mesh, profile: &Profile, side: Side) -> (Vec<Mesh>, Section) {
+    let planes = profile.planes();
+    let tolerance = PLANE_TOLERANCE * refined.size().max(f64::MIN_POSITIVE);
+    let reach = 10.0
+        * (refined.size()
+            + refined
+                .vertices
+                .iter()
+                .map(|&p| (p - profile.origin).len())
+                .fold(0.0, f64::max)
+            + profile.arcs().last().unwrap());
+    let on = |plane: &Plane, i: usize| plane.distance(refined.vertices[i]).abs() <= tolerance;
+
+    let triangles: Vec<Triangle> = refined
+        .triangles
+        .iter()
+        .filter(|triangle| {
+            let idx = triangle.indices();
+            let [a, b, c] = refined.triangle_points(triangle);
+            let q = profile.to_2d(a + ((b - a) + (c - a)) / 3.0);
+
+            // Lying on the surface, it closes off whichever side its normal faces away from
+            let (j, _, distance) = profile.locate(q);
+            let above = if distance <= tolerance && idx.iter().all(|&i| on(&planes[j], i)) {
+                refined.triangle_normal(triangle).dot(planes[j].normal) <= 0.0
+            } else {
+                profile.is_above(q, reach)
+            };
+            above == (side == Side::Above)
+        })
+        .cloned()
+        .collect();
+
+    // Cap the side in the unfolded surface, facing away from the side
+    let normal = match side {
+        Side::Above => Vector::Z,
+        Side::Below => -Vector::Z,
+    };
+    let edges: Vec<(usize, usize)> = open_edges(&triangles)
+        .into_iter()
+        .map(|(a, b)| (b, a))
+        .collect();
+    let flat: Vec<Point> = refined
+        .vertices
+        .iter()
+        .map(|&p| {
+            let [u, v] = profile.unfold(p);
+            Point::new(u, v, 0.0)
+        })
+        .collect();
+    let section = Section::from_edges(Plane::new(normal, Point::zero()), &flat, &edges);
+
+    // Then crease the cap where the surface bends
+    let mut cap = Mesh::new(
+        section.points.clone(),
+        section
+            .triangulate()
+            .into_iter()
+            .map(|[a, b, c]| Triangle::new(a, b, c))
+            .collect(),
+    );
+    for &arc in profile.arcs()[1..profile.points.len() - 1].iter() {
+        let split = split_mesh(&cap, &Plane::new(Vector::X, Point::new(arc, 0.0, 0.0)));
+        let mut triangles = split.above;
+        triangles.extend(split.below);
+        cap = Mesh::new(split.vertices, triangles);
+    }
+
+    let mut vertices = refined.vertices.clone();
+    let mut source = section.source.clone();
+    for &q in cap.vertices[source.len()..].iter() {
+        source.push(vertices.len());
+        vertices.push(profile.fold([q.x, q.y]));
+    }
+    let mut triangles = triangles;
+    for t in cap.triangles.iter() {
+        let [a, b, c] = t.indices().map(|i| source[i]);
+        triangles.push(Triangle::new(a, b, c));
+    }
+
+    (Mesh::new(vertices, triangles).components(), section)
+}
+
+#[test]
+fn test_cut_profile() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+
+    // A step down across the cube, running along Y
+    let plane = Plane::new(Vector::Z, Point::new(0.0, 0.0, 0.5));
+    let step = Profile::step(&plane, Vector::Y, -1.0).unwrap();
+    let cut = cut_profile(mesh, &step);
+
+    assert_eq!(cut.num_pieces(), 2);
+    for piece in cut.above.iter().chain(cut.below.iter()) {
+        assert!(open_edges(&piece.triangles).is_empty());
+    }
+    assert!((cut.above[0].volume() - 4.0).abs() < 1e-9);
+    assert!((cut.below[0].volume() - 4.0).abs() < 1e-9);
+    assert!((cut.above[0].bounds().0.z + 0.5).abs() < 1e-9);
+    assert!((cut.below[0].bounds().1.z - 0.5).abs() < 1e-9);
+    // The section runs down the step too
+    assert!((cut.section.area() - 6.0).abs() < 1e-9);
+
+    // Teeth poking out of both sides of the cube only add volume where they are inside it
+    let plane = Plane::new(Vector::Z, Point::zero());
+    let zigzag = Profile::zigzag(&plane, Vector::Y, 4, 1.0, 0.5).unwrap();
+    let cut = cut_profile(mesh, &zigzag);
+    let volume = cut.above[0].volume() + cut.below[0].volume();
+    assert!((volume - 8.0).abs() < 1e-9);
+    assert!((cut.below[0].volume() - 5.0).abs() < 1e-9);
+
+    assert!(Profile::new(
+        Point::zero(),
+        Vector::Y,
+        Vector::Y,
+        vec![[0.0, 0.0], [1.0, 0.0]]
+    )
+    .is_none());
+}
@@ -0,0 +1,178 @@
+use crate::geometry::polygon;
+use crate::geometry::{Plane, Point, Vector};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// The planar cross-section of a mesh along a cut.
+// Loops are counter-clockwise around the plane's normal for outer boundaries and clockwise
+// for holes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    pub plane: Plane,
+    // In-plane axes used for the 2D coordinates, with u x v along the plane's normal
+    pub u: Vector,
+    pub v: Vector,
+    pub points: Vec<Point>,
+    pub loops: Vec<Vec<usize>>,
+    // Index of the mesh vertex each point came from
+    #[serde(skip)]
+    pub(crate) source: Vec<usize>,
+}
+
+impl Section {
+    pub fn empty(plane: Plane) -> Self {
+        let (u, v) = plane.basis();
+        Section {
+            plane,
+            u,
+            v,
+            points: Vec::new(),
+            loops: Vec::new(),
+            source: Vec::new(),
+        }
+    }
+
+    // Chains directed edges (between mesh vertices lying on the plane) into closed loops.
+    // Dangling chains, which only appear with open meshes, are dropped.
+    pub(crate) fn from_edges(plane: Plane, vertices: &[Point], edges: &[(usize, usize)]) -> Self {
+        let mut section = Section::empty(plane);
+
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &(a, b) in edges.iter() {
+            outgoing.entry(a).or_default().push(b);
+        }
+        let mut starts: Vec<usize> = outgoing.keys().copied().collect();
+        starts.sort_unstable();
+
+        let mut vertex_loops: Vec<Vec<usize>> = Vec::new();
+        for start in starts {
+            while !outgoing[&start].is_empty() {
+                let mut path = vec![start];
+                let mut seen: HashMap<usize, usize> = HashMap::from([(start, 0)]);
+                loop {
+                    let cur = *path.last().unwrap();
+                    let next = match outgoing.get_mut(&cur).and_then(|v| v.pop()) {
+                        Some(n) => n,
+                        None => break,
+                    };
+
+                    match seen.get(&next) {
+                        // Closed a loop, which may be a sub-loop at a non-manifold vertex
+                        Some(&pos) => {
+                            let cycle = path.split_off(pos);
+                            for v in cycle.iter() {
+                                seen.remove(v);
+                            }
+                            if cycle.len() >= 3 {
+                                vertex_loops.push(cycle);
+                            }
+                            seen.insert(next, path.len());
+                            path.push(next);
+                        }
+                        None => {
+                            seen.insert(next, path.len());
+                            path.push(next);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut index: HashMap<usize, usize> = HashMap::new();
+        for vertex_loop in vertex_loops {
+            let ring = vertex_loop
+                .into_iter()
+                .map(|v| {
+                    *index.entry(v).or_insert_with(|| {
+                        section.points.push(vertices[v]);
+                        section.source.push(v);
+                        section.points.len() - 1
+                    })
+                })
+                .collect();
+            section.loops.push(ring);
+        }
+
+        section
+    }
+
+    // Adds a loop of points that came from the given mesh vertices
+    pub(crate) fn add_loop(&mut self, points: Vec<Point>, sources: Vec<usize>) {
+        let start = self.points.len();
+        self.points.extend(points);
+        self.source.extend(sources);
+        self.loops.push((start..self.points.len()).collect());
+    }
+
+    // The part of the section inside some of its loops
+    pub(crate) fn with_loops(&self, loops: Vec<Vec<usize>>) -> Section {
+        Section {
+            plane: self.plane.clone(),
+            u: self.u,
+            v: self.v,
+            points: self.points.clone(),
+            loops,
+            source: self.source.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loops.is_empty()
+    }
+
+    // 2D coordinates of a point in the plane's (u, v) frame
+    pub fn project(&self, p: Point) -> [f64; 2] {
+        let d = p - self.plane.point;
+        [d.dot(self.u), d.dot(self.v)]
+    }
+
+    pub fn unproject(&self, q: [f64; 2]) -> Point {
+        self.plane.point + self.u * q[0] + self.v * q[1]
+    }
+
+    pub fn points_2d(&self) -> Vec<[f64; 2]> {
+        self.points.iter().map(|p| self.project(*p)).collect()
+    }
+
+    // Area enclosed by the section, with holes removed
+    pub fn area(&self) -> f64 {
+        let points = self.points_2d();
+        self.loops
+            .iter()
+            .map(|l| polygon::signed_area(&points, l))
+            .sum::<f64>()
+            / 2.0
+    }
+
+    pub fn contains(&self, q: [f64; 2]) -> bool {
+        self.contains_in(&self.points_2d(), q)
+    }
+
+    // Distance from a 2D point to the closest edge of the section
+    pub fn boundary_distance(&self, q: [f64; 2]) -> f64 {
+        self.boundary_distance_in(&self.points_2d(), q)
+    }
+
+    // Same as contains, with `points_2d` worked out beforehand for repeated queries
+    pub(crate) fn contains_in(&self, points: &[[f64; 2]], q: [f64; 2]) -> bool {
+        self.loops
+            .iter()
+            .filter(|l| polygon::contains(points, l, q))
+            .count()
+            % 2
+            == 1
+    }
+
+    pub(crate) fn boundary_distance_in(&self, points: &[[f64; 2]], q: [f64; 2]) -> f64 {
+        self.loops
+            .iter()
+            .map(|l| polygon::boundary_distance(points, l, q))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // Triangles covering the section, as indices into `points`, wound counter-clockwise
+    // around the plane's normal
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        polygon::triangulate_loops(&self.points_2d(), &self.loops)
+    }
+}
@@ -1,4 +1,5 @@
 use quick_xml::de::DeError;
+use std::fmt;
 use thiserror::Error;
 use zip::result::ZipError;
 
@@ -7,14 +8,22 @@ pub enum Error {
     #[error("Too many models found.")]
     TooManyModels,
     #[error("No model data found in zip.")]
-    #[allow(dead_code)]
     EmptyModel,
+    #[error("Object {0}, or one it is made of, is missing.")]
+    MissingObject(usize),
+    #[error("Part {0} is missing from the package.")]
+    MissingPart(String),
+    #[error("Part {0} has the wrong content type.")]
+    InvalidContentType(String),
     #[error("Failed to unzip 3MF.")]
     #[allow(dead_code)]
     ZipError(#[from] ZipError),
-    #[error("Failed to parse XML.")]
-    #[allow(dead_code)]
-    InvalidXML(#[from] DeError),
+    #[error("Failed to parse XML: {0}")]
+    InvalidXML(Box<XmlError>),
+    #[error("Failed to write XML.")]
+    WriteXML(#[from] quick_xml::Error),
+    #[error("Layer height {0} is not a positive number.")]
+    InvalidLayerHeight(f64),
     #[error("Failed to read units.")]
     #[allow(dead_code)]
     InvalidUnits(String),
@@ -22,3 +31,93 @@ pub enum Error {
     #[allow(dead_code)]
     InvalidZipString(#[from] std::io::Error),
 }
+
+impl From<DeError> for Error {
+    fn from(e: DeError) -> Self {
+        Error::InvalidXML(Box::new(XmlError::new(e.to_string())))
+    }
+}
+
+impl Error {
+    // Names the part of the package an XML error is in, if it doesn't have one yet
+    pub fn in_part(self, part: &str) -> Error {
+        match self {
+            Error::InvalidXML(mut e) => {
+                e.part.get_or_insert_with(|| part.to_string());
+                Error::InvalidXML(e)
+            }
+            e => e,
+        }
+    }
+}
+
+// Where in an XML document something is. Lines and columns count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextPosition {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TextPosition {
+    pub fn find(data: &[u8], offset: usize) -> Self {
+        let before = &data[..offset.min(data.len())];
+        let line_start = before
+            .iter()
+            .rposition(|&c| c == b'\n')
+            .map_or(0, |k| k + 1);
+        TextPosition {
+            offset,
+            line: before.iter().filter(|&&c| c == b'\n').count() + 1,
+            column: offset - line_start + 1,
+        }
+    }
+}
+
+// What is wrong with an XML part, and as much as is known of where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlError {
+    pub message: String,
+    // Part (archive entry) name, e.g. /3D/3dmodel.model
+    pub part: Option<String>,
+    pub position: Option<TextPosition>,
+    // Path to the element, e.g. model/resources/object[3]/mesh/triangles/triangle[1022].
+    // Elements are numbered from 1 among those of the same name, and a first one isn't.
+    pub path: Option<String>,
+    pub attribute: Option<String>,
+}
+
+impl XmlError {
+    pub fn new(message: String) -> Self {
+        XmlError {
+            message,
+            part: None,
+            position: None,
+            path: None,
+            attribute: None,
+        }
+    }
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(attribute) = self.attribute.as_deref() {
+            write!(f, " in attribute {}", attribute)?;
+        }
+        if let Some(path) = self.path.as_deref() {
+            write!(f, " of {}", path)?;
+        }
+        if let Some(part) = self.part.as_deref() {
+            write!(f, " in {}", part)?;
+        }
+        if let Some(p) = self.position {
+            write!(
+                f,
+                " at line {}, column {} (byte {})",
+                p.line, p.column, p.offset
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,538 @@
+use crate::geometry::containment::{containment, Containment, RAY};
+use crate::geometry::{Plane, Point, Vector};
+use crate::threemf::Mesh;
+
+// A bounding volume hierarchy over the triangles of a mesh, for finding the few triangles a
+// query can touch without looking at all of them.
+//
+// Nodes are split at the median of their longest axis. With the `parallel` feature the two
+// halves of large nodes are built on separate threads. Without it (as in the wasm build)
+// everything runs on the calling thread and gives the same tree.
+
+// Triangles per leaf
+const LEAF_SIZE: usize = 4;
+// Nodes with fewer triangles than this are not worth handing to another thread
+#[cfg(feature = "parallel")]
+const PARALLEL_SIZE: usize = 4096;
+
+// Axis aligned box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    pub fn new(min: Point, max: Point) -> Self {
+        Aabb { min, max }
+    }
+
+    // Contains nothing, and adding anything to it gives that thing's box
+    pub fn empty() -> Self {
+        let inf = f64::INFINITY;
+        Aabb::new(Point::new(inf, inf, inf), Point::new(-inf, -inf, -inf))
+    }
+
+    pub fn of_points(points: &[Point]) -> Self {
+        points.iter().fold(Aabb::empty(), |b, &p| b.add(p))
+    }
+
+    pub fn add(&self, p: Point) -> Self {
+        Aabb::new(
+            Point::new(
+                self.min.x.min(p.x),
+                self.min.y.min(p.y),
+                self.min.z.min(p.z),
+            ),
+            Point::new(
+                self.max.x.max(p.x),
+                self.max.y.max(p.y),
+                self.max.z.max(p.z),
+            ),
+        )
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.add(other.min).add(other.max)
+    }
+
+    pub fn center(&self) -> Point {
+        self.min + (self.max - self.min) / 2.0
+    }
+
+    // Boxes touching at a face, edge or corner overlap
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    // Squared distance from a point to the box, zero inside it
+    pub fn distance_squared(&self, p: Point) -> f64 {
+        let d = |v: f64, min: f64, max: f64| (min - v).max(0.0).max(v - max);
+        let (x, y, z) = (
+            d(p.x, self.min.x, self.max.x),
+            d(p.y, self.min.y, self.max.y),
+            d(p.z, self.min.z, self.max.z),
+        );
+        x * x + y * y + z * z
+    }
+
+    // Range of signed distances from the plane over the box
+    fn plane_range(&self, plane: &Plane) -> (f64, f64) {
+        let center = plane.distance(self.center());
+        let half = self.max - self.min;
+        let n = plane.normal.abs();
+        let extent = (half.x * n.x + half.y * n.y + half.z * n.z) / 2.0;
+        (center - extent, center + extent)
+    }
+
+    // Where along the ray it is inside the box, if anywhere from `from` to `to`
+    fn ray_range(&self, ray: &Ray, from: f64, to: f64) -> Option<(f64, f64)> {
+        let (mut near, mut far) = (from, to);
+        let o = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let d = [ray.direction.x, ray.direction.y, ray.direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+        for k in 0..3 {
+            if d[k] == 0.0 {
+                if o[k] < min[k] || o[k] > max[k] {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min[k] - o[k]) / d[k], (max[k] - o[k]) / d[k]);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+            if near > far {
+                return None;
+            }
+        }
+        Some((near, far))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point,
+    pub direction: Vector,
+}
+
+impl Ray {
+    pub fn new(origin: Point, direction: Vector) -> Self {
+        Ray { origin, direction }
+    }
+
+    pub fn at(&self, distance: f64) -> Point {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub triangle: usize,
+    // How far along the ray the hit is, in multiples of its direction
+    pub distance: f64,
+    pub point: Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosestPoint {
+    pub triangle: usize,
+    pub point: Point,
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    // Triangles order[start..start + count]
+    Leaf { start: usize, count: usize },
+    // The left child is the next node, the right child is at `right`
+    Inner { right: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    triangles: Vec<[Point; 3]>,
+    nodes: Vec<Node>,
+    // Triangle indices, grouped by leaf
+    order: Vec<usize>,
+}
+
+struct Item {
+    triangle: usize,
+    bounds: Aabb,
+    center: Point,
+}
+
+#[cfg(feature = "parallel")]
+fn join<A: Send, B: Send>(
+    size: usize,
+    a: impl FnOnce() -> A + Send,
+    b: impl FnOnce() -> B + Send,
+) -> (A, B) {
+    if size >= PARALLEL_SIZE {
+        rayon::join(a, b)
+    } else {
+        (a(), b())
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn join<A, B>(_size: usize, a: impl FnOnce() -> A, b: impl FnOnce() -> B) -> (A, B) {
+    (a(), b())
+}
+
+// Builds the nodes for a set of triangles, numbered from 0 and referring to the items from 0
+fn build(items: &mut [Item]) -> Vec<Node> {
+    let bounds = items
+        .iter()
+        .fold(Aabb::empty(), |b, item| b.union(&item.bounds));
+    if items.len() <= LEAF_SIZE {
+        let kind = Kind::Leaf {
+            start: 0,
+            count: items.len(),
+        };
+        return vec![Node { bounds, kind }];
+    }
+
+    let centers = items
+        .iter()
+        .fold(Aabb::empty(), |b, item| b.add(item.center));
+    let size = centers.max - centers.min;
+    let key: fn(&Item) -> f64 = if size.x >= size.y && size.x >= size.z {
+        |item| item.center.x
+    } else if size.y >= size.z {
+        |item| item.center.y
+    } else {
+        |item| item.center.z
+    };
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |a, b| key(a).partial_cmp(&key(b)).unwrap());
+
+    let count = items.len();
+    let (low, high) = items.split_at_mut(middle);
+    let (left, right) = join(count, || build(low), || build(high));
+
+    let mut nodes = Vec::with_capacity(1 + left.len() + right.len());
+    nodes.push(Node {
+        bounds,
+        kind: Kind::Inner {
+            right: 1 + left.len(),
+        },
+    });
+    let right_start = 1 + left.len();
+    nodes.extend(shift(left, 1, 0));
+    nodes.extend(shift(right, right_start, middle));
+    nodes
+}
+
+// Renumbers a subtree's nodes and items for where it ends up in its parent
+fn shift(nodes: Vec<Node>, node_offset: usize, item_offset: usize) -> impl Iterator<Item = Node> {
+    nodes.into_iter().map(move |mut node| {
+        node.kind = match node.kind {
+            Kind::Leaf { start, count } => Kind::Leaf {
+                start: start + item_offset,
+                count,
+            },
+            Kind::Inner { right } => Kind::Inner {
+                right: right + node_offset,
+            },
+        };
+        node
+    })
+}
+
+// Möller-Trumbore, hitting either side of the triangle
+fn ray_triangle(ray: &Ray, [a, b, c]: [Point; 3]) -> Option<f64> {
+    let (ab, ac) = (b - a, c - a);
+    let p = ray.direction.cross(ac);
+    let det = ab.dot(p);
+    if det == 0.0 {
+        return None;
+    }
+    let s = ray.origin - a;
+    let u = s.dot(p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(ab);
+    let v = ray.direction.dot(q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) / det;
+    if t >= 0.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// Closest point on a triangle, by which of its regions the point projects into (Ericson,
+// Real-Time Collision Detection 5.1.5)
+fn closest_on_triangle(p: Point, [a, b, c]: [Point; 3]) -> Point {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = va + vb + vc;
+    a + ab * (vb / denominator) + ac * (vc / denominator)
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let triangles: Vec<[Point; 3]> = mesh
+            .triangles
+            .iter()
+            .map(|t| mesh.triangle_points(t))
+            .collect();
+        let mut items: Vec<Item> = triangles
+            .iter()
+            .enumerate()
+            .map(|(triangle, points)| {
+                let bounds = Aabb::of_points(points);
+                Item {
+                    triangle,
+                    bounds,
+                    center: bounds.center(),
+                }
+            })
+            .collect();
+
+        let nodes = build(&mut items);
+        Bvh {
+            triangles,
+            nodes,
+            order: items.iter().map(|item| item.triangle).collect(),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds
+    }
+
+    // Goes through the tree, entering nodes `enter` accepts and handing every triangle in the
+    // leaves reached to `leaf`
+    fn visit<S>(
+        &self,
+        state: &mut S,
+        enter: impl Fn(&S, &Aabb) -> bool,
+        mut leaf: impl FnMut(&mut S, usize),
+    ) {
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !enter(state, &node.bounds) {
+                continue;
+            }
+            match node.kind {
+                Kind::Leaf { start, count } => {
+                    for &t in self.order[start..start + count].iter() {
+                        leaf(state, t);
+                    }
+                }
+                Kind::Inner { right } => {
+                    stack.push(right);
+                    stack.push(i + 1);
+                }
+            }
+        }
+    }
+
+    // The nearest triangle the ray hits, from either side
+    pub fn first_hit(&self, ray: &Ray) -> Option<RayHit> {
+        let mut best: Option<RayHit> = None;
+        self.visit(
+            &mut best,
+            |best, bounds| {
+                let to = best.map_or(f64::INFINITY, |hit| hit.distance);
+                bounds.ray_range(ray, 0.0, to).is_some()
+            },
+            |best, t| {
+                if let Some(distance) = ray_triangle(ray, self.triangles[t]) {
+                    if best.is_none_or(|hit| distance < hit.distance) {
+                        *best = Some(RayHit {
+                            triangle: t,
+                            distance,
+                            point: ray.at(distance),
+                        });
+                    }
+                }
+            },
+        );
+        best
+    }
+
+    // Every triangle the ray hits, nearest first
+    pub fn all_hits(&self, ray: &Ray) -> Vec<RayHit> {
+        let mut hits: Vec<RayHit> = Vec::new();
+        self.visit(
+            &mut hits,
+            |_, bounds| bounds.ray_range(ray, 0.0, f64::INFINITY).is_some(),
+            |hits, t| {
+                if let Some(distance) = ray_triangle(ray, self.triangles[t]) {
+                    hits.push(RayHit {
+                        triangle: t,
+                        distance,
+                        point: ray.at(distance),
+                    });
+                }
+            },
+        );
+        hits.sort_by(|a, b| {
+            (a.distance, a.triangle)
+                .partial_cmp(&(b.distance, b.triangle))
+                .unwrap()
+        });
+        hits
+    }
+
+    // Triangles touching or crossing a plane
+    pub fn plane_overlap(&self, plane: &Plane) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        self.visit(
+            &mut found,
+            |_, bounds| {
+                let (low, high) = bounds.plane_range(plane);
+                low <= 0.0 && high >= 0.0
+            },
+            |found, t| {
+                let d = self.triangles[t].map(|p| plane.distance(p));
+                if d.iter().any(|&x| x <= 0.0) && d.iter().any(|&x| x >= 0.0) {
+                    found.push(t);
+                }
+            },
+        );
+        found.sort_unstable();
+        found
+    }
+
+    // Triangles whose bounding boxes overlap the given box
+    pub fn aabb_overlap(&self, bounds: &Aabb) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        self.visit(
+            &mut found,
+            |_, node| node.overlaps(bounds),
+            |found, t| {
+                if Aabb::of_points(&self.triangles[t]).overlaps(bounds) {
+                    found.push(t);
+                }
+            },
+        );
+        found.sort_unstable();
+        found
+    }
+
+    // See geometry::containment. Only the triangles near the ray are checked.
+    pub fn containment(&self, p: Point) -> Containment {
+        let ray = Ray::new(p, RAY);
+        let mut candidates: Vec<usize> = Vec::new();
+        self.visit(
+            &mut candidates,
+            |_, bounds| bounds.ray_range(&ray, 0.0, f64::INFINITY).is_some(),
+            |candidates, t| candidates.push(t),
+        );
+        containment(candidates.into_iter().map(|t| self.triangles[t]), p)
+    }
+
+    // The point on the mesh's surface closest to `p`. None for an empty mesh.
+    pub fn closest_point(&self, p: Point) -> Option<ClosestPoint> {
+        let mut best: Option<ClosestPoint> = None;
+        self.visit(
+            &mut best,
+            |best, bounds| {
+                let limit = best.map_or(f64::INFINITY, |c| c.distance * c.distance);
+                bounds.distance_squared(p) <= limit
+            },
+            |best, t| {
+                let point = closest_on_triangle(p, self.triangles[t]);
+                let distance = (point - p).len();
+                if best.is_none_or(|c| distance < c.distance) {
+                    *best = Some(ClosestPoint {
+                        triangle: t,
+                        point,
+                        distance,
+                    });
+                }
+            },
+        );
+        best
+    }
+}
+
+#[test]
+fn test_bvh() {
+    use crate::geometry::primitives::sphere;
+
+    let mesh = sphere(2.0, 48, 24);
+    let bvh = Bvh::new(&mesh);
+    let brute = Bvh {
+        triangles: bvh.triangles.clone(),
+        nodes: vec![Node {
+            bounds: bvh.bounds(),
+            kind: Kind::Leaf {
+                start: 0,
+                count: mesh.triangles.len(),
+            },
+        }],
+        order: (0..mesh.triangles.len()).collect(),
+    };
+
+    for i in 0..20 {
+        let angle = i as f64 * 0.7;
+        let origin = Point::new(3.0 * angle.cos(), 3.0 * angle.sin(), 0.5 * i as f64 - 5.0);
+        let ray = Ray::new(origin, Point::new(0.1, -0.2, 0.3) - origin);
+
+        let hit = bvh.first_hit(&ray);
+        assert_eq!(hit, brute.first_hit(&ray));
+        let hits = bvh.all_hits(&ray);
+        assert_eq!(hits, brute.all_hits(&ray));
+        // In through one side and out through the other
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hit, Some(hits[0]));
+        assert!((hits[0].point - Point::zero()).len() < 2.0 + 1e-9);
+
+        let plane = Plane::new(Vector::new(angle.sin(), 1.0, angle.cos()), origin / 2.0);
+        assert_eq!(bvh.plane_overlap(&plane), brute.plane_overlap(&plane));
+
+        let bounds = Aabb::new(origin / 2.0, origin / 2.0 + Vector::new(1.0, 1.0, 1.0));
+        assert_eq!(bvh.aabb_overlap(&bounds), brute.aabb_overlap(&bounds));
+
+        let closest = bvh.closest_point(origin).unwrap();
+        assert!((closest.distance - brute.closest_point(origin).unwrap().distance).abs() < 1e-12);
+        assert!((closest.distance - ((origin - Point::zero()).len() - 2.0)).abs() < 0.01);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::geometry::{Point, Vector};
+use robust::{orient2d, orient3d, Coord, Coord3D};
+
+// Whether a point is inside a closed mesh, decided exactly.
+//
+// A ray is cast from the point along +X and the triangles it crosses are counted, +1 where it
+// leaves the solid through them and -1 where it enters. The ray is nudged by an infinitesimal
+// amount (first along Y, then Z), so that where it goes through an edge or a vertex exactly
+// one of the triangles around it counts, and triangles seen edge-on never do. All the
+// decisions are exact orientation tests on the input coordinates.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Inside,
+    Outside,
+    // Exactly on one of the triangles
+    OnSurface,
+}
+
+// The direction the ray goes in
+pub(crate) const RAY: Vector = Vector::X;
+
+fn sign(v: f64) -> i32 {
+    if v > 0.0 {
+        1
+    } else if v < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn coord(p: Point) -> Coord3D<f64> {
+    Coord3D {
+        x: p.x,
+        y: p.y,
+        z: p.z,
+    }
+}
+
+// Side of the line from a to b the point q is on, seen down the ray (in Y-Z), with q moved by
+// (ε, ε²) to break ties
+fn side(a: Point, b: Point, q: Point) -> i32 {
+    let yz = |p: Point| Coord { x: p.y, y: p.z };
+    let s = sign(orient2d(yz(a), yz(b), yz(q)));
+    if s != 0 {
+        return s;
+    }
+    // The derivatives of the determinant with respect to q.y and q.z
+    match a.z.partial_cmp(&b.z) {
+        Some(std::cmp::Ordering::Greater) => 1,
+        Some(std::cmp::Ordering::Less) => -1,
+        _ => sign(b.y - a.y),
+    }
+}
+
+// Whether a point in the plane of a triangle is on it, edges included
+fn on_triangle(p: Point, [a, b, c]: [Point; 3]) -> bool {
+    let normal = (b - a).cross(c - a);
+    let n = [normal.x.abs(), normal.y.abs(), normal.z.abs()];
+    // Look along the axis the triangle is most square to
+    let flat = |p: Point| {
+        if n[0] >= n[1] && n[0] >= n[2] {
+            Coord { x: p.y, y: p.z }
+        } else if n[1] >= n[2] {
+            Coord { x: p.z, y: p.x }
+        } else {
+            Coord { x: p.x, y: p.y }
+        }
+    };
+    let (a, b, c, p) = (flat(a), flat(b), flat(c), flat(p));
+    let s = [orient2d(a, b, p), orient2d(b, c, p), orient2d(c, a, p)].map(sign);
+    (s.iter().all(|&x| x >= 0) || s.iter().all(|&x| x <= 0)) && s.iter().any(|&x| x != 0)
+}
+
+// Net number of times the solid wraps around the point, or None if the point is on one of the
+// triangles. Triangles the ray from the point cannot reach may be left out.
+pub fn winding_count(triangles: impl IntoIterator<Item = [Point; 3]>, p: Point) -> Option<i32> {
+    let mut count = 0;
+    for [a, b, c] in triangles {
+        let height = sign(orient3d(coord(a), coord(b), coord(c), coord(p)));
+        if height == 0 && on_triangle(p, [a, b, c]) {
+            return None;
+        }
+
+        let s = side(a, b, p);
+        if s == 0 || side(b, c, p) != s || side(c, a, p) != s {
+            continue;
+        }
+        // The ray goes through the triangle's outline. `s` is the sign of the normal's X, and
+        // the triangle is ahead when the point is behind it.
+        if height == s {
+            count += s;
+        }
+    }
+    Some(count)
+}
+
+// Checks every triangle. See `Bvh::containment` for asking about many points.
+pub fn containment(triangles: impl IntoIterator<Item = [Point; 3]>, p: Point) -> Containment {
+    match winding_count(triangles, p) {
+        None => Containment::OnSurface,
+        Some(count) if count > 0 => Containment::Inside,
+        Some(_) => Containment::Outside,
+    }
+}
+
+#[test]
+fn test_containment() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    let bvh = mesh.bvh();
+    let triangles = || mesh.triangles.iter().map(|t| mesh.triangle_points(t));
+
+    // Rays through the middle of faces, along diagonals, edges and corners
+    let values = [-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0];
+    for x in values {
+        for y in values {
+            for z in values {
+                let p = Point::new(x, y, z);
+                let outside = [x, y, z].iter().any(|v: &f64| v.abs() > 1.0);
+                let expected = if outside {
+                    Containment::Outside
+                } else if [x, y, z].iter().any(|v: &f64| v.abs() == 1.0) {
+                    Containment::OnSurface
+                } else {
+                    Containment::Inside
+                };
+                assert_eq!(containment(triangles(), p), expected);
+                assert_eq!(bvh.containment(p), expected);
+            }
+        }
+    }
+
+    // Turned inside out, the cube counts as wrapping the other way
+    let flipped: Vec<[Point; 3]> = triangles().map(|[a, b, c]| [a, c, b]).collect();
+    assert_eq!(winding_count(flipped, Point::zero()), Some(-1));
+}
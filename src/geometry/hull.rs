@@ -0,0 +1,297 @@
+use crate::geometry::{Point, Triangle, Vector};
+use crate::threemf::Mesh;
+use std::collections::{HashMap, VecDeque};
+
+// Quickhull in 3D.
+//
+// Points closer to a face than a small tolerance (relative to the size of the point cloud)
+// count as lying on it, so duplicate and coplanar points never end up as hull vertices of
+// sliver faces. Coplanar regions of the hull come out as several triangles in one plane.
+
+struct Face {
+    vertices: [usize; 3],
+    normal: Vector,
+    offset: f64,
+    // Points outside this face that have not been processed yet
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl Face {
+    fn new(points: &[Point], vertices: [usize; 3]) -> Self {
+        let [a, b, c] = vertices.map(|i| points[i]);
+        let normal = (b - a).cross(c - a).unit();
+        Face {
+            vertices,
+            normal,
+            offset: normal.dot(a - Point::zero()),
+            outside: Vec::new(),
+            alive: true,
+        }
+    }
+
+    fn distance(&self, p: Point) -> f64 {
+        self.normal.dot(p - Point::zero()) - self.offset
+    }
+}
+
+// Convex hull of a set of points, as a closed mesh wound outwards.
+// Returns None when the points do not span a volume (fewer than four points, or all of them
+// collinear or coplanar), or when any of them isn't finite.
+pub fn convex_hull(points: &[Point]) -> Option<Mesh> {
+    if points.len() < 4
+        || !points
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    {
+        return None;
+    }
+
+    let (min, max) = points.iter().fold((points[0], points[0]), |(lo, hi), p| {
+        (
+            Point::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z)),
+            Point::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z)),
+        )
+    });
+    let tolerance = 1e-9 * (max - min).len().max(f64::MIN_POSITIVE);
+
+    let simplex = initial_simplex(points, tolerance)?;
+
+    let mut faces: Vec<Face> = Vec::new();
+    // Directed edge -> face owning it
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+
+    let [a, b, c, d] = simplex;
+    let mut initial = vec![[a, b, c], [a, c, d], [a, d, b], [b, d, c]];
+    // Wind the tetrahedron outwards
+    if Face::new(points, initial[0]).distance(points[d]) > 0.0 {
+        for f in initial.iter_mut() {
+            f.swap(1, 2);
+        }
+    }
+    for vertices in initial {
+        add_face(&mut faces, &mut edges, points, vertices);
+    }
+
+    // Hand every other point to the first face it is outside of
+    for (i, &p) in points.iter().enumerate() {
+        if simplex.contains(&i) {
+            continue;
+        }
+        if let Some(face) = faces.iter_mut().find(|f| f.distance(p) > tolerance) {
+            face.outside.push(i);
+        }
+    }
+
+    let mut pending: VecDeque<usize> = (0..faces.len()).collect();
+    while let Some(start) = pending.pop_front() {
+        if !faces[start].alive || faces[start].outside.is_empty() {
+            continue;
+        }
+
+        // The furthest point outside this face is the next hull vertex
+        let eye = *faces[start]
+            .outside
+            .iter()
+            .max_by(|&&i, &&j| {
+                let (di, dj) = (
+                    faces[start].distance(points[i]),
+                    faces[start].distance(points[j]),
+                );
+                di.total_cmp(&dj)
+            })
+            .unwrap();
+        let eye_point = points[eye];
+
+        // Flood fill the faces that can see the eye point
+        let mut visible: Vec<usize> = vec![start];
+        let mut is_visible: HashMap<usize, bool> = HashMap::from([(start, true)]);
+        let mut k = 0;
+        while k < visible.len() {
+            let f = visible[k];
+            k += 1;
+            let v = faces[f].vertices;
+            for (p, q) in [(v[0], v[1]), (v[1], v[2]), (v[2], v[0])] {
+                let neighbour = match edges.get(&(q, p)) {
+                    Some(&n) => n,
+                    None => continue,
+                };
+                if is_visible.contains_key(&neighbour) {
+                    continue;
+                }
+                let sees = faces[neighbour].distance(eye_point) > tolerance;
+                is_visible.insert(neighbour, sees);
+                if sees {
+                    visible.push(neighbour);
+                }
+            }
+        }
+
+        // The horizon is made of the edges between visible and hidden faces
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        for &f in visible.iter() {
+            let v = faces[f].vertices;
+            for (p, q) in [(v[0], v[1]), (v[1], v[2]), (v[2], v[0])] {
+                let hidden = match edges.get(&(q, p)) {
+                    Some(n) => !is_visible.get(n).copied().unwrap_or(false),
+                    None => true,
+                };
+                if hidden {
+                    horizon.push((p, q));
+                }
+            }
+        }
+
+        // Retire the visible faces, keeping hold of their outside points
+        let mut orphans: Vec<usize> = Vec::new();
+        for &f in visible.iter() {
+            faces[f].alive = false;
+            orphans.append(&mut faces[f].outside);
+            let v = faces[f].vertices;
+            for (p, q) in [(v[0], v[1]), (v[1], v[2]), (v[2], v[0])] {
+                if edges.get(&(p, q)) == Some(&f) {
+                    edges.remove(&(p, q));
+                }
+            }
+        }
+
+        // Cone the horizon to the eye point
+        let first_new = faces.len();
+        for &(p, q) in horizon.iter() {
+            add_face(&mut faces, &mut edges, points, [p, q, eye]);
+        }
+
+        for i in orphans {
+            if i == eye {
+                continue;
+            }
+            if let Some(face) = faces[first_new..]
+                .iter_mut()
+                .find(|f| f.distance(points[i]) > tolerance)
+            {
+                face.outside.push(i);
+            }
+        }
+        pending.extend(first_new..faces.len());
+    }
+
+    // Compact the vertices used by the surviving faces
+    let mut remap: HashMap<usize, usize> = HashMap::new();
+    let mut hull = Mesh::default();
+    for face in faces.iter().filter(|f| f.alive) {
+        let [a, b, c] = face.vertices.map(|v| {
+            *remap.entry(v).or_insert_with(|| {
+                hull.vertices.push(points[v]);
+                hull.vertices.len() - 1
+            })
+        });
+        hull.triangles.push(Triangle::new(a, b, c));
+    }
+    Some(hull)
+}
+
+fn add_face(
+    faces: &mut Vec<Face>,
+    edges: &mut HashMap<(usize, usize), usize>,
+    points: &[Point],
+    vertices: [usize; 3],
+) {
+    let id = faces.len();
+    let [a, b, c] = vertices;
+    for edge in [(a, b), (b, c), (c, a)] {
+        edges.insert(edge, id);
+    }
+    faces.push(Face::new(points, vertices));
+}
+
+// Four points spanning a tetrahedron with as much volume as is cheaply found
+fn initial_simplex(points: &[Point], tolerance: f64) -> Option<[usize; 4]> {
+    // Extreme points along each axis, then the pair of them furthest apart
+    let mut extremes: Vec<usize> = Vec::new();
+    for axis in [Vector::X, Vector::Y, Vector::Z] {
+        let along = |i: &usize| (points[*i] - Point::zero()).dot(axis);
+        let cmp = |i: &usize, j: &usize| along(i).total_cmp(&along(j));
+        let indices = 0..points.len();
+        extremes.push(indices.clone().min_by(cmp).unwrap());
+        extremes.push(indices.max_by(cmp).unwrap());
+    }
+    let mut best = (0.0, 0, 0);
+    for &i in extremes.iter() {
+        for &j in extremes.iter() {
+            let d = (points[i] - points[j]).len();
+            if d > best.0 {
+                best = (d, i, j);
+            }
+        }
+    }
+    let (spread, a, b) = best;
+    if spread <= tolerance {
+        return None;
+    }
+
+    // Furthest from the line ab
+    let dir = (points[b] - points[a]).unit();
+    let line_distance = |i: usize| {
+        let v = points[i] - points[a];
+        (v - dir * v.dot(dir)).len()
+    };
+    let c = (0..points.len())
+        .max_by(|&i, &j| line_distance(i).total_cmp(&line_distance(j)))
+        .unwrap();
+    if line_distance(c) <= tolerance {
+        return None;
+    }
+
+    // Furthest from the plane abc
+    let normal = (points[b] - points[a]).cross(points[c] - points[a]).unit();
+    let plane_distance = |i: usize| normal.dot(points[i] - points[a]).abs();
+    let d = (0..points.len())
+        .max_by(|&i, &j| plane_distance(i).total_cmp(&plane_distance(j)))
+        .unwrap();
+    if plane_distance(d) <= tolerance {
+        return None;
+    }
+
+    Some([a, b, c, d])
+}
+
+#[test]
+fn test_convex_hull() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mut points = model.objects[0].mesh.vertices.clone();
+
+    // Duplicates, interior points, and points lying on the faces and edges
+    points.extend(model.objects[0].mesh.vertices.clone());
+    for i in -4..=4 {
+        for j in -4..=4 {
+            let (u, v) = (i as f64 / 4.0, j as f64 / 4.0);
+            points.push(Point::new(u, v, 1.0));
+            points.push(Point::new(1.0, u, v));
+            points.push(Point::new(u * 0.5, v * 0.5, 0.0));
+        }
+    }
+
+    let hull = convex_hull(&points).unwrap();
+    assert!((hull.volume() - 8.0).abs() < 1e-9);
+    assert!(crate::cut::open_edges(&hull.triangles).is_empty());
+
+    // Every triangle faces outwards
+    for t in hull.triangles.iter() {
+        let [p, _, _] = hull.triangle_points(t);
+        assert!(hull.triangle_normal(t).dot(p - Point::zero()) > 0.0);
+    }
+
+    let flat: Vec<Point> = points.iter().map(|p| Point::new(p.x, p.y, 0.0)).collect();
+    assert!(convex_hull(&flat).is_none());
+
+    // A file can hold coordinates that aren't numbers
+    let mut broken = model.clone();
+    broken.objects[0].mesh.vertices[3].x = f64::NAN;
+    let read = crate::threemf::Model::from_raw_data(&broken.to_raw_data().unwrap()).unwrap();
+    assert!(read.objects[0].mesh.vertices[3].x.is_nan());
+    assert!(read.objects[0].mesh.convex_hull().is_none());
+    points[3].x = f64::INFINITY;
+    assert!(convex_hull(&points).is_none());
+}
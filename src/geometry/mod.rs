@@ -1,9 +1,18 @@
+pub use obb::OrientedBox;
 pub use plane::Plane;
 pub use point::Point;
+pub use transform::Transform;
 pub use triangle::Triangle;
 pub use vector::Vector;
 
+pub mod bvh;
+pub mod containment;
+pub mod hull;
+pub mod obb;
 pub mod plane;
 pub mod point;
+pub mod polygon;
+pub mod primitives;
+pub mod transform;
 pub mod triangle;
 pub mod vector;
@@ -0,0 +1,296 @@
+use crate::geometry::hull::convex_hull;
+use crate::geometry::{Point, Transform, Vector};
+use serde::Serialize;
+use std::collections::HashSet;
+
+// A box with arbitrary orientation
+#[derive(Debug, Clone, Serialize)]
+pub struct OrientedBox {
+    pub center: Point,
+    // Orthonormal and right-handed, ordered from the longest side to the shortest
+    pub axes: [Vector; 3],
+    pub half_extents: [f64; 3],
+}
+
+impl OrientedBox {
+    // Box aligned with the coordinate axes
+    pub fn axis_aligned(min: Point, max: Point) -> Self {
+        let half = (max - min) / 2.0;
+        OrientedBox::new(
+            min + half,
+            [Vector::X, Vector::Y, Vector::Z],
+            [half.x, half.y, half.z],
+        )
+    }
+
+    // Sorts the axes by extent and makes them right-handed
+    fn new(center: Point, axes: [Vector; 3], half_extents: [f64; 3]) -> Self {
+        let mut order = [0, 1, 2];
+        order.sort_by(|&a, &b| half_extents[b].total_cmp(&half_extents[a]));
+        let mut axes = order.map(|i| axes[i]);
+        if axes[0].cross(axes[1]).dot(axes[2]) < 0.0 {
+            axes[2] = -axes[2];
+        }
+        OrientedBox {
+            center,
+            axes,
+            half_extents: order.map(|i| half_extents[i]),
+        }
+    }
+
+    // Full side lengths, longest first
+    pub fn size(&self) -> [f64; 3] {
+        self.half_extents.map(|h| h * 2.0)
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.size().iter().product()
+    }
+
+    pub fn corners(&self) -> [Point; 8] {
+        let mut corners = [self.center; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for k in 0..3 {
+                let sign = if i & (1 << k) == 0 { -1.0 } else { 1.0 };
+                *corner += self.axes[k] * (sign * self.half_extents[k]);
+            }
+        }
+        corners
+    }
+
+    // Whether the box fits in a build volume of the given dimensions, when turned so its
+    // sides line up with the printer's axes
+    pub fn fits(&self, build_volume: [f64; 3]) -> bool {
+        let mut available = build_volume;
+        available.sort_by(|a, b| b.total_cmp(a));
+        self.size()
+            .iter()
+            .zip(available.iter())
+            .all(|(need, have)| need <= have)
+    }
+
+    // Rigid transform taking the box's axes onto X, Y and Z, with its center at the origin
+    pub fn to_local(&self) -> Transform {
+        let [a, b, c] = self.axes;
+        let rotation = Transform {
+            matrix: [[a.x, a.y, a.z], [b.x, b.y, b.z], [c.x, c.y, c.z]],
+            translation: Vector::zero(),
+        };
+        Transform::translation(Point::zero() - self.center).then(&rotation)
+    }
+}
+
+// Oriented bounding box of a set of points.
+//
+// Tries every face direction of the convex hull as one of the box's axes, and fits the
+// minimum area rectangle (which has a side along a hull edge) around the points projected
+// onto that face. This finds the minimum volume box whenever it lies flush with a hull face,
+// which is the common case, and is never worse than the axis aligned box.
+pub fn oriented_bounding_box(points: &[Point]) -> OrientedBox {
+    if points.is_empty() {
+        return OrientedBox::axis_aligned(Point::zero(), Point::zero());
+    }
+
+    let (min, max) = points.iter().fold((points[0], points[0]), |(lo, hi), p| {
+        (
+            Point::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z)),
+            Point::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z)),
+        )
+    });
+    let mut best = OrientedBox::axis_aligned(min, max);
+
+    let hull = match convex_hull(points) {
+        Some(h) => h,
+        None => return best,
+    };
+
+    let mut tried: HashSet<(i64, i64, i64)> = HashSet::new();
+    for triangle in hull.triangles.iter() {
+        let normal = hull.triangle_normal(triangle);
+        if normal.len() <= 0.0 {
+            continue;
+        }
+        let n = normal.unit();
+        // Opposite faces give the same box
+        let key = quantise(if n.x + n.y * 1e-3 + n.z * 1e-6 < 0.0 {
+            -n
+        } else {
+            n
+        });
+        if !tried.insert(key) {
+            continue;
+        }
+
+        let candidate = flush_box(&hull.vertices, n);
+        if candidate.volume() < best.volume() {
+            best = candidate;
+        }
+    }
+
+    best
+}
+
+fn quantise(n: Vector) -> (i64, i64, i64) {
+    (
+        (n.x * 1e6).round() as i64,
+        (n.y * 1e6).round() as i64,
+        (n.z * 1e6).round() as i64,
+    )
+}
+
+// A rectangle in the plane, with its first side along `axis`
+struct Rectangle {
+    area: f64,
+    axis: [f64; 2],
+    center: [f64; 2],
+    half: [f64; 2],
+}
+
+// Smallest box with one axis along `normal`
+fn flush_box(points: &[Point], normal: Vector) -> OrientedBox {
+    let other = if normal.x.abs() < 0.9 {
+        Vector::X
+    } else {
+        Vector::Y
+    };
+    let u = normal.cross(other).unit();
+    let v = normal.cross(u);
+
+    let origin = Point::zero();
+    let projected: Vec<[f64; 2]> = points
+        .iter()
+        .map(|p| [(p - origin).dot(u), (p - origin).dot(v)])
+        .collect();
+    let heights: Vec<f64> = points.iter().map(|p| (p - origin).dot(normal)).collect();
+    let (low, high) = heights
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &h| {
+            (lo.min(h), hi.max(h))
+        });
+
+    let ring = convex_hull_2d(&projected);
+    let mut best: Option<Rectangle> = None;
+    for k in 0..ring.len() {
+        let (a, b) = (projected[ring[k]], projected[ring[(k + 1) % ring.len()]]);
+        let len = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt();
+        if len <= 0.0 {
+            continue;
+        }
+        let e = [(b[0] - a[0]) / len, (b[1] - a[1]) / len];
+        let f = [-e[1], e[0]];
+
+        let mut range_e = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut range_f = (f64::INFINITY, f64::NEG_INFINITY);
+        for &i in ring.iter() {
+            let q = projected[i];
+            let (pe, pf) = (q[0] * e[0] + q[1] * e[1], q[0] * f[0] + q[1] * f[1]);
+            range_e = (range_e.0.min(pe), range_e.1.max(pe));
+            range_f = (range_f.0.min(pf), range_f.1.max(pf));
+        }
+
+        let area = (range_e.1 - range_e.0) * (range_f.1 - range_f.0);
+        if best.as_ref().is_none_or(|r| area < r.area) {
+            let center = [
+                (range_e.0 + range_e.1) / 2.0 * e[0] + (range_f.0 + range_f.1) / 2.0 * f[0],
+                (range_e.0 + range_e.1) / 2.0 * e[1] + (range_f.0 + range_f.1) / 2.0 * f[1],
+            ];
+            let half = [(range_e.1 - range_e.0) / 2.0, (range_f.1 - range_f.0) / 2.0];
+            best = Some(Rectangle {
+                area,
+                axis: e,
+                center,
+                half,
+            });
+        }
+    }
+
+    let Rectangle {
+        axis: e,
+        center,
+        half,
+        ..
+    } = best.unwrap_or(Rectangle {
+        area: 0.0,
+        axis: [1.0, 0.0],
+        center: [0.0, 0.0],
+        half: [0.0, 0.0],
+    });
+    let axis_e = u * e[0] + v * e[1];
+    let axis_f = u * -e[1] + v * e[0];
+    let center = origin + u * center[0] + v * center[1] + normal * ((low + high) / 2.0);
+    OrientedBox::new(
+        center,
+        [axis_e, axis_f, normal],
+        [half[0], half[1], (high - low) / 2.0],
+    )
+}
+
+// Andrew's monotone chain. Returns indices of the hull, counter-clockwise.
+fn convex_hull_2d(points: &[[f64; 2]]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (p, q) = (points[a], points[b]);
+        p[0].total_cmp(&q[0]).then(p[1].total_cmp(&q[1]))
+    });
+    order.dedup_by(|a, b| points[*a] == points[*b]);
+    if order.len() < 3 {
+        return order;
+    }
+
+    let cross = |o: usize, a: usize, b: usize| {
+        let (o, a, b) = (points[o], points[a], points[b]);
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+
+    let mut hull: Vec<usize> = Vec::with_capacity(order.len() * 2);
+    for pass in [order.clone(), order.iter().rev().copied().collect()] {
+        let start = hull.len();
+        for i in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], i) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(i);
+        }
+        // The last point of each chain starts the other one
+        hull.pop();
+    }
+    hull
+}
+
+#[test]
+fn test_oriented_bounding_box() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let cube = &model.objects[0].mesh;
+
+    // Spin the cube so its axis aligned bounds are much bigger than it is
+    let spin = Transform::rotation(Vector::new(1.0, 2.0, 3.0), 0.7)
+        .then(&Transform::translation(Vector::new(5.0, -2.0, 1.0)));
+    let spun = cube.transform(&spin);
+
+    let obb = oriented_bounding_box(&spun.vertices);
+    assert!((obb.volume() - 8.0).abs() < 1e-9);
+    assert!((obb.center - spin.apply_point(Point::zero())).len() < 1e-9);
+    assert!(obb.fits([2.001, 3.0, 2.001]));
+    assert!(!obb.fits([1.9, 3.0, 3.0]));
+
+    let (min, max) = spun.bounds();
+    let aabb = OrientedBox::axis_aligned(min, max);
+    assert!(aabb.volume() > obb.volume());
+    assert!(!aabb.fits([2.001, 3.0, 2.001]));
+
+    // Back in the box's frame, the cube is axis aligned again
+    let local = spun.transform(&obb.to_local());
+    let (min, max) = local.bounds();
+    assert!((min - Point::new(-1.0, -1.0, -1.0)).len() < 1e-9);
+    assert!((max - Point::new(1.0, 1.0, 1.0)).len() < 1e-9);
+
+    // Points that aren't finite have no hull, so the box is the axis aligned one
+    let mut broken = spun.vertices.clone();
+    broken.push(Point::new(f64::NAN, 0.0, 0.0));
+    let obb = oriented_bounding_box(&broken);
+    assert!(obb.volume().is_finite() && obb.volume() >= aabb.volume());
+}
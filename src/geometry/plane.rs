@@ -25,6 +25,27 @@ impl Plane {
         let point = p3;
         Plane::new(normal, point)
     }
+
+    // Signed distance from the plane, positive on the side the normal points to
+    pub fn distance(&self, p: Point) -> f64 {
+        (p - self.point).dot(self.normal)
+    }
+
+    // Two unit vectors (u, v) spanning the plane, with u x v pointing along the normal
+    pub fn basis(&self) -> (Vector, Vector) {
+        let n = self.normal;
+        // Start from the axis least aligned with the normal
+        let axis = if n.x.abs() <= n.y.abs() && n.x.abs() <= n.z.abs() {
+            Vector::X
+        } else if n.y.abs() <= n.z.abs() {
+            Vector::Y
+        } else {
+            Vector::Z
+        };
+        let u = axis.cross(n).unit();
+        let v = n.cross(u);
+        (u, v)
+    }
 }
 
 // For some reason #[derive(PartialEq)] doesn't work.
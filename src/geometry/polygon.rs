@@ -0,0 +1,734 @@
+// Triangulation of planar polygons (with holes) using ear clipping.
+//
+// This is a port of the core of mapbox's earcut, without the z-order hashing. Polygons are
+// given as loops of indices into a shared list of 2D points so that the resulting triangles
+// can be mapped straight back onto mesh vertices.
+
+use std::collections::HashMap;
+
+// Twice the signed area of a loop. Positive for counter-clockwise loops.
+pub fn signed_area(points: &[[f64; 2]], ring: &[usize]) -> f64 {
+    let mut sum = 0.0;
+    for (k, &i) in ring.iter().enumerate() {
+        let j = ring[(k + 1) % ring.len()];
+        sum += points[i][0] * points[j][1] - points[j][0] * points[i][1];
+    }
+    sum
+}
+
+// Even-odd point in polygon test
+pub fn contains(points: &[[f64; 2]], ring: &[usize], p: [f64; 2]) -> bool {
+    let mut inside = false;
+    for (k, &i) in ring.iter().enumerate() {
+        let a = points[i];
+        let b = points[ring[(k + 1) % ring.len()]];
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Distance from a point to the closest edge of a loop
+pub fn boundary_distance(points: &[[f64; 2]], ring: &[usize], p: [f64; 2]) -> f64 {
+    let mut best = f64::INFINITY;
+    for (k, &i) in ring.iter().enumerate() {
+        let a = points[i];
+        let b = points[ring[(k + 1) % ring.len()]];
+        best = best.min(segment_distance(a, b, p));
+    }
+    best
+}
+
+pub fn segment_distance(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (cx, cy) = (a[0] + t * dx - p[0], a[1] + t * dy - p[1]);
+    (cx * cx + cy * cy).sqrt()
+}
+
+// Groups a set of loops into polygons. Counter-clockwise loops are outer boundaries and
+// clockwise loops are holes, each hole belonging to the smallest outer loop that contains it.
+// Returns (outer, holes) pairs of indices into `loops`.
+pub fn group_loops(points: &[[f64; 2]], loops: &[Vec<usize>]) -> Vec<(usize, Vec<usize>)> {
+    let areas: Vec<f64> = loops.iter().map(|l| signed_area(points, l)).collect();
+    let mut polygons: Vec<(usize, Vec<usize>)> = (0..loops.len())
+        .filter(|&i| areas[i] > 0.0)
+        .map(|i| (i, Vec::new()))
+        .collect();
+
+    for hole in (0..loops.len()).filter(|&i| areas[i] < 0.0) {
+        // Use an edge midpoint, so a hole touching its outer loop at a vertex is still found
+        let ring = &loops[hole];
+        let (a, b) = (points[ring[0]], points[ring[1 % ring.len()]]);
+        let probe = [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+
+        let owner = polygons
+            .iter_mut()
+            .filter(|(outer, _)| contains(points, &loops[*outer], probe))
+            .min_by(|(a, _), (b, _)| areas[*a].partial_cmp(&areas[*b]).unwrap());
+        if let Some((_, holes)) = owner {
+            holes.push(hole);
+        }
+    }
+
+    polygons
+}
+
+// Triangulates every polygon formed by `loops` (see `group_loops`).
+// Triangles are returned counter-clockwise, as indices into `points`.
+pub fn triangulate_loops(points: &[[f64; 2]], loops: &[Vec<usize>]) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::new();
+    for (outer, holes) in group_loops(points, loops) {
+        let holes: Vec<&[usize]> = holes.iter().map(|&h| loops[h].as_slice()).collect();
+        triangles.extend(triangulate(points, &loops[outer], &holes));
+    }
+    triangles
+}
+
+// Triangulates a single polygon. The orientation of the input loops does not matter.
+// Triangles are returned counter-clockwise, as indices into `points`, and use every point of
+// the loops (except exact duplicates) so that they join up with whatever surrounds the polygon.
+pub fn triangulate(points: &[[f64; 2]], outer: &[usize], holes: &[&[usize]]) -> Vec<[usize; 3]> {
+    earcut(points, outer, holes)
+}
+
+fn earcut(points: &[[f64; 2]], outer: &[usize], holes: &[&[usize]]) -> Vec<[usize; 3]> {
+    let mut earcut = Earcut {
+        points,
+        nodes: Vec::new(),
+        triangles: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let outer_node = match earcut.linked_list(outer, true) {
+        Some(n) => n,
+        None => return Vec::new(),
+    };
+    if earcut.nodes[outer_node].next == earcut.nodes[outer_node].prev {
+        return Vec::new();
+    }
+
+    let outer_node = earcut.eliminate_holes(holes, outer_node);
+    earcut.earcut_linked(Some(outer_node), 0);
+    earcut.restore_skipped();
+    earcut.triangles
+}
+
+struct Node {
+    i: usize,
+    x: f64,
+    y: f64,
+    prev: usize,
+    next: usize,
+    steiner: bool,
+}
+
+struct Earcut<'a> {
+    points: &'a [[f64; 2]],
+    nodes: Vec<Node>,
+    triangles: Vec<[usize; 3]>,
+    // Points dropped from straight runs, between the two points around them at the time
+    skipped: Vec<[usize; 3]>,
+}
+
+impl<'a> Earcut<'a> {
+    fn x(&self, n: usize) -> f64 {
+        self.nodes[n].x
+    }
+
+    fn y(&self, n: usize) -> f64 {
+        self.nodes[n].y
+    }
+
+    fn next(&self, n: usize) -> usize {
+        self.nodes[n].next
+    }
+
+    fn prev(&self, n: usize) -> usize {
+        self.nodes[n].prev
+    }
+
+    // Negative for a counter-clockwise (convex) turn, following earcut
+    fn area(&self, p: usize, q: usize, r: usize) -> f64 {
+        let (p, q, r) = (&self.nodes[p], &self.nodes[q], &self.nodes[r]);
+        (q.y - p.y) * (r.x - q.x) - (q.x - p.x) * (r.y - q.y)
+    }
+
+    fn equals(&self, a: usize, b: usize) -> bool {
+        self.x(a) == self.x(b) && self.y(a) == self.y(b)
+    }
+
+    fn insert_node(&mut self, i: usize, last: Option<usize>) -> usize {
+        let n = self.nodes.len();
+        let [x, y] = self.points[i];
+        self.nodes.push(Node {
+            i,
+            x,
+            y,
+            prev: n,
+            next: n,
+            steiner: false,
+        });
+        if let Some(last) = last {
+            let last_next = self.next(last);
+            self.nodes[n].next = last_next;
+            self.nodes[n].prev = last;
+            self.nodes[last_next].prev = n;
+            self.nodes[last].next = n;
+        }
+        n
+    }
+
+    fn remove_node(&mut self, n: usize) {
+        let (prev, next) = (self.prev(n), self.next(n));
+        self.nodes[next].prev = prev;
+        self.nodes[prev].next = next;
+    }
+
+    // Builds a circular list from a loop, forcing counter-clockwise (or clockwise) winding
+    fn linked_list(&mut self, ring: &[usize], ccw: bool) -> Option<usize> {
+        let mut last = None;
+        if ccw == (signed_area(self.points, ring) > 0.0) {
+            for &i in ring.iter() {
+                last = Some(self.insert_node(i, last));
+            }
+        } else {
+            for &i in ring.iter().rev() {
+                last = Some(self.insert_node(i, last));
+            }
+        }
+
+        if let Some(l) = last {
+            if self.equals(l, self.next(l)) {
+                let next = self.next(l);
+                self.remove_node(l);
+                last = Some(next);
+            }
+        }
+        last
+    }
+
+    // Removes duplicate and collinear points
+    fn filter_points(&mut self, start: usize, end: Option<usize>) -> usize {
+        let mut end = end.unwrap_or(start);
+        let mut p = start;
+        loop {
+            let mut again = false;
+            if !self.nodes[p].steiner
+                && (self.equals(p, self.next(p)) || self.area(self.prev(p), p, self.next(p)) == 0.0)
+            {
+                let prev = self.prev(p);
+                if self.between(prev, p, self.next(p)) {
+                    let [a, b, c] = [prev, p, self.next(p)].map(|n| self.nodes[n].i);
+                    self.skipped.push([a, b, c]);
+                }
+                self.remove_node(p);
+                p = prev;
+                end = prev;
+                if p == self.next(p) {
+                    break;
+                }
+                again = true;
+            } else {
+                p = self.next(p);
+            }
+            if !again && p == end {
+                break;
+            }
+        }
+        end
+    }
+
+    // Whether q is strictly inside the run from p to r
+    fn between(&self, p: usize, q: usize, r: usize) -> bool {
+        let (p, q, r) = (&self.nodes[p], &self.nodes[q], &self.nodes[r]);
+        let forward = (q.x - p.x) * (r.x - q.x) + (q.y - p.y) * (r.y - q.y);
+        forward > 0.0
+    }
+
+    // Adds a triangle of nodes. Degenerate ones (three points in a line, up to rounding) are
+    // left out, and the point in the middle is put back on the edge between the other two
+    // afterwards.
+    fn add_triangle(&mut self, a: usize, b: usize, c: usize) {
+        let length = |p: usize, q: usize| (self.x(q) - self.x(p)).hypot(self.y(q) - self.y(p));
+        if self.area(a, b, c).abs() > 1e-12 * length(a, b) * length(b, c) {
+            self.triangles.push([a, b, c].map(|n| self.nodes[n].i));
+            return;
+        }
+        for [p, q, r] in [[a, b, c], [b, c, a], [c, a, b]] {
+            if self.between(p, q, r) {
+                self.skipped.push([p, q, r].map(|n| self.nodes[n].i));
+                return;
+            }
+        }
+    }
+
+    // Puts the points dropped from straight runs back into the triangles, splitting the edges
+    // that replaced the runs, so triangles elsewhere using them still join up
+    fn restore_skipped(&mut self) {
+        let mut owners: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            for k in 0..3 {
+                let edge = (triangle[k], triangle[(k + 1) % 3]);
+                owners.entry(edge).or_default().push(t);
+            }
+        }
+
+        for &[a, p, c] in self.skipped.iter().rev() {
+            for (from, to) in [(a, c), (c, a)] {
+                for t in owners.remove(&(from, to)).unwrap_or_default() {
+                    let triangle = self.triangles[t];
+                    let k = match (0..3).find(|&k| triangle[k] == from) {
+                        Some(k) if triangle[(k + 1) % 3] == to => k,
+                        _ => continue,
+                    };
+                    let other = triangle[(k + 2) % 3];
+                    let u = self.triangles.len();
+                    self.triangles[t] = [from, p, other];
+                    self.triangles.push([p, to, other]);
+                    for (edge, owner) in [
+                        ((from, p), t),
+                        ((p, other), t),
+                        ((p, to), u),
+                        ((other, p), u),
+                    ] {
+                        owners.entry(edge).or_default().push(owner);
+                    }
+                    if let Some(list) = owners.get_mut(&(to, other)) {
+                        list.retain(|&o| o != t);
+                        list.push(u);
+                    }
+                }
+            }
+        }
+    }
+
+    fn earcut_linked(&mut self, ear: Option<usize>, pass: u8) {
+        let mut ear = match ear {
+            Some(e) => e,
+            None => return,
+        };
+        let mut stop = ear;
+
+        while self.prev(ear) != self.next(ear) {
+            let prev = self.prev(ear);
+            let next = self.next(ear);
+
+            if self.is_ear(ear) {
+                self.add_triangle(prev, ear, next);
+                self.remove_node(ear);
+                ear = self.next(next);
+                stop = ear;
+                continue;
+            }
+
+            ear = next;
+
+            if ear == stop {
+                // Nothing left that looks like an ear. Try progressively harder fixes.
+                match pass {
+                    0 => {
+                        let e = self.filter_points(ear, None);
+                        self.earcut_linked(Some(e), 1);
+                    }
+                    1 => {
+                        let e = self.filter_points(ear, None);
+                        let e = self.cure_local_intersections(e);
+                        self.earcut_linked(Some(e), 2);
+                    }
+                    _ => self.split_earcut(ear),
+                }
+                break;
+            }
+        }
+    }
+
+    fn is_ear(&self, ear: usize) -> bool {
+        let (a, b, c) = (self.prev(ear), ear, self.next(ear));
+        if self.area(a, b, c) >= 0.0 {
+            // Reflex
+            return false;
+        }
+
+        let mut p = self.next(c);
+        while p != a {
+            if point_in_triangle(
+                [self.x(a), self.y(a)],
+                [self.x(b), self.y(b)],
+                [self.x(c), self.y(c)],
+                [self.x(p), self.y(p)],
+            ) && self.area(self.prev(p), p, self.next(p)) >= 0.0
+            {
+                return false;
+            }
+            p = self.next(p);
+        }
+        true
+    }
+
+    fn cure_local_intersections(&mut self, start: usize) -> usize {
+        let mut start = start;
+        let mut p = start;
+        loop {
+            let a = self.prev(p);
+            let b = self.next(self.next(p));
+
+            if !self.equals(a, b)
+                && self.intersects(a, p, self.next(p), b)
+                && self.locally_inside(a, b)
+                && self.locally_inside(b, a)
+            {
+                self.add_triangle(a, p, b);
+                let next = self.next(p);
+                self.remove_node(p);
+                self.remove_node(next);
+                p = b;
+                start = b;
+            }
+            p = self.next(p);
+            if p == start {
+                break;
+            }
+        }
+        self.filter_points(p, None)
+    }
+
+    fn split_earcut(&mut self, start: usize) {
+        let mut a = start;
+        loop {
+            let mut b = self.next(self.next(a));
+            while b != self.prev(a) {
+                if self.nodes[a].i != self.nodes[b].i && self.is_valid_diagonal(a, b) {
+                    let c = self.split_polygon(a, b);
+                    let a = self.filter_points(a, Some(self.next(a)));
+                    let c = self.filter_points(c, Some(self.next(c)));
+                    self.earcut_linked(Some(a), 0);
+                    self.earcut_linked(Some(c), 0);
+                    return;
+                }
+                b = self.next(b);
+            }
+            a = self.next(a);
+            if a == start {
+                break;
+            }
+        }
+    }
+
+    fn eliminate_holes(&mut self, holes: &[&[usize]], mut outer_node: usize) -> usize {
+        let mut queue = Vec::new();
+        for hole in holes.iter() {
+            if let Some(list) = self.linked_list(hole, false) {
+                if list == self.next(list) {
+                    self.nodes[list].steiner = true;
+                }
+                queue.push(self.leftmost(list));
+            }
+        }
+        queue.sort_by(|&a, &b| self.x(a).partial_cmp(&self.x(b)).unwrap());
+
+        for hole in queue {
+            outer_node = self.eliminate_hole(hole, outer_node);
+        }
+        outer_node
+    }
+
+    fn eliminate_hole(&mut self, hole: usize, outer_node: usize) -> usize {
+        let bridge = match self.find_hole_bridge(hole, outer_node) {
+            Some(b) => b,
+            None => return outer_node,
+        };
+        let bridge_reverse = self.split_polygon(bridge, hole);
+        self.filter_points(bridge_reverse, Some(self.next(bridge_reverse)));
+        self.filter_points(bridge, Some(self.next(bridge)))
+    }
+
+    // David Eberly's algorithm for finding a bridge between a hole and the outer polygon
+    fn find_hole_bridge(&self, hole: usize, outer_node: usize) -> Option<usize> {
+        let (hx, hy) = (self.x(hole), self.y(hole));
+        let mut qx = f64::NEG_INFINITY;
+        let mut m = None;
+
+        // Find a segment intersected by a ray from the hole's leftmost point to the left
+        let mut p = outer_node;
+        loop {
+            let next = self.next(p);
+            if hy <= self.y(p) && hy >= self.y(next) && self.y(next) != self.y(p) {
+                let x = self.x(p)
+                    + (hy - self.y(p)) * (self.x(next) - self.x(p)) / (self.y(next) - self.y(p));
+                if x <= hx && x > qx {
+                    qx = x;
+                    let candidate = if self.x(p) < self.x(next) { p } else { next };
+                    if x == hx {
+                        // The hole touches the outer segment
+                        return Some(candidate);
+                    }
+                    m = Some(candidate);
+                }
+            }
+            p = next;
+            if p == outer_node {
+                break;
+            }
+        }
+
+        let mut m = m?;
+
+        // Look for points inside the triangle of the hole point, the segment intersection and
+        // the endpoint. If any are found, connect to the one with the smallest angle instead.
+        let stop = m;
+        let (mx, my) = (self.x(m), self.y(m));
+        let mut tan_min = f64::INFINITY;
+        let mut p = m;
+        loop {
+            let (px, py) = (self.x(p), self.y(p));
+            if hx >= px
+                && px >= mx
+                && hx != px
+                && point_in_triangle(
+                    [if hy < my { hx } else { qx }, hy],
+                    [mx, my],
+                    [if hy < my { qx } else { hx }, hy],
+                    [px, py],
+                )
+            {
+                let tan = (hy - py).abs() / (hx - px);
+                if self.locally_inside(p, hole)
+                    && (tan < tan_min
+                        || (tan == tan_min
+                            && (px > self.x(m)
+                                || (px == self.x(m) && self.sector_contains_sector(m, p)))))
+                {
+                    m = p;
+                    tan_min = tan;
+                }
+            }
+            p = self.next(p);
+            if p == stop {
+                break;
+            }
+        }
+
+        Some(m)
+    }
+
+    fn sector_contains_sector(&self, m: usize, p: usize) -> bool {
+        self.area(self.prev(m), m, self.prev(p)) < 0.0
+            && self.area(self.next(p), m, self.next(m)) < 0.0
+    }
+
+    fn leftmost(&self, start: usize) -> usize {
+        let mut p = start;
+        let mut leftmost = start;
+        loop {
+            if self.x(p) < self.x(leftmost)
+                || (self.x(p) == self.x(leftmost) && self.y(p) < self.y(leftmost))
+            {
+                leftmost = p;
+            }
+            p = self.next(p);
+            if p == start {
+                break;
+            }
+        }
+        leftmost
+    }
+
+    fn is_valid_diagonal(&self, a: usize, b: usize) -> bool {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        self.nodes[self.next(a)].i != bi
+            && self.nodes[self.prev(a)].i != bi
+            && !self.intersects_polygon(a, b)
+            && ((self.locally_inside(a, b)
+                && self.locally_inside(b, a)
+                && self.middle_inside(a, b)
+                && (self.area(self.prev(a), a, self.prev(b)) != 0.0
+                    || self.area(a, self.prev(b), b) != 0.0))
+                || (self.equals(a, b)
+                    && ai != bi
+                    && self.area(self.prev(a), a, self.next(a)) > 0.0
+                    && self.area(self.prev(b), b, self.next(b)) > 0.0))
+    }
+
+    fn intersects(&self, p1: usize, q1: usize, p2: usize, q2: usize) -> bool {
+        let o1 = sign(self.area(p1, q1, p2));
+        let o2 = sign(self.area(p1, q1, q2));
+        let o3 = sign(self.area(p2, q2, p1));
+        let o4 = sign(self.area(p2, q2, q1));
+
+        (o1 != o2 && o3 != o4)
+            || (o1 == 0 && self.on_segment(p1, p2, q1))
+            || (o2 == 0 && self.on_segment(p1, q2, q1))
+            || (o3 == 0 && self.on_segment(p2, p1, q2))
+            || (o4 == 0 && self.on_segment(p2, q1, q2))
+    }
+
+    // For collinear points p, q, r, check if q lies on segment pr
+    fn on_segment(&self, p: usize, q: usize, r: usize) -> bool {
+        self.x(q) <= self.x(p).max(self.x(r))
+            && self.x(q) >= self.x(p).min(self.x(r))
+            && self.y(q) <= self.y(p).max(self.y(r))
+            && self.y(q) >= self.y(p).min(self.y(r))
+    }
+
+    fn intersects_polygon(&self, a: usize, b: usize) -> bool {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        let mut p = a;
+        loop {
+            let next = self.next(p);
+            let (pi, ni) = (self.nodes[p].i, self.nodes[next].i);
+            if pi != ai && ni != ai && pi != bi && ni != bi && self.intersects(p, next, a, b) {
+                return true;
+            }
+            p = next;
+            if p == a {
+                break;
+            }
+        }
+        false
+    }
+
+    fn locally_inside(&self, a: usize, b: usize) -> bool {
+        let (prev, next) = (self.prev(a), self.next(a));
+        if self.area(prev, a, next) < 0.0 {
+            self.area(a, b, next) >= 0.0 && self.area(a, prev, b) >= 0.0
+        } else {
+            self.area(a, b, prev) < 0.0 || self.area(a, next, b) < 0.0
+        }
+    }
+
+    fn middle_inside(&self, a: usize, b: usize) -> bool {
+        let (px, py) = ((self.x(a) + self.x(b)) / 2.0, (self.y(a) + self.y(b)) / 2.0);
+        let mut inside = false;
+        let mut p = a;
+        loop {
+            let next = self.next(p);
+            if (self.y(p) > py) != (self.y(next) > py)
+                && self.y(next) != self.y(p)
+                && px
+                    < (self.x(next) - self.x(p)) * (py - self.y(p)) / (self.y(next) - self.y(p))
+                        + self.x(p)
+            {
+                inside = !inside;
+            }
+            p = next;
+            if p == a {
+                break;
+            }
+        }
+        inside
+    }
+
+    // Links a and b with a bridge. If a and b are in the same ring this splits it in two,
+    // otherwise it merges the two rings. Returns the copy of b.
+    fn split_polygon(&mut self, a: usize, b: usize) -> usize {
+        let (ai, bi) = (self.nodes[a].i, self.nodes[b].i);
+        let a2 = self.insert_node(ai, None);
+        let b2 = self.insert_node(bi, None);
+        let an = self.next(a);
+        let bp = self.prev(b);
+
+        self.nodes[a].next = b;
+        self.nodes[b].prev = a;
+
+        self.nodes[a2].next = an;
+        self.nodes[an].prev = a2;
+
+        self.nodes[b2].next = a2;
+        self.nodes[a2].prev = b2;
+
+        self.nodes[bp].next = b2;
+        self.nodes[b2].prev = bp;
+
+        b2
+    }
+}
+
+fn sign(v: f64) -> i8 {
+    if v > 0.0 {
+        1
+    } else if v < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn point_in_triangle(a: [f64; 2], b: [f64; 2], c: [f64; 2], p: [f64; 2]) -> bool {
+    (c[0] - p[0]) * (a[1] - p[1]) >= (a[0] - p[0]) * (c[1] - p[1])
+        && (a[0] - p[0]) * (b[1] - p[1]) >= (b[0] - p[0]) * (a[1] - p[1])
+        && (b[0] - p[0]) * (c[1] - p[1]) >= (c[0] - p[0]) * (b[1] - p[1])
+}
+
+#[test]
+fn test_triangulate_square_with_hole() {
+    let points = [
+        [0.0, 0.0],
+        [4.0, 0.0],
+        [4.0, 4.0],
+        [0.0, 4.0],
+        [1.0, 1.0],
+        [1.0, 3.0],
+        [3.0, 3.0],
+        [3.0, 1.0],
+    ];
+    let loops = vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]];
+    let triangles = triangulate_loops(&points, &loops);
+
+    // Every triangle is counter-clockwise and together they cover the area between the loops
+    let mut total = 0.0;
+    for t in triangles.iter() {
+        let area = signed_area(&points, t);
+        assert!(area > 0.0);
+        total += area;
+    }
+    assert!((total / 2.0 - 12.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_triangulate_aligned_holes() {
+    // A row of square holes whose sides line up, so earcut finds straight runs across them
+    let mut points = vec![[0.0, 0.0], [12.0, 0.0], [12.0, 6.0], [0.0, 6.0]];
+    let mut loops = vec![vec![0, 1, 2, 3]];
+    for x in [1.0, 4.0, 7.0, 10.0] {
+        for y in [1.0, 4.0] {
+            let n = points.len();
+            points.extend([[x, y], [x, y + 1.0], [x + 1.0, y + 1.0], [x + 1.0, y]]);
+            loops.push(vec![n, n + 1, n + 2, n + 3]);
+        }
+    }
+    let triangles = triangulate_loops(&points, &loops);
+
+    // Every edge inside is shared, and the loops are the only edges left over
+    let mut open: HashMap<(usize, usize), i32> = HashMap::new();
+    for t in triangles.iter() {
+        for k in 0..3 {
+            *open.entry((t[k], t[(k + 1) % 3])).or_insert(0) += 1;
+            *open.entry((t[(k + 1) % 3], t[k])).or_insert(0) -= 1;
+        }
+    }
+    open.retain(|_, &mut count| count > 0);
+    let mut boundary: Vec<(usize, usize)> = loops
+        .iter()
+        .flat_map(|l| (0..l.len()).map(move |k| (l[k], l[(k + 1) % l.len()])))
+        .map(|(a, b)| {
+            if open.contains_key(&(a, b)) {
+                (a, b)
+            } else {
+                (b, a)
+            }
+        })
+        .collect();
+    let mut edges: Vec<(usize, usize)> = open.into_keys().collect();
+    boundary.sort();
+    edges.sort();
+    assert_eq!(edges, boundary);
+}
@@ -0,0 +1,259 @@
+use crate::geometry::polygon;
+use crate::geometry::{Point, Triangle, Vector};
+use crate::threemf::Mesh;
+use std::f64::consts::PI;
+
+// Closed meshes of simple solids, wound outwards. Boxes, cylinders, cones and extrusions
+// stand on the XY plane around the Z axis, reaching up to their height. Spheres and tori are
+// centered on the origin. Move them into place with `Mesh::transform`.
+//
+// `segments` is the number of sides used around the Z axis. Resolutions are raised to the
+// smallest that still gives a closed solid.
+
+// A polygon with holes, extruded up along Z. Outer loops go counter-clockwise and holes
+// clockwise, as in `polygon::group_loops`.
+pub fn extrude(loops: &[Vec<[f64; 2]>], height: f64) -> Mesh {
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    let mut rings: Vec<Vec<usize>> = Vec::new();
+    for ring in loops.iter() {
+        rings.push((points.len()..points.len() + ring.len()).collect());
+        points.extend(ring.iter().copied());
+    }
+
+    let n = points.len();
+    let mut vertices: Vec<Point> = points.iter().map(|p| Point::new(p[0], p[1], 0.0)).collect();
+    vertices.extend(points.iter().map(|p| Point::new(p[0], p[1], height)));
+
+    let mut triangles = Vec::new();
+    for ring in rings.iter() {
+        for (k, &a) in ring.iter().enumerate() {
+            let b = ring[(k + 1) % ring.len()];
+            triangles.push(Triangle::new(a, b, n + b));
+            triangles.push(Triangle::new(a, n + b, n + a));
+        }
+    }
+    for [a, b, c] in polygon::triangulate_loops(&points, &rings) {
+        triangles.push(Triangle::new(n + a, n + b, n + c));
+        triangles.push(Triangle::new(a, c, b));
+    }
+    Mesh::new(vertices, triangles)
+}
+
+pub fn cuboid(size: Vector) -> Mesh {
+    let (x, y) = (size.x / 2.0, size.y / 2.0);
+    extrude(&[vec![[-x, -y], [x, -y], [x, y], [-x, y]]], size.z)
+}
+
+// A cone, or a frustum when both radii are non-zero
+pub fn cone(bottom_radius: f64, top_radius: f64, height: f64, segments: usize) -> Mesh {
+    let n = segments.max(3);
+    let ring = |radius: f64, z: f64| -> Vec<Point> {
+        (0..n)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / n as f64;
+                Point::new(radius * angle.cos(), radius * angle.sin(), z)
+            })
+            .collect()
+    };
+
+    // Each end is a ring closed off around its center, or a single point
+    let mut vertices: Vec<Point> = Vec::new();
+    let mut ends: Vec<(Vec<usize>, usize)> = Vec::new();
+    for (radius, z) in [(bottom_radius, 0.0), (top_radius, height)] {
+        let center = vertices.len();
+        vertices.push(Point::new(0.0, 0.0, z));
+        if radius > 0.0 {
+            ends.push(((center + 1..center + 1 + n).collect(), center));
+            vertices.extend(ring(radius, z));
+        } else {
+            ends.push((vec![center; n], center));
+        }
+    }
+
+    let mut triangles = Vec::new();
+    let ((bottom, bottom_center), (top, top_center)) = (&ends[0], &ends[1]);
+    for i in 0..n {
+        let j = (i + 1) % n;
+        if bottom[i] != bottom[j] {
+            triangles.push(Triangle::new(*bottom_center, bottom[j], bottom[i]));
+            triangles.push(Triangle::new(bottom[i], bottom[j], top[j]));
+        }
+        if top[i] != top[j] {
+            triangles.push(Triangle::new(*top_center, top[i], top[j]));
+            triangles.push(Triangle::new(bottom[i], top[j], top[i]));
+        }
+    }
+
+    // Drops the center of a pointed end, which only the cap would have used
+    let mesh = Mesh::new(vertices, triangles);
+    mesh.submesh(&(0..mesh.triangles.len()).collect::<Vec<usize>>())
+}
+
+pub fn cylinder(radius: f64, height: f64, segments: usize) -> Mesh {
+    cone(radius, radius, height, segments)
+}
+
+// A UV sphere, with `rings` bands from pole to pole
+pub fn sphere(radius: f64, segments: usize, rings: usize) -> Mesh {
+    let (n, m) = (segments.max(3), rings.max(2));
+    let mut vertices = vec![Point::new(0.0, 0.0, radius)];
+    for k in 1..m {
+        let polar = PI * k as f64 / m as f64;
+        for i in 0..n {
+            let angle = 2.0 * PI * i as f64 / n as f64;
+            vertices.push(Point::new(
+                radius * polar.sin() * angle.cos(),
+                radius * polar.sin() * angle.sin(),
+                radius * polar.cos(),
+            ));
+        }
+    }
+    vertices.push(Point::new(0.0, 0.0, -radius));
+
+    let south = vertices.len() - 1;
+    let at = |k: usize, i: usize| 1 + (k - 1) * n + i % n;
+    let mut triangles = Vec::new();
+    for i in 0..n {
+        triangles.push(Triangle::new(0, at(1, i), at(1, i + 1)));
+        for k in 1..m - 1 {
+            triangles.push(Triangle::new(at(k, i), at(k + 1, i), at(k + 1, i + 1)));
+            triangles.push(Triangle::new(at(k, i), at(k + 1, i + 1), at(k, i + 1)));
+        }
+        triangles.push(Triangle::new(at(m - 1, i), south, at(m - 1, i + 1)));
+    }
+    Mesh::new(vertices, triangles)
+}
+
+// A beam along Z from the origin up to `height`. Each end is rounded off with half a sphere of
+// its radius, or flat where `rounded` is false. Rounded ends have a band for every four
+// segments.
+pub fn capsule(
+    bottom_radius: f64,
+    top_radius: f64,
+    height: f64,
+    segments: usize,
+    rounded: [bool; 2],
+) -> Mesh {
+    let n = segments.max(3);
+    let bands = (n / 4).max(1);
+    let polar = |k: usize| PI / 2.0 * k as f64 / bands as f64;
+
+    // Radius and height from the bottom pole up to the top one
+    let mut profile: Vec<(f64, f64)> = Vec::new();
+    if rounded[0] {
+        for k in 0..bands {
+            let (sin, cos) = polar(k).sin_cos();
+            profile.push((bottom_radius * sin, -bottom_radius * cos));
+        }
+    } else {
+        profile.push((0.0, 0.0));
+    }
+    profile.push((bottom_radius, 0.0));
+    profile.push((top_radius, height));
+    if rounded[1] {
+        for k in (0..bands).rev() {
+            let (sin, cos) = polar(k).sin_cos();
+            profile.push((top_radius * sin, height + top_radius * cos));
+        }
+    } else {
+        profile.push((0.0, height));
+    }
+    lathe(&profile, n)
+}
+
+// Turns a profile of (radius, z) around the Z axis. It starts and ends on the axis.
+fn lathe(profile: &[(f64, f64)], segments: usize) -> Mesh {
+    let n = segments;
+    let m = profile.len() - 1;
+    let mut vertices = vec![Point::new(0.0, 0.0, profile[0].1)];
+    for &(radius, z) in profile[1..m].iter() {
+        for i in 0..n {
+            let angle = 2.0 * PI * i as f64 / n as f64;
+            vertices.push(Point::new(radius * angle.cos(), radius * angle.sin(), z));
+        }
+    }
+    vertices.push(Point::new(0.0, 0.0, profile[m].1));
+
+    let top = vertices.len() - 1;
+    let at = |k: usize, i: usize| 1 + (k - 1) * n + i % n;
+    let mut triangles = Vec::new();
+    for i in 0..n {
+        triangles.push(Triangle::new(0, at(1, i + 1), at(1, i)));
+        for k in 1..m - 1 {
+            triangles.push(Triangle::new(at(k, i), at(k, i + 1), at(k + 1, i + 1)));
+            triangles.push(Triangle::new(at(k, i), at(k + 1, i + 1), at(k + 1, i)));
+        }
+        triangles.push(Triangle::new(at(m - 1, i), at(m - 1, i + 1), top));
+    }
+    Mesh::new(vertices, triangles)
+}
+
+// A ring around the Z axis. `sides` is the number of sides around the tube.
+pub fn torus(major_radius: f64, minor_radius: f64, segments: usize, sides: usize) -> Mesh {
+    let (n, m) = (segments.max(3), sides.max(3));
+    let mut vertices = Vec::with_capacity(n * m);
+    for i in 0..n {
+        let angle = 2.0 * PI * i as f64 / n as f64;
+        for j in 0..m {
+            let tube = 2.0 * PI * j as f64 / m as f64;
+            let r = major_radius + minor_radius * tube.cos();
+            vertices.push(Point::new(
+                r * angle.cos(),
+                r * angle.sin(),
+                minor_radius * tube.sin(),
+            ));
+        }
+    }
+
+    let at = |i: usize, j: usize| (i % n) * m + j % m;
+    let mut triangles = Vec::with_capacity(2 * n * m);
+    for i in 0..n {
+        for j in 0..m {
+            triangles.push(Triangle::new(at(i, j), at(i + 1, j), at(i + 1, j + 1)));
+            triangles.push(Triangle::new(at(i, j), at(i + 1, j + 1), at(i, j + 1)));
+        }
+    }
+    Mesh::new(vertices, triangles)
+}
+
+#[test]
+fn test_primitives() {
+    use crate::cut::open_edges;
+
+    let square = vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+    let hole = vec![[1.0, 1.0], [1.0, 3.0], [3.0, 3.0], [3.0, 1.0]];
+    let n = 32;
+    // Area of a regular polygon with n sides around a unit circle
+    let polygon = n as f64 / 2.0 * (2.0 * PI / n as f64).sin();
+
+    for (mesh, volume) in [
+        (cuboid(Vector::new(1.0, 2.0, 3.0)), 6.0),
+        (extrude(&[square, hole], 0.5), 6.0),
+        (cylinder(2.0, 3.0, n), 4.0 * polygon * 3.0),
+        (cone(2.0, 0.0, 3.0, n), 4.0 * polygon * 3.0 / 3.0),
+        (
+            cone(2.0, 1.0, 3.0, n),
+            polygon * 3.0 * (4.0 + 2.0 + 1.0) / 3.0,
+        ),
+        (cone(0.0, 1.0, 3.0, n), polygon * 3.0 / 3.0),
+    ] {
+        assert!(open_edges(&mesh.triangles).is_empty());
+        assert!((mesh.volume() - volume).abs() < 1e-9);
+    }
+
+    let beam = capsule(1.0, 1.0, 3.0, n, [false, false]);
+    assert!(open_edges(&beam.triangles).is_empty());
+    assert!((beam.volume() - polygon * 3.0).abs() < 1e-9);
+
+    let ball = sphere(2.0, 64, 32);
+    let ring = torus(3.0, 1.0, 64, 32);
+    let rounded = capsule(2.0, 2.0, 3.0, 64, [true, true]);
+    for (mesh, volume) in [
+        (&ball, 4.0 / 3.0 * PI * 8.0),
+        (&ring, 2.0 * PI * PI * 3.0),
+        (&rounded, 4.0 / 3.0 * PI * 8.0 + 4.0 * PI * 3.0),
+    ] {
+        assert!(open_edges(&mesh.triangles).is_empty());
+        assert!((mesh.volume() - volume).abs() < volume * 0.01);
+    }
+}
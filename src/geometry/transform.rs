@@ -0,0 +1,234 @@
+use crate::geometry::{Point, Vector};
+use serde::Serialize;
+use std::fmt::Formatter;
+
+// An affine transform, laid out like a 3MF transform: a 3x3 matrix followed by a translation.
+// Points are transformed as `matrix * p + translation`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Transform {
+    // Row-major
+    pub matrix: [[f64; 3]; 3],
+    pub translation: Vector,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            translation: Vector::zero(),
+        }
+    }
+
+    pub fn translation(v: Vector) -> Self {
+        Transform {
+            translation: v,
+            ..Transform::identity()
+        }
+    }
+
+    // Right-handed rotation of `angle` radians around `axis`
+    pub fn rotation(axis: Vector, angle: f64) -> Self {
+        let a = axis.unit();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+        Transform {
+            matrix: [
+                [
+                    t * a.x * a.x + c,
+                    t * a.x * a.y - s * a.z,
+                    t * a.x * a.z + s * a.y,
+                ],
+                [
+                    t * a.x * a.y + s * a.z,
+                    t * a.y * a.y + c,
+                    t * a.y * a.z - s * a.x,
+                ],
+                [
+                    t * a.x * a.z - s * a.y,
+                    t * a.y * a.z + s * a.x,
+                    t * a.z * a.z + c,
+                ],
+            ],
+            translation: Vector::zero(),
+        }
+    }
+
+    // The shortest rotation taking the direction `from` onto `to`
+    pub fn rotation_between(from: Vector, to: Vector) -> Self {
+        let (f, t) = (from.unit(), to.unit());
+        let axis = f.cross(t);
+        let cos = f.dot(t).clamp(-1.0, 1.0);
+
+        if axis.len() < 1e-12 {
+            if cos > 0.0 {
+                return Transform::identity();
+            }
+            // Opposite directions, turn half way around any perpendicular axis
+            let other = if f.x.abs() < 0.9 {
+                Vector::X
+            } else {
+                Vector::Y
+            };
+            return Transform::rotation(f.cross(other), std::f64::consts::PI);
+        }
+        Transform::rotation(axis, axis.len().atan2(cos))
+    }
+
+    pub fn apply_vector(&self, v: Vector) -> Vector {
+        let m = &self.matrix;
+        Vector::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn apply_point(&self, p: Point) -> Point {
+        Point::zero() + self.apply_vector(p - Point::zero()) + self.translation
+    }
+
+    // The transform applying `self` first, then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        let (a, b) = (&next.matrix, &self.matrix);
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        Transform {
+            matrix,
+            translation: next.apply_vector(self.translation) + next.translation,
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // None if the matrix is singular
+    pub fn inverse(&self) -> Option<Transform> {
+        let det = self.determinant();
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let m = &self.matrix;
+        let cofactor = |r1: usize, r2: usize, c1: usize, c2: usize| {
+            m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+        };
+        let matrix = [
+            [
+                cofactor(1, 2, 1, 2) / det,
+                -cofactor(0, 2, 1, 2) / det,
+                cofactor(0, 1, 1, 2) / det,
+            ],
+            [
+                -cofactor(1, 2, 0, 2) / det,
+                cofactor(0, 2, 0, 2) / det,
+                -cofactor(0, 1, 0, 2) / det,
+            ],
+            [
+                cofactor(1, 2, 0, 1) / det,
+                -cofactor(0, 2, 0, 1) / det,
+                cofactor(0, 1, 0, 1) / det,
+            ],
+        ];
+
+        let mut inverse = Transform {
+            matrix,
+            translation: Vector::zero(),
+        };
+        inverse.translation = -inverse.apply_vector(self.translation);
+        Some(inverse)
+    }
+}
+
+impl Transform {
+    // Parses a 3MF transform attribute. Its twelve numbers are the matrix column by column,
+    // then the translation.
+    pub fn parse(text: &str) -> Option<Transform> {
+        let values: Vec<f64> = text
+            .split_whitespace()
+            .map(|v| v.parse().ok())
+            .collect::<Option<_>>()?;
+        if values.len() != 12 {
+            return None;
+        }
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, m) in row.iter_mut().enumerate() {
+                *m = values[3 * j + i];
+            }
+        }
+        Some(Transform {
+            matrix,
+            translation: Vector::new(values[9], values[10], values[11]),
+        })
+    }
+
+    // As a 3MF transform attribute, see `parse`
+    pub fn to_attribute(&self) -> String {
+        let m = &self.matrix;
+        let t = self.translation;
+        let values = [
+            m[0][0], m[1][0], m[2][0], m[0][1], m[1][1], m[2][1], m[0][2], m[1][2], m[2][2], t.x,
+            t.y, t.z,
+        ];
+        values.map(|v| v.to_string()).join(" ")
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let m = &self.matrix;
+        write!(
+            f,
+            "[{} {} {}; {} {} {}; {} {} {}] + ({})",
+            m[0][0],
+            m[0][1],
+            m[0][2],
+            m[1][0],
+            m[1][1],
+            m[1][2],
+            m[2][0],
+            m[2][1],
+            m[2][2],
+            self.translation
+        )
+    }
+}
+
+#[test]
+fn test_rotation_between() {
+    let from = Vector::new(1.0, 2.0, -0.5);
+    let to = Vector::new(0.0, 0.0, -1.0);
+    let rotation = Transform::rotation_between(from, to);
+    let rotated = rotation.apply_vector(from.unit());
+    assert!((rotated - to).len() < 1e-12);
+
+    let moved = rotation.then(&Transform::translation(Vector::new(1.0, 2.0, 3.0)));
+    let back = moved.inverse().unwrap();
+    let p = Point::new(3.0, -1.0, 2.0);
+    let q = back.apply_point(moved.apply_point(p));
+    assert!((q - p).len() < 1e-12);
+}
+
+#[test]
+fn test_parse_transform() {
+    // A quarter turn around Z, then a move along X
+    let transform = Transform::parse("0 1 0 -1 0 0 0 0 1 5 0 0").unwrap();
+    let p = transform.apply_point(Point::new(1.0, 0.0, 0.0));
+    assert!((p - Point::new(5.0, 1.0, 0.0)).len() < 1e-12);
+    assert_eq!(Transform::parse(&transform.to_attribute()), Some(transform));
+    assert_eq!(Transform::parse("1 0 0"), None);
+}
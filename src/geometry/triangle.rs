@@ -5,4 +5,50 @@ pub struct Triangle {
     pub v1: usize,
     pub v2: usize,
     pub v3: usize,
+    // Property group (3MF materials extension), overriding the object's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<usize>,
+    // Index into the property group for each corner. p2 and p3 default to p1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p1: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p3: Option<usize>,
+}
+
+impl Triangle {
+    pub fn new(v1: usize, v2: usize, v3: usize) -> Self {
+        Triangle {
+            v1,
+            v2,
+            v3,
+            pid: None,
+            p1: None,
+            p2: None,
+            p3: None,
+        }
+    }
+
+    // The same triangle with the given property group and property indices for its corners
+    pub fn with_properties(self, pid: Option<usize>, properties: Option<[usize; 3]>) -> Self {
+        Triangle {
+            pid,
+            p1: properties.map(|p| p[0]),
+            p2: properties.map(|p| p[1]),
+            p3: properties.map(|p| p[2]),
+            ..self
+        }
+    }
+
+    // Property index of each corner, if the triangle has its own. Otherwise the object's
+    // property applies.
+    pub fn properties(&self) -> Option<[usize; 3]> {
+        let p1 = self.p1?;
+        Some([p1, self.p2.unwrap_or(p1), self.p3.unwrap_or(p1)])
+    }
+
+    pub fn indices(&self) -> [usize; 3] {
+        [self.v1, self.v2, self.v3]
+    }
 }
@@ -1,12 +1,16 @@
 use crate::error::Error;
 use crate::geometry::{Plane, Point, Vector};
-use crate::threemf::Model;
-use itertools::Itertools;
+use crate::score::{rank_cuts, CutCandidate, ScoreOptions};
+use crate::threemf::{Mesh, Model, ObjectType};
 
+pub mod analysis;
+pub mod boolean;
 pub mod common;
+pub mod cut;
 pub mod error;
 pub mod geometry;
 pub mod load;
+pub mod score;
 pub mod threemf;
 
 // Check if the cuts are possible
@@ -33,21 +37,57 @@ fn check_equal_normals(v1: Vector, v2: Vector, similarity: f64) -> bool {
     (diff.x < similarity) && (diff.y < similarity) && (diff.z < similarity)
 }
 
-pub fn slice_model(model: Model) -> Result<Vec<Plane>, Error> {
-    let num_objects = model.num_objects();
-    if num_objects > 1 {
+// The mesh of the one build item of the given types in a model, where it is placed, with the
+// objects it is made of in this part and the others. Items of other types, like supports, are
+// left out.
+fn model_mesh(model: &Model, types: &[ObjectType]) -> Result<Mesh, Error> {
+    let mut items = model.build.items.iter().filter(|item| {
+        model
+            .object(item.path.as_deref(), item.objectid)
+            .is_some_and(|o| types.contains(&o.otype))
+    });
+    let item = items.next().ok_or(Error::EmptyModel)?;
+    if items.next().is_some() {
         return Err(Error::TooManyModels);
     }
-    let object = &model.objects[0];
+    model
+        .item_mesh(item)
+        .ok_or(Error::MissingObject(item.objectid))
+}
+
+pub fn slice_model(model: Model) -> Result<Vec<Plane>, Error> {
+    slice_model_types(model, ObjectType::PARTS)
+}
+
+// Same as slice_model, with the objects of the given types taken to be the model
+pub fn slice_model_types(model: Model, types: &[ObjectType]) -> Result<Vec<Plane>, Error> {
+    Ok(candidate_planes(&model_mesh(&model, types)?))
+}
 
+// Same as slice_model, but each plane is scored and the cuts are returned best-first
+pub fn rank_model_cuts(model: Model, options: &ScoreOptions) -> Result<Vec<CutCandidate>, Error> {
+    rank_model_cuts_types(model, options, ObjectType::PARTS)
+}
+
+pub fn rank_model_cuts_types(
+    model: Model,
+    options: &ScoreOptions,
+    types: &[ObjectType],
+) -> Result<Vec<CutCandidate>, Error> {
+    let mesh = model_mesh(&model, types)?;
+
+    Ok(rank_cuts(&mesh, candidate_planes(&mesh), options))
+}
+
+fn candidate_planes(mesh: &Mesh) -> Vec<Plane> {
     let mut cutting_planes: Vec<Plane> = Vec::new();
 
     // For every triangle
-    for triangle in object.mesh.triangles.iter() {
+    for triangle in mesh.triangles.iter() {
         // Get vertices of triangle
-        let v1 = object.mesh.vertices[triangle.v1];
-        let v2 = object.mesh.vertices[triangle.v2];
-        let v3 = object.mesh.vertices[triangle.v3];
+        let v1 = mesh.vertices[triangle.v1];
+        let v2 = mesh.vertices[triangle.v2];
+        let v3 = mesh.vertices[triangle.v3];
 
         // Form the "plane" from the triangle's verts
         let cut = Plane::from_points(
@@ -96,19 +136,23 @@ pub fn slice_model(model: Model) -> Result<Vec<Plane>, Error> {
 
     // We have the indices we want to remove
     // There may be more than one similar (e.g. 3 planes similar), meaning some indices could
-    // be duplicated. Use unique to solve this, then reverse it to remove back to front.
-    for i in marked_indices.into_iter().unique().rev() {
+    // be duplicated. Sort and dedup them, then remove back to front so that swap_remove only
+    // ever moves a plane that is kept.
+    marked_indices.sort_unstable();
+    marked_indices.dedup();
+    for i in marked_indices.into_iter().rev() {
         cutting_planes.swap_remove(i);
     }
 
-    Ok(cutting_planes)
+    cutting_planes
 }
 
 #[cfg(test)]
 mod tests {
     use crate::load::load_model;
-    use crate::slice_model;
-    use crate::threemf::NormalizeLocation;
+    use crate::score::ScoreOptions;
+    use crate::threemf::{Item, Model, NormalizeLocation, ObjectType};
+    use crate::{rank_model_cuts, slice_model, slice_model_types};
 
     #[test]
     fn test_slice_model() {
@@ -130,4 +174,69 @@ mod tests {
         let json = serde_json::to_string(&ret).unwrap();
         println!("{}", json)
     }
+
+    #[test]
+    fn test_object_types() {
+        use crate::geometry::{Transform, Vector};
+
+        // A cube printed on a support pillar
+        let mut model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+        let cube = model.objects[0].clone();
+        let moved = Transform::translation(Vector::new(5.0, 0.0, 0.0));
+        let mut pillar = cube.piece(cube.id + 1, cube.mesh.transform(&moved));
+        pillar.otype = ObjectType::Support;
+        model.objects.push(pillar);
+        model.build.items.push(Item {
+            objectid: cube.id + 1,
+            ..Item::default()
+        });
+
+        let read = Model::from_raw_data(&model.to_raw_data().unwrap()).unwrap();
+        assert_eq!(read.objects[0].otype, ObjectType::Model);
+        assert_eq!(read.objects[1].otype, ObjectType::Support);
+        assert!(quick_xml::de::from_str::<Model>(
+            r#"<model><resources><object id="1" name="" type="scaffold"/></resources></model>"#
+        )
+        .is_err());
+
+        // Supports are left out unless asked for,
+        assert!((read.volume(ObjectType::PARTS) - 8.0).abs() < 1e-9);
+        let all = [ObjectType::Model, ObjectType::Support];
+        assert!((read.volume(&all) - 16.0).abs() < 1e-9);
+        assert_eq!(
+            slice_model(read.clone()).unwrap(),
+            slice_model(load_model("data/centered_cube_2x2x2.3mf").unwrap()).unwrap()
+        );
+        assert!(slice_model_types(read.clone(), &all).is_err());
+
+        // and so are surfaces
+        let mut surface = read.clone();
+        surface.objects[1].otype = ObjectType::Surface;
+        assert!((surface.volume(ObjectType::PARTS) - 8.0).abs() < 1e-9);
+        assert!(slice_model(surface.clone()).is_ok());
+        let with_surfaces = [ObjectType::Model, ObjectType::Surface];
+        assert!((surface.volume(&with_surfaces) - 16.0).abs() < 1e-9);
+
+        let support = slice_model_types(read, &[ObjectType::Support]).unwrap();
+        assert!(support.iter().all(|p| p.point.x > 3.0));
+    }
+
+    #[test]
+    fn test_slice_frontplate() {
+        // Many similar planes, found in no particular order
+        let model = load_model("data/Frontplate.3mf").unwrap();
+        let planes = slice_model(model).unwrap();
+        assert!(!planes.is_empty());
+    }
+
+    #[test]
+    fn test_rank_model_cuts() {
+        let model = load_model("data/corner3.3mf").unwrap();
+        let ret = rank_model_cuts(model, &ScoreOptions::default()).unwrap();
+        for pair in ret.windows(2) {
+            assert!(pair[0].score >= pair[1].score);
+        }
+        let json = serde_json::to_string(&ret).unwrap();
+        println!("{}", json)
+    }
 }
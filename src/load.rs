@@ -13,11 +13,10 @@ fn test_load_model() {
     match load_model(path) {
         Ok(m) => {
             println!("{:?}", m);
-            assert!(true)
         }
         Err(e) => {
             println!("{:?}", e);
-            assert!(false)
+            panic!("{:?}", e)
         }
     }
 }
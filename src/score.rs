@@ -0,0 +1,207 @@
+use crate::analysis::analyze_overhangs;
+use crate::cut::cut_mesh;
+use crate::geometry::{Plane, Point, Vector};
+use crate::threemf::Mesh;
+use serde::Serialize;
+
+// Measurements of what a cut would produce
+#[derive(Debug, Clone, Serialize)]
+pub struct CutMetrics {
+    // Area of the cross-section, i.e. the surface available for gluing
+    pub section_area: f64,
+    // Number of closed pieces left after the cut
+    pub pieces: usize,
+    // Volume of the smaller side over the larger one, from 0 (lopsided) to 1 (even)
+    pub balance: f64,
+    // Overhang area added by the cut, summed over all pieces
+    pub overhang_area: f64,
+    // Distance from the plane to the closest fine feature
+    pub feature_distance: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CutCandidate {
+    pub plane: Plane,
+    // Between 0 and 1, higher is better
+    pub score: f64,
+    pub metrics: CutMetrics,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoreOptions {
+    // Build direction the pieces are printed in
+    pub up: Vector,
+    // How far (in degrees) a downward facing surface may lean from vertical before it needs
+    // supports
+    pub overhang_angle: f64,
+    // Edges shorter than this, as a fraction of the model size, are fine features
+    pub feature_size: f64,
+    // Cuts further than this from any fine feature, as a fraction of the model size, are not
+    // penalised
+    pub feature_clearance: f64,
+    pub area_weight: f64,
+    pub pieces_weight: f64,
+    pub balance_weight: f64,
+    pub overhang_weight: f64,
+    pub feature_weight: f64,
+}
+
+impl Default for ScoreOptions {
+    fn default() -> Self {
+        ScoreOptions {
+            up: Vector::Z,
+            overhang_angle: 45.0,
+            feature_size: 0.01,
+            feature_clearance: 0.05,
+            area_weight: 1.0,
+            pieces_weight: 1.0,
+            balance_weight: 1.0,
+            overhang_weight: 1.0,
+            feature_weight: 1.0,
+        }
+    }
+}
+
+// Cuts the mesh with every plane and returns the candidates sorted best-first
+pub fn rank_cuts(mesh: &Mesh, planes: Vec<Plane>, options: &ScoreOptions) -> Vec<CutCandidate> {
+    let size = mesh.size();
+    let fine_edges = fine_edges(mesh, options.feature_size * size);
+    let base_overhang = analyze_overhangs(mesh, options.up, options.overhang_angle).overhang_area;
+
+    let metrics: Vec<CutMetrics> = planes
+        .iter()
+        .map(|plane| {
+            let cut = cut_mesh(mesh, plane);
+
+            let above: f64 = cut.above.iter().map(|m| m.volume().abs()).sum();
+            let below: f64 = cut.below.iter().map(|m| m.volume().abs()).sum();
+            let balance = if above.max(below) > 0.0 {
+                above.min(below) / above.max(below)
+            } else {
+                0.0
+            };
+
+            let overhang: f64 = cut
+                .above
+                .iter()
+                .chain(cut.below.iter())
+                .map(|m| analyze_overhangs(m, options.up, options.overhang_angle).overhang_area)
+                .sum();
+
+            let feature_distance = fine_edges
+                .iter()
+                .map(|&(a, b)| {
+                    let (da, db) = (plane.distance(a), plane.distance(b));
+                    if da * db <= 0.0 {
+                        0.0
+                    } else {
+                        da.abs().min(db.abs())
+                    }
+                })
+                .fold(size, f64::min);
+
+            CutMetrics {
+                section_area: cut.section.area(),
+                pieces: cut.num_pieces(),
+                balance,
+                overhang_area: (overhang - base_overhang).max(0.0),
+                feature_distance,
+            }
+        })
+        .collect();
+
+    // Areas are only comparable between candidates, so normalise them over the set
+    let max_area = metrics.iter().map(|m| m.section_area).fold(0.0, f64::max);
+    let max_overhang = metrics.iter().map(|m| m.overhang_area).fold(0.0, f64::max);
+    let total_weight = options.area_weight
+        + options.pieces_weight
+        + options.balance_weight
+        + options.overhang_weight
+        + options.feature_weight;
+
+    let mut candidates: Vec<CutCandidate> = planes
+        .into_iter()
+        .zip(metrics)
+        .map(|(plane, metrics)| {
+            // A plane that misses the mesh is not a cut at all
+            let score = if metrics.pieces < 2 || total_weight <= 0.0 {
+                0.0
+            } else {
+                let area = ratio(metrics.section_area, max_area);
+                let pieces = 2.0 / metrics.pieces as f64;
+                let overhang = 1.0 - ratio(metrics.overhang_area, max_overhang);
+                let feature =
+                    (metrics.feature_distance / (options.feature_clearance * size)).min(1.0);
+
+                (options.area_weight * area
+                    + options.pieces_weight * pieces
+                    + options.balance_weight * metrics.balance
+                    + options.overhang_weight * overhang
+                    + options.feature_weight * feature)
+                    / total_weight
+            };
+            CutCandidate {
+                plane,
+                score,
+                metrics,
+            }
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    candidates
+}
+
+fn ratio(value: f64, max: f64) -> f64 {
+    if max > 0.0 {
+        value / max
+    } else {
+        0.0
+    }
+}
+
+// Edges shorter than `length`, as pairs of end points
+fn fine_edges(mesh: &Mesh, length: f64) -> Vec<(Point, Point)> {
+    let mut edges = Vec::new();
+    for triangle in mesh.triangles.iter() {
+        let [a, b, c] = triangle.indices();
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            // Each edge is shared by two triangles, only keep one direction
+            if from < to {
+                let (p, q) = (mesh.vertices[from], mesh.vertices[to]);
+                if (q - p).len() < length {
+                    edges.push((p, q));
+                }
+            }
+        }
+    }
+    edges
+}
+
+#[test]
+fn test_rank_cuts() {
+    use crate::load::load_model;
+
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+
+    let planes = vec![
+        // Misses the cube
+        Plane::new(Vector::Z, Point::new(0.0, 0.0, 5.0)),
+        // Off-center
+        Plane::new(Vector::Z, Point::new(0.0, 0.0, 0.8)),
+        // Even split
+        Plane::new(Vector::Z, Point::new(0.0, 0.0, 0.0)),
+    ];
+    let ranked = rank_cuts(mesh, planes, &ScoreOptions::default());
+
+    assert_eq!(ranked[0].plane.point, Point::new(0.0, 0.0, 0.0));
+    assert_eq!(ranked[0].metrics.pieces, 2);
+    assert!((ranked[0].metrics.balance - 1.0).abs() < 1e-9);
+    assert!((ranked[0].metrics.section_area - 4.0).abs() < 1e-9);
+    assert_eq!(ranked[2].score, 0.0);
+}
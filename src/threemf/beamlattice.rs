@@ -0,0 +1,629 @@
+use crate::boolean::{union, Operand};
+use crate::cut::open_edges;
+use crate::geometry::primitives::{capsule, sphere};
+use crate::geometry::{Point, Transform, Vector};
+use crate::threemf::Mesh;
+use serde::{Deserialize, Deserializer};
+
+// The 3MF beam lattice extension: struts between a mesh's vertices, described by their radius
+// at each end instead of by triangles, and balls on the vertices (balls extension). They are
+// kept as read and only turned into triangles on request.
+
+// How the end of a beam is closed off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CapMode {
+    Sphere,
+    HemiSphere,
+    Butt,
+}
+
+impl CapMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CapMode::Sphere => "sphere",
+            CapMode::HemiSphere => "hemisphere",
+            CapMode::Butt => "butt",
+        }
+    }
+}
+
+// Which vertices have a ball on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BallMode {
+    None,
+    // Only those with a <ball>
+    Mixed,
+    // Every end of a beam
+    All,
+}
+
+impl BallMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BallMode::None => "none",
+            BallMode::Mixed => "mixed",
+            BallMode::All => "all",
+        }
+    }
+}
+
+// Leaving out a radius, cap or property takes the lattice's
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Beam {
+    pub v1: usize,
+    pub v2: usize,
+    #[serde(default)]
+    pub r1: Option<f64>,
+    // Defaults to r1
+    #[serde(default)]
+    pub r2: Option<f64>,
+    #[serde(default)]
+    pub cap1: Option<CapMode>,
+    #[serde(default)]
+    pub cap2: Option<CapMode>,
+    #[serde(default)]
+    pub pid: Option<usize>,
+    #[serde(default)]
+    pub p1: Option<usize>,
+    #[serde(default)]
+    pub p2: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Ball {
+    pub vindex: usize,
+    #[serde(default)]
+    pub r: Option<f64>,
+    #[serde(default)]
+    pub pid: Option<usize>,
+    #[serde(default)]
+    pub p: Option<usize>,
+}
+
+// A named group of beams and balls, by their index
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BeamSet {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub identifier: Option<String>,
+    #[serde(rename = "b:ref", alias = "ref", deserialize_with = "indices", default)]
+    pub beams: Vec<usize>,
+    #[serde(
+        rename = "b2:ballref",
+        alias = "ballref",
+        deserialize_with = "indices",
+        default
+    )]
+    pub balls: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BeamLattice {
+    #[serde(default)]
+    pub minlength: f64,
+    pub radius: f64,
+    #[serde(default = "default_cap")]
+    pub cap: CapMode,
+    #[serde(
+        rename = "b2:ballmode",
+        alias = "ballmode",
+        default = "default_ball_mode"
+    )]
+    pub ballmode: BallMode,
+    #[serde(rename = "b2:ballradius", alias = "ballradius", default)]
+    pub ballradius: Option<f64>,
+    // Objects the lattice is clipped by, or stands in for, kept as they are
+    #[serde(default)]
+    pub clippingmode: Option<String>,
+    #[serde(default)]
+    pub clippingmesh: Option<usize>,
+    #[serde(default)]
+    pub representationmesh: Option<usize>,
+    #[serde(default)]
+    pub pid: Option<usize>,
+    #[serde(default)]
+    pub pindex: Option<usize>,
+    #[serde(
+        rename = "b:beams",
+        alias = "beams",
+        deserialize_with = "beams",
+        default
+    )]
+    pub beams: Vec<Beam>,
+    #[serde(
+        rename = "b2:balls",
+        alias = "b:balls",
+        alias = "balls",
+        deserialize_with = "balls",
+        default
+    )]
+    pub balls: Vec<Ball>,
+    #[serde(
+        rename = "b:beamsets",
+        alias = "beamsets",
+        deserialize_with = "beam_sets",
+        default
+    )]
+    pub beamsets: Vec<BeamSet>,
+}
+
+fn default_cap() -> CapMode {
+    CapMode::Sphere
+}
+
+fn default_ball_mode() -> BallMode {
+    BallMode::None
+}
+
+impl BeamLattice {
+    pub fn new(radius: f64) -> Self {
+        BeamLattice {
+            minlength: 0.0,
+            radius,
+            cap: default_cap(),
+            ballmode: default_ball_mode(),
+            ballradius: None,
+            clippingmode: None,
+            clippingmesh: None,
+            representationmesh: None,
+            pid: None,
+            pindex: None,
+            beams: Vec::new(),
+            balls: Vec::new(),
+            beamsets: Vec::new(),
+        }
+    }
+
+    // Radius at each end of a beam
+    pub fn beam_radii(&self, beam: &Beam) -> [f64; 2] {
+        let r1 = beam.r1.unwrap_or(self.radius);
+        [r1, beam.r2.unwrap_or(r1)]
+    }
+
+    // Balls to be made, as a vertex and a radius each
+    pub fn ball_radii(&self) -> Vec<(usize, f64)> {
+        let radius = self.ballradius.unwrap_or(self.radius);
+        let mut radii: Vec<(usize, f64)> = match self.ballmode {
+            BallMode::None => return Vec::new(),
+            BallMode::Mixed => Vec::new(),
+            BallMode::All => self
+                .beams
+                .iter()
+                .flat_map(|b| [b.v1, b.v2])
+                .map(|v| (v, radius))
+                .collect(),
+        };
+        // Balls of their own replace the default ones
+        for ball in self.balls.iter() {
+            radii.retain(|&(v, _)| v != ball.vindex);
+            radii.push((ball.vindex, ball.r.unwrap_or(radius)));
+        }
+        radii.sort_by_key(|&(v, _)| v);
+        radii.dedup_by_key(|&mut (v, _)| v);
+        radii
+    }
+
+    // Every beam as a capsule and every ball as a sphere, with `segments` sides around each,
+    // merged into one closed surface. Beams and balls at vertices that aren't in `vertices` are
+    // left out. Triangles take the property of the beam or ball they are on: a beam's first
+    // one along its first half and its second along the rest.
+    pub fn tessellate(&self, vertices: &[Point], segments: usize) -> Mesh {
+        let mut solids: Vec<Solid> = Vec::new();
+        for beam in self.beams.iter() {
+            let ends = match (vertices.get(beam.v1), vertices.get(beam.v2)) {
+                (Some(&p), Some(&q)) => [p, q],
+                _ => continue,
+            };
+            let length = (ends[1] - ends[0]).len();
+            if length <= self.minlength || length == 0.0 {
+                continue;
+            }
+            let p1 = beam.p1.or(self.pindex);
+            solids.push(Solid::Beam {
+                ends,
+                radii: self.beam_radii(beam),
+                rounded: [beam.cap1, beam.cap2].map(|cap| cap.unwrap_or(self.cap) != CapMode::Butt),
+                pid: beam.pid.or(self.pid),
+                properties: [p1, beam.p2.or(p1)],
+            });
+        }
+        for (v, radius) in self.ball_radii() {
+            let center = match vertices.get(v) {
+                Some(&center) => center,
+                None => continue,
+            };
+            let ball = self.balls.iter().rfind(|b| b.vindex == v);
+            solids.push(Solid::Ball {
+                center,
+                radius,
+                pid: ball.and_then(|b| b.pid).or(self.pid),
+                property: ball.and_then(|b| b.p).or(self.pindex),
+            });
+        }
+
+        // Merged two at a time, so that each union is of parts of about the same size
+        let mut parts: Vec<Part> = (0..solids.len())
+            .map(|k| Part::solid(&solids, k, 0, segments))
+            .collect();
+        while parts.len() > 1 {
+            let mut merged = Vec::with_capacity(parts.len().div_ceil(2));
+            let mut pairs = parts.into_iter();
+            while let Some(first) = pairs.next() {
+                merged.push(match pairs.next() {
+                    Some(second) => first.merge(second, &solids, segments),
+                    None => first,
+                });
+            }
+            parts = merged;
+        }
+
+        let Part {
+            mut mesh, origin, ..
+        } = match parts.pop() {
+            Some(part) => part,
+            None => return Mesh::default(),
+        };
+        for (triangle, &k) in mesh.triangles.iter_mut().zip(origin.iter()) {
+            let corners = triangle.indices().map(|v| mesh.vertices[v]);
+            let (pid, properties) = solids[k].properties(corners);
+            *triangle = triangle.clone().with_properties(pid, properties);
+        }
+        mesh
+    }
+}
+
+// A beam or ball of a lattice, in place
+enum Solid {
+    Beam {
+        ends: [Point; 2],
+        radii: [f64; 2],
+        rounded: [bool; 2],
+        pid: Option<usize>,
+        properties: [Option<usize>; 2],
+    },
+    Ball {
+        center: Point,
+        radius: f64,
+        pid: Option<usize>,
+        property: Option<usize>,
+    },
+}
+
+// An axis well away from the coordinate axes and the directions beams usually run in
+const TIPPED: Vector = Vector {
+    x: 0.4,
+    y: 0.7,
+    z: 1.3,
+};
+
+// Times a solid is placed a little differently to get it to merge
+const ATTEMPTS: usize = 8;
+
+impl Solid {
+    // Each solid is turned about its axis by a different fraction of a segment, and moved by up
+    // to a thousandth of its radius, so that solids meeting at a vertex don't meet all in the
+    // same places. Union doesn't cope with many surfaces through one point, or with surfaces
+    // that only touch. Balls are turned about a tipped axis, so that their poles don't land on
+    // the ends of beams.
+    fn mesh(&self, solid: usize, attempt: usize, segments: usize) -> Mesh {
+        // Well spread out fractions, different for every solid and attempt
+        let n = (solid * ATTEMPTS + attempt + 1) as f64;
+        let [a, b, c, d] = [0.618034, 0.819173, 0.671044, 0.549700].map(|f| (n * f).fract());
+        let twist = a * std::f64::consts::TAU / segments.max(3) as f64;
+        let offset = Vector::new(b - 0.5, c - 0.5, d - 0.5) * 2e-3;
+
+        match *self {
+            Solid::Beam {
+                ends: [p, q],
+                radii: [r1, r2],
+                rounded,
+                ..
+            } => capsule(r1, r2, (q - p).len(), segments, rounded).transform(
+                &Transform::rotation(Vector::Z, twist)
+                    .then(&Transform::rotation_between(Vector::Z, q - p))
+                    .then(&Transform::translation(
+                        p - Point::zero() + offset * r1.max(r2),
+                    )),
+            ),
+            Solid::Ball { center, radius, .. } => sphere(radius, segments, segments / 2).transform(
+                &Transform::rotation(TIPPED, twist).then(&Transform::translation(
+                    center - Point::zero() + offset * radius,
+                )),
+            ),
+        }
+    }
+
+    // The property group and properties of a triangle on it
+    fn properties(&self, corners: [Point; 3]) -> (Option<usize>, Option<[usize; 3]>) {
+        match *self {
+            Solid::Beam {
+                ends: [p, q],
+                pid: Some(pid),
+                properties,
+                ..
+            } => {
+                let axis = q - p;
+                let end =
+                    |c: Point| properties[usize::from((c - p).dot(axis) > axis.dot(axis) / 2.0)];
+                match corners.map(end) {
+                    [Some(a), Some(b), Some(c)] => (Some(pid), Some([a, b, c])),
+                    _ => (None, None),
+                }
+            }
+            Solid::Ball {
+                pid: Some(pid),
+                property: Some(p),
+                ..
+            } => (Some(pid), Some([p; 3])),
+            _ => (None, None),
+        }
+    }
+}
+
+// Some of the solids merged, with the solid each triangle is on
+struct Part {
+    mesh: Mesh,
+    origin: Vec<usize>,
+    solids: Vec<usize>,
+}
+
+impl Part {
+    fn solid(solids: &[Solid], k: usize, attempt: usize, segments: usize) -> Part {
+        let mesh = solids[k].mesh(k, attempt, segments);
+        Part {
+            origin: vec![k; mesh.triangles.len()],
+            mesh,
+            solids: vec![k],
+        }
+    }
+
+    fn union(&self, other: &Part) -> Part {
+        let result = union(&self.mesh, &other.mesh);
+        let origin = result
+            .provenance
+            .iter()
+            .map(|p| match p.operand {
+                Operand::First => self.origin[p.triangle],
+                Operand::Second => other.origin[p.triangle],
+            })
+            .collect();
+        Part {
+            mesh: result.mesh,
+            origin,
+            solids: [self.solids.as_slice(), other.solids.as_slice()].concat(),
+        }
+    }
+
+    // Union doesn't always close up the surface where two solids only just graze each other.
+    // When it doesn't, the other part's solids are added one at a time instead, each placed
+    // a little differently until it does.
+    fn merge(self, other: Part, solids: &[Solid], segments: usize) -> Part {
+        let merged = self.union(&other);
+        if open_edges(&merged.mesh.triangles).is_empty() {
+            return merged;
+        }
+        let mut part = self;
+        for &k in other.solids.iter() {
+            let add = |attempt| part.union(&Part::solid(solids, k, attempt, segments));
+            part = (0..ATTEMPTS - 1)
+                .map(add)
+                .find(|merged| open_edges(&merged.mesh.triangles).is_empty())
+                .unwrap_or_else(|| add(ATTEMPTS - 1));
+        }
+        part
+    }
+}
+
+#[derive(Deserialize)]
+struct Beams {
+    #[serde(rename = "b:beam", alias = "beam", default)]
+    beam: Vec<Beam>,
+}
+
+fn beams<'de, D>(deserializer: D) -> Result<Vec<Beam>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Beams::deserialize(deserializer)?.beam)
+}
+
+#[derive(Deserialize)]
+struct Balls {
+    #[serde(rename = "b2:ball", alias = "b:ball", alias = "ball", default)]
+    ball: Vec<Ball>,
+}
+
+fn balls<'de, D>(deserializer: D) -> Result<Vec<Ball>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Balls::deserialize(deserializer)?.ball)
+}
+
+#[derive(Deserialize)]
+struct BeamSets {
+    #[serde(rename = "b:beamset", alias = "beamset", default)]
+    beamset: Vec<BeamSet>,
+}
+
+fn beam_sets<'de, D>(deserializer: D) -> Result<Vec<BeamSet>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(BeamSets::deserialize(deserializer)?.beamset)
+}
+
+#[derive(Deserialize)]
+struct Index {
+    index: usize,
+}
+
+fn indices<'de, D>(deserializer: D) -> Result<Vec<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let elements = Vec::<Index>::deserialize(deserializer)?;
+    Ok(elements.into_iter().map(|e| e.index).collect())
+}
+
+#[test]
+fn test_beam_lattice() {
+    use crate::cut::{cut_mesh, open_edges};
+    use crate::geometry::primitives::cuboid;
+    use crate::geometry::Plane;
+    use crate::threemf::{validate_model, Model};
+    use std::f64::consts::PI;
+
+    // A lattice-only object: an L of two beams with a ball at the corner, coloured in
+    let xml = r##"<model unit="millimeter"
+        xmlns:m="http://schemas.microsoft.com/3dmanufacturing/material/2015/02"
+        xmlns:b="http://schemas.microsoft.com/3dmanufacturing/beamlattice/2017/02"
+        xmlns:b2="http://schemas.microsoft.com/3dmanufacturing/beamlattice/balls/2020/07">
+        <resources>
+            <m:colorgroup id="2">
+                <m:color color="#FF0000"/><m:color color="#00FF00"/><m:color color="#0000FF"/>
+            </m:colorgroup>
+            <object id="1" name="Strut" type="model">
+                <mesh>
+                    <vertices>
+                        <vertex x="0" y="0" z="0"/><vertex x="10" y="0" z="0"/>
+                        <vertex x="10" y="0" z="10"/>
+                    </vertices>
+                    <triangles/>
+                    <b:beamlattice minlength="0.01" radius="1" cap="butt" b2:ballmode="mixed"
+                        pid="2" pindex="0">
+                        <b:beams>
+                            <b:beam v1="0" v2="1"/>
+                            <b:beam v1="1" v2="2" r1="1" r2="0.5" cap2="sphere" p1="1" p2="2"/>
+                        </b:beams>
+                        <b2:balls><b2:ball vindex="1" r="1.5" p="1"/></b2:balls>
+                        <b:beamsets>
+                            <b:beamset name="Legs"><b:ref index="0"/><b:ref index="1"/></b:beamset>
+                        </b:beamsets>
+                    </b:beamlattice>
+                </mesh>
+            </object>
+        </resources>
+        <build><item objectid="1"/></build>
+    </model>"##;
+    let model: Model = quick_xml::de::from_str(xml).unwrap();
+    let mesh = &model.objects[0].mesh;
+    assert!(mesh.triangles.is_empty());
+    let lattice = mesh.beamlattice.as_ref().unwrap();
+    assert_eq!(lattice.beams.len(), 2);
+    assert_eq!(lattice.beam_radii(&lattice.beams[1]), [1.0, 0.5]);
+    assert_eq!(lattice.beams[1].cap2, Some(CapMode::Sphere));
+    assert_eq!(lattice.ball_radii(), vec![(1, 1.5)]);
+    assert_eq!(lattice.beamsets[0].beams, vec![0, 1]);
+
+    let tessellated = mesh.tessellate_lattice(32);
+    assert!(tessellated.beamlattice.is_none());
+    assert!(open_edges(&tessellated.triangles).is_empty());
+    // Each solid is moved a little to merge them
+    let (min, max) = tessellated.bounds();
+    assert!(min.x.abs() < 2e-3 && (max.z - 10.5).abs() < 2e-3);
+
+    // Two beams and a ball, merged where they overlap at the corner
+    let parts = [
+        PI * 10.0,
+        PI * 10.0 * (1.0 + 0.5 + 0.25) / 3.0 + PI / 12.0,
+        4.0 / 3.0 * PI * 1.5f64.powi(3),
+    ];
+    assert_eq!(tessellated.components().len(), 1);
+    let volume = tessellated.volume();
+    assert!(parts.iter().all(|&part| volume > part));
+    assert!(volume < parts.iter().sum::<f64>() - 5.0);
+
+    // Each triangle has the colour of the beam or ball it is on: the first beam and the ball
+    // take the lattice's and their own, and the second beam its own at each end
+    let colours = |on: &dyn Fn(Point) -> bool| {
+        let mut colours: Vec<Option<[usize; 3]>> = tessellated
+            .triangles
+            .iter()
+            .filter(|t| tessellated.triangle_points(t).into_iter().all(on))
+            .map(|t| t.properties())
+            .collect();
+        colours.sort();
+        colours.dedup();
+        colours
+    };
+    assert!(tessellated.triangles.iter().all(|t| t.pid == Some(2)));
+    assert_eq!(colours(&|p| p.x < 8.0), [Some([0; 3])]);
+    assert_eq!(colours(&|p| p.x > 10.0 && p.z < 0.0), [Some([1; 3])]);
+    let mut ends = [0, 0];
+    for triangle in tessellated.triangles.iter() {
+        let corners = tessellated.triangle_points(triangle);
+        // Above the first beam. The ball shares the second beam's first colour.
+        if corners.iter().all(|p| p.z > 1.0) {
+            let expected = corners.map(|p| if p.z < 5.0 { 1 } else { 2 });
+            assert_eq!(triangle.properties(), Some(expected));
+            ends[usize::from(expected == [2; 3])] += 1;
+        }
+    }
+    assert!(ends[0] > 0 && ends[1] > 0);
+
+    // Beams and balls at vertices that aren't there are left out
+    let mut broken = lattice.clone();
+    broken.beams[0].v2 = 7;
+    broken.balls[0].vindex = 9;
+    let left = broken.tessellate(&mesh.vertices, 32);
+    assert_eq!(left.components().len(), 1);
+    assert!((left.volume() - parts[1]).abs() < parts[1] * 0.02);
+    let mut invalid = model.clone();
+    invalid.objects[0].mesh.beamlattice = Some(broken);
+    let messages: Vec<String> = validate_model(&invalid)
+        .iter()
+        .filter(|f| f.is_error())
+        .map(|f| f.message.clone())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "1 beams of object 1 use vertices past the end of the list, the first is beam 0",
+            "1 balls of object 1 use vertices past the end of the list, the first is ball 0",
+        ]
+    );
+
+    // Tessellated lattices can be cut, here through the corner, leaving one piece either side
+    let cut = cut_mesh(
+        &tessellated,
+        &Plane::new(Vector::X, Point::new(9.7, 0.0, 0.0)),
+    );
+    assert_eq!((cut.above.len(), cut.below.len()), (1, 1));
+    for piece in cut.above.iter().chain(cut.below.iter()) {
+        assert!(open_edges(&piece.triangles).is_empty());
+    }
+    let pieces = cut.above[0].volume() + cut.below[0].volume();
+    assert!((pieces - volume).abs() < 1e-6);
+
+    // and written back as they are, requiring the lattice extension as there is nothing else
+    // to the object
+    let mut xml = Vec::new();
+    model.write_xml(&mut xml).unwrap();
+    assert!(String::from_utf8(xml)
+        .unwrap()
+        .contains(r#"requiredextensions="b""#));
+    let mut read = Model::from_raw_data(&model.to_raw_data().unwrap()).unwrap();
+    assert_eq!(read.objects, model.objects);
+    read.tessellate_lattices(32);
+    assert_eq!(read.objects[0].mesh, tessellated);
+
+    // A lattice is merged with the rest of its object too, here a beam out of a block
+    let mut block = cuboid(Vector::new(4.0, 4.0, 4.0));
+    block
+        .vertices
+        .extend([Point::new(0.0, 0.0, 2.0), Point::new(10.0, 0.0, 2.0)]);
+    let mut beam = BeamLattice::new(1.0);
+    beam.cap = CapMode::Butt;
+    beam.beams = vec![lattice.beams[0].clone()];
+    beam.beams[0].v1 = 8;
+    beam.beams[0].v2 = 9;
+    block.beamlattice = Some(beam);
+    let merged = block.tessellate_lattice(32);
+    assert!(open_edges(&merged.triangles).is_empty());
+    assert_eq!(merged.components().len(), 1);
+    assert!((merged.volume() - (64.0 + PI * 8.0)).abs() < 0.5);
+}
@@ -0,0 +1,164 @@
+use crate::geometry::Transform;
+use crate::threemf::xml_parse::transform;
+use serde::Deserialize;
+
+// Object placement in a 3MF model. `path` and `uuid` come from the production extension: a
+// path names the model part the object is in (when it isn't the part referring to it), and
+// UUIDs identify items across files.
+
+// An object to be printed
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct Item {
+    pub objectid: usize,
+    #[serde(rename = "p:path", default)]
+    pub path: Option<String>,
+    #[serde(rename = "p:UUID", default)]
+    pub uuid: Option<String>,
+    #[serde(deserialize_with = "transform", default)]
+    pub transform: Transform,
+    #[serde(default)]
+    pub partnumber: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct Build {
+    #[serde(rename = "p:UUID", default)]
+    pub uuid: Option<String>,
+    #[serde(rename = "item", default)]
+    pub items: Vec<Item>,
+}
+
+// Another object placed as part of an object
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct Component {
+    pub objectid: usize,
+    #[serde(rename = "p:path", default)]
+    pub path: Option<String>,
+    #[serde(rename = "p:UUID", default)]
+    pub uuid: Option<String>,
+    #[serde(deserialize_with = "transform", default)]
+    pub transform: Transform,
+}
+
+#[test]
+fn test_production_parts() {
+    use crate::cut::cut_model;
+    use crate::geometry::{Plane, Point, Vector};
+    use crate::load::load_model;
+    use crate::threemf::{validate_model, Color, ColorGroup, Model, Object, ObjectType};
+
+    // Two copies of a cube from another part, side by side in an assembly
+    let cube = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let path = "/3D/parts/cube.model".to_string();
+    let placed = |x: f64, uuid: &str| Component {
+        objectid: cube.objects[0].id,
+        path: Some(path.clone()),
+        uuid: Some(uuid.to_string()),
+        transform: Transform::translation(Vector::new(x, 0.0, 0.0)),
+    };
+    let assembly = Object {
+        id: 7,
+        name: "Pair".to_string(),
+        otype: ObjectType::Model,
+        uuid: Some("b0c2a9c4-8d3c-4b7e-9d2a-000000000007".to_string()),
+        pid: None,
+        pindex: None,
+        slicestackid: None,
+        meshresolution: None,
+        metadata: Default::default(),
+        mesh: Default::default(),
+        components: vec![
+            placed(-3.0, "b0c2a9c4-8d3c-4b7e-9d2a-000000000001"),
+            placed(3.0, "b0c2a9c4-8d3c-4b7e-9d2a-000000000002"),
+        ],
+    };
+    let mut model = Model {
+        unit: cube.unit,
+        objects: vec![assembly],
+        build: Build {
+            uuid: Some("b0c2a9c4-8d3c-4b7e-9d2a-0000000000b0".to_string()),
+            items: vec![Item {
+                objectid: 7,
+                uuid: Some("b0c2a9c4-8d3c-4b7e-9d2a-0000000000a0".to_string()),
+                transform: Transform::translation(Vector::new(0.0, 0.0, 1.0)),
+                ..Item::default()
+            }],
+        },
+        ..Model::empty()
+    };
+    // Red by default, in a colour group of the part's own, with a blue top
+    let (red, blue) = (Color::new(255, 0, 0, 255), Color::new(0, 0, 255, 255));
+    let mut part = cube.clone();
+    part.build = Build::default();
+    part.colors.push(ColorGroup {
+        id: 5,
+        colors: vec![red, blue],
+    });
+    let object = &mut part.objects[0];
+    (object.pid, object.pindex) = (Some(5), Some(0));
+    let vertices = object.mesh.vertices.clone();
+    for triangle in object.mesh.triangles.iter_mut() {
+        if triangle.indices().iter().all(|&i| vertices[i].z == 1.0) {
+            *triangle = triangle.clone().with_properties(Some(5), Some([1; 3]));
+        }
+    }
+    model.parts.insert(path.clone(), part);
+
+    let data = model.to_raw_data().unwrap();
+    let read = Model::from_raw_data(&data).unwrap();
+    assert_eq!(read, model);
+    assert_eq!(read.parts.len(), 1);
+
+    let item = &read.build.items[0];
+    let mesh = read.item_mesh(item).unwrap();
+    assert!((mesh.volume() - 16.0).abs() < 1e-9);
+    let (min, max) = mesh.bounds();
+    assert_eq!(
+        (min, max),
+        (Point::new(-4.0, -1.0, 0.0), Point::new(4.0, 1.0, 2.0))
+    );
+
+    // Cuts are looked for on the placed item too: the cubes' tops, as their bottoms are on
+    // the plate and their sides are upright
+    let planes = crate::slice_model(read.clone()).unwrap();
+    assert_eq!(planes.len(), 1);
+    assert!((planes[0].point.z - 2.0).abs() < 1e-9);
+    let options = crate::score::ScoreOptions::default();
+    assert_eq!(
+        crate::rank_model_cuts(read.clone(), &options)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Cutting works on the placed item, so each cube is cut
+    let cut = cut_model(&read, &Plane::new(Vector::Z, Point::new(0.0, 0.0, 1.0)));
+    assert_eq!(cut.objects.len(), 4);
+    assert_eq!(cut.build.items.len(), 4);
+    for object in cut.objects.iter() {
+        assert!((object.mesh.volume() - 4.0).abs() < 1e-9);
+    }
+
+    // The part's colour group comes along under an id of the cut model's own, and the
+    // triangles and caps keep their colours
+    assert_eq!(cut.colors.len(), 1);
+    assert_eq!(cut.colors[0].colors, vec![red, blue]);
+    assert!(cut.objects.iter().all(|o| o.id != cut.colors[0].id));
+    for object in cut.objects.iter() {
+        for triangle in object.mesh.triangles.iter() {
+            let corners = object.mesh.triangle_points(triangle);
+            let expected = if corners.iter().all(|p| p.z == 2.0) {
+                blue
+            } else {
+                red
+            };
+            assert_eq!(cut.triangle_colors(object, triangle), Some([expected; 3]));
+        }
+    }
+    assert!(!validate_model(&cut).iter().any(|f| f.is_error()));
+
+    // A missing object can't be placed
+    let mut broken = read.clone();
+    broken.objects[0].components[0].objectid = 99;
+    assert!(broken.item_mesh(item).is_none());
+}
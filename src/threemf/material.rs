@@ -0,0 +1,173 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+// Resources from the 3MF materials and properties extension. Objects and triangles refer to
+// them by their id (pid) and an index into them.
+
+// An sRGB colour with alpha, written #RRGGBB or #RRGGBBAA in 3MF files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    pub fn parse(text: &str) -> Option<Color> {
+        let hex = text.strip_prefix('#')?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |k: usize| u8::from_str_radix(&hex[2 * k..2 * k + 2], 16).ok();
+        let a = if hex.len() == 8 { channel(3)? } else { 255 };
+        Some(Color::new(channel(0)?, channel(1)?, channel(2)?, a))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.r, self.g, self.b, self.a
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        Color::parse(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid colour {:?}", text)))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct BaseMaterial {
+    pub name: String,
+    #[serde(rename = "displaycolor")]
+    pub color: Color,
+}
+
+// A <basematerials> group
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct BaseMaterials {
+    pub id: usize,
+    #[serde(rename = "base", default)]
+    pub materials: Vec<BaseMaterial>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ColorElement {
+    color: Color,
+}
+
+// A <colorgroup>
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct ColorGroup {
+    pub id: usize,
+    #[serde(
+        rename = "m:color",
+        alias = "color",
+        deserialize_with = "color_elements",
+        default
+    )]
+    pub colors: Vec<Color>,
+}
+
+fn color_elements<'de, D>(deserializer: D) -> Result<Vec<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let elements = Vec::<ColorElement>::deserialize(deserializer)?;
+    Ok(elements.into_iter().map(|e| e.color).collect())
+}
+
+#[test]
+fn test_parse_color() {
+    assert_eq!(
+        Color::parse("#A0A0A0"),
+        Some(Color::new(160, 160, 160, 255))
+    );
+    assert_eq!(Color::parse("#ff000080"), Some(Color::new(255, 0, 0, 128)));
+    assert_eq!(Color::parse("#ff00"), None);
+    assert_eq!(Color::parse("ff0000"), None);
+    assert_eq!(Color::new(1, 2, 255, 16).to_string(), "#0102FF10");
+}
+
+#[test]
+fn test_cut_keeps_colors() {
+    use crate::cut::cut_model;
+    use crate::geometry::{Plane, Point, Vector};
+    use crate::load::load_model;
+    use crate::threemf::Model;
+
+    let model = load_model("data/Frontplate.3mf").unwrap();
+    assert_eq!(model.colors.len(), 1);
+    assert_eq!(model.colors[0].colors[1], Color::new(160, 160, 160, 255));
+    assert_eq!(
+        (model.objects[0].pid, model.objects[0].pindex),
+        (Some(2), Some(0))
+    );
+
+    // Grey by default, with a red top and a side going from blue at the bottom to red at the top
+    let (grey, red, blue) = (
+        Color::new(128, 128, 128, 255),
+        Color::new(255, 0, 0, 255),
+        Color::new(0, 0, 255, 255),
+    );
+    let mut model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    model.materials.push(BaseMaterials {
+        id: 2,
+        materials: vec![BaseMaterial {
+            name: "PLA".to_string(),
+            color: grey,
+        }],
+    });
+    model.colors.push(ColorGroup {
+        id: 3,
+        colors: vec![red, blue],
+    });
+    let object = &mut model.objects[0];
+    object.pid = Some(2);
+    object.pindex = Some(0);
+    let vertices = object.mesh.vertices.clone();
+    for triangle in object.mesh.triangles.iter_mut() {
+        let corners = triangle.indices().map(|i| vertices[i]);
+        if corners.iter().all(|p| p.z == 1.0) {
+            *triangle = triangle.clone().with_properties(Some(3), Some([0; 3]));
+        } else if corners.iter().all(|p| p.x == 1.0) {
+            let properties = corners.map(|p| if p.z > 0.0 { 0 } else { 1 });
+            *triangle = triangle.clone().with_properties(Some(3), Some(properties));
+        }
+    }
+
+    let data = model.to_raw_data().unwrap();
+    let model = Model::from_raw_data(&data).unwrap();
+    let cut = cut_model(&model, &Plane::new(Vector::Z, Point::zero()));
+    assert_eq!(cut.colors, model.colors);
+    assert_eq!(cut.materials, model.materials);
+    for (k, object) in cut.objects.iter().enumerate() {
+        let side = if k == 0 { red } else { blue };
+        for triangle in object.mesh.triangles.iter() {
+            let corners = object.mesh.triangle_points(triangle);
+            let expected = if corners.iter().all(|p| p.z == 1.0) {
+                red
+            } else if corners.iter().all(|p| p.x == 1.0) {
+                side
+            } else {
+                // Including the caps
+                grey
+            };
+            assert_eq!(cut.triangle_colors(object, triangle), Some([expected; 3]));
+        }
+    }
+}
@@ -1,13 +1,25 @@
-use crate::geometry::{Point, Triangle, Vector};
+use crate::boolean::{union, Operand};
+use crate::cut::open_edges;
+use crate::geometry::bvh::Bvh;
+use crate::geometry::containment::{containment, Containment};
+use crate::geometry::hull::convex_hull;
+use crate::geometry::obb::oriented_bounding_box;
+use crate::geometry::{OrientedBox, Point, Transform, Triangle, Vector};
+use crate::threemf::beamlattice::BeamLattice;
 use crate::threemf::xml_parse::*;
 use serde::Deserialize;
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
 pub struct Mesh {
     #[serde(rename = "vertices", with = "Vertices", default)]
     pub vertices: Vec<Point>,
     #[serde(rename = "triangles", with = "Triangles", default)]
     pub triangles: Vec<Triangle>,
+    // Beams between the vertices (beam lattice extension). Only `tessellate_lattice` and
+    // `transform` take them into account.
+    #[serde(rename = "b:beamlattice", alias = "beamlattice", default)]
+    pub beamlattice: Option<BeamLattice>,
 }
 
 pub struct NormalizeLocation {}
@@ -96,6 +108,223 @@ impl Mesh {
     }
 }
 
+impl Mesh {
+    pub fn new(vertices: Vec<Point>, triangles: Vec<Triangle>) -> Self {
+        Mesh {
+            vertices,
+            triangles,
+            beamlattice: None,
+        }
+    }
+
+    pub fn triangle_points(&self, triangle: &Triangle) -> [Point; 3] {
+        [
+            self.vertices[triangle.v1],
+            self.vertices[triangle.v2],
+            self.vertices[triangle.v3],
+        ]
+    }
+
+    // Normal of a triangle, following its winding. Its length is twice the triangle's area.
+    pub fn triangle_normal(&self, triangle: &Triangle) -> Vector {
+        let [p1, p2, p3] = self.triangle_points(triangle);
+        (p2 - p1).cross(p3 - p1)
+    }
+
+    pub fn triangle_area(&self, triangle: &Triangle) -> f64 {
+        self.triangle_normal(triangle).len() / 2.0
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        self.triangles.iter().map(|t| self.triangle_area(t)).sum()
+    }
+
+    // Signed volume enclosed by the mesh (divergence theorem). Only meaningful for closed
+    // meshes; positive when the triangles are wound outwards.
+    pub fn volume(&self) -> f64 {
+        let mut volume = 0.0;
+        for triangle in self.triangles.iter() {
+            let [p1, p2, p3] = self.triangle_points(triangle);
+            let (a, b, c) = (p1 - Point::zero(), p2 - Point::zero(), p3 - Point::zero());
+            volume += a.dot(b.cross(c));
+        }
+        volume / 6.0
+    }
+
+    // Axis aligned bounds as (min, max)
+    pub fn bounds(&self) -> (Point, Point) {
+        if self.vertices.is_empty() {
+            return (Point::zero(), Point::zero());
+        }
+
+        let mut min = self.vertices[0];
+        let mut max = self.vertices[0];
+        for p in self.vertices.iter() {
+            min = Point::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        (min, max)
+    }
+
+    // Length of the bounding box diagonal. Useful as a scale for tolerances.
+    pub fn size(&self) -> f64 {
+        let (min, max) = self.bounds();
+        (max - min).len()
+    }
+
+    // See geometry::bvh::Bvh
+    pub fn bvh(&self) -> Bvh {
+        Bvh::new(self)
+    }
+
+    // See geometry::containment
+    pub fn containment(&self, p: Point) -> Containment {
+        containment(self.triangles.iter().map(|t| self.triangle_points(t)), p)
+    }
+
+    // See geometry::hull::convex_hull
+    pub fn convex_hull(&self) -> Option<Mesh> {
+        convex_hull(&self.vertices)
+    }
+
+    // See geometry::obb::oriented_bounding_box
+    pub fn oriented_bounding_box(&self) -> OrientedBox {
+        oriented_bounding_box(&self.vertices)
+    }
+
+    // Copy of the mesh with every vertex transformed. Mirroring transforms flip the winding so
+    // that the triangles keep facing outwards.
+    pub fn transform(&self, transform: &Transform) -> Mesh {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|p| transform.apply_point(*p))
+            .collect();
+        let triangles = if transform.determinant() < 0.0 {
+            self.triangles
+                .iter()
+                .map(|t| {
+                    Triangle::new(t.v1, t.v3, t.v2)
+                        .with_properties(t.pid, t.properties().map(|[a, b, c]| [a, c, b]))
+                })
+                .collect()
+        } else {
+            self.triangles.clone()
+        };
+        Mesh {
+            beamlattice: self.beamlattice.clone(),
+            ..Mesh::new(vertices, triangles)
+        }
+    }
+
+    // The mesh with its beam lattice turned into triangles, see BeamLattice::tessellate. When
+    // the mesh's own triangles are closed the lattice is merged with them, as long as that
+    // gives a closed surface again. Otherwise it is added as it is.
+    pub fn tessellate_lattice(&self, segments: usize) -> Mesh {
+        let mut mesh = Mesh::new(self.vertices.clone(), self.triangles.clone());
+        let lattice = match self.beamlattice.as_ref() {
+            Some(lattice) => lattice.tessellate(&self.vertices, segments),
+            None => return mesh,
+        };
+        if !mesh.triangles.is_empty()
+            && !lattice.triangles.is_empty()
+            && open_edges(&mesh.triangles).is_empty()
+        {
+            let merged = union(&mesh, &lattice);
+            if open_edges(&merged.mesh.triangles).is_empty() {
+                let mut triangles = merged.mesh.triangles;
+                for (triangle, p) in triangles.iter_mut().zip(merged.provenance.iter()) {
+                    let source = match p.operand {
+                        Operand::First => &mesh.triangles[p.triangle],
+                        Operand::Second => &lattice.triangles[p.triangle],
+                    };
+                    *triangle = triangle
+                        .clone()
+                        .with_properties(source.pid, source.properties());
+                }
+                return Mesh::new(merged.mesh.vertices, triangles);
+            }
+        }
+        mesh.append(&lattice);
+        mesh
+    }
+
+    // Adds the triangles of another mesh, and their vertices
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.vertices.len();
+        self.vertices.extend(other.vertices.iter().copied());
+        self.triangles.extend(other.triangles.iter().map(|t| {
+            Triangle::new(t.v1 + offset, t.v2 + offset, t.v3 + offset)
+                .with_properties(t.pid, t.properties())
+        }));
+    }
+
+    // A new mesh made of the given triangles only, with unused vertices dropped
+    pub fn submesh(&self, triangles: &[usize]) -> Mesh {
+        let mut remap: HashMap<usize, usize> = HashMap::new();
+        let mut mesh = Mesh::default();
+
+        for &t in triangles.iter() {
+            let triangle = &self.triangles[t];
+            let mut indices = [0; 3];
+            for (k, v) in triangle.indices().into_iter().enumerate() {
+                indices[k] = *remap.entry(v).or_insert_with(|| {
+                    mesh.vertices.push(self.vertices[v]);
+                    mesh.vertices.len() - 1
+                });
+            }
+            mesh.triangles.push(
+                Triangle::new(indices[0], indices[1], indices[2])
+                    .with_properties(triangle.pid, triangle.properties()),
+            );
+        }
+        mesh
+    }
+
+    // Splits the mesh into connected pieces. Triangles are connected if they share a vertex.
+    pub fn components(&self) -> Vec<Mesh> {
+        connected_triangles(self.vertices.len(), &self.triangles)
+            .iter()
+            .map(|g| self.submesh(g))
+            .collect()
+    }
+}
+
+// The triangles of each connected piece of a mesh with `vertices` vertices, as in
+// `Mesh::components`, in the order of the first triangle of each
+pub(crate) fn connected_triangles(vertices: usize, triangles: &[Triangle]) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..vertices).collect();
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for triangle in triangles.iter() {
+        let a = find(&mut parent, triangle.v1);
+        for v in [triangle.v2, triangle.v3] {
+            let b = find(&mut parent, v);
+            parent[b] = a;
+        }
+    }
+
+    // Group triangles by the root of their first vertex, keeping first-seen order
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of: HashMap<usize, usize> = HashMap::new();
+    for (t, triangle) in triangles.iter().enumerate() {
+        let root = find(&mut parent, triangle.v1);
+        let g = *group_of.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[g].push(t);
+    }
+    groups
+}
+
 #[test]
 fn test_normalize() {
     use crate::load::load_model;
@@ -106,7 +335,6 @@ fn test_normalize() {
     // for point in model.objects[0].mesh.vertices.iter() {
     //     println!("{}", point);
     // }
-    assert!(true)
 }
 
 #[test]
@@ -118,3 +346,15 @@ fn test_centroid() {
     println!("Calculated Centroid: {}", cent);
     assert_eq!(cent, Point::zero())
 }
+
+#[test]
+fn test_volume_and_components() {
+    use crate::load::load_model;
+
+    // 2x2x2 cube
+    let model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mesh = &model.objects[0].mesh;
+    assert!((mesh.volume() - 8.0).abs() < 1e-9);
+    assert!((mesh.surface_area() - 24.0).abs() < 1e-9);
+    assert_eq!(mesh.components().len(), 1);
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+// Names the 3MF core spec defines for model metadata
+pub const TITLE: &str = "Title";
+pub const DESIGNER: &str = "Designer";
+pub const DESCRIPTION: &str = "Description";
+pub const COPYRIGHT: &str = "Copyright";
+pub const LICENSE_TERMS: &str = "LicenseTerms";
+pub const RATING: &str = "Rating";
+pub const CREATION_DATE: &str = "CreationDate";
+pub const MODIFICATION_DATE: &str = "ModificationDate";
+pub const APPLICATION: &str = "Application";
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MetadataValue {
+    pub value: String,
+    // Whether consumers should keep the entry even when they change the model
+    pub preserve: bool,
+    // XML schema type of the value, e.g. "xs:string"
+    pub dtype: Option<String>,
+}
+
+impl MetadataValue {
+    pub fn new(value: &str) -> Self {
+        MetadataValue {
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// A <metadata> element as it appears in the file
+#[derive(Debug, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    preserve: Option<String>,
+    #[serde(rename = "type", default)]
+    dtype: Option<String>,
+    #[serde(rename = "$value", default)]
+    value: String,
+}
+
+// Metadata of a model or an object, by name
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Metadata {
+    entries: BTreeMap<String, MetadataValue>,
+}
+
+impl Metadata {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(|e| e.value.as_str())
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&MetadataValue> {
+        self.entries.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, value: MetadataValue) -> Option<MetadataValue> {
+        self.entries.insert(name.to_string(), value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<MetadataValue> {
+        self.entries.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetadataValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.get(TITLE)
+    }
+
+    pub fn designer(&self) -> Option<&str> {
+        self.get(DESIGNER)
+    }
+}
+
+impl<'de> Deserialize<'de> for Metadata {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let entries = Vec::<Entry>::deserialize(deserializer)?;
+        let mut metadata = Metadata::default();
+        // Names are unique in a valid file. Otherwise the last one wins.
+        for entry in entries {
+            let preserve = matches!(entry.preserve.as_deref(), Some("1" | "true"));
+            metadata.insert(
+                &entry.name,
+                MetadataValue {
+                    value: entry.value,
+                    preserve,
+                    dtype: entry.dtype,
+                },
+            );
+        }
+        Ok(metadata)
+    }
+}
+
+#[test]
+fn test_parse_metadata() {
+    use crate::threemf::Model;
+
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<model xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02" unit="millimeter">
+    <metadata name="Title">Bracket &amp; plate</metadata>
+    <metadata name="Designer" preserve="1" type="xs:string">J. Smith</metadata>
+    <resources>
+        <object id="1" name="Body1" type="model">
+            <metadatagroup>
+                <metadata name="PartNumber">1234-A</metadata>
+            </metadatagroup>
+            <mesh>
+                <vertices>
+                    <vertex x="0" y="0" z="0" />
+                    <vertex x="1" y="0" z="0" />
+                    <vertex x="0" y="1" z="0" />
+                </vertices>
+                <triangles><triangle v1="0" v2="1" v3="2" /></triangles>
+            </mesh>
+        </object>
+        <object id="2" name="Body2" type="model">
+            <mesh>
+                <vertices>
+                    <vertex x="0" y="0" z="0" />
+                    <vertex x="1" y="0" z="0" />
+                    <vertex x="0" y="1" z="0" />
+                </vertices>
+                <triangles><triangle v1="0" v2="1" v3="2" /></triangles>
+            </mesh>
+        </object>
+    </resources>
+</model>"#;
+    let model: Model = quick_xml::de::from_str(xml).unwrap();
+
+    assert_eq!(model.metadata.len(), 2);
+    assert_eq!(model.metadata.title(), Some("Bracket & plate"));
+    assert_eq!(model.metadata.designer(), Some("J. Smith"));
+    let designer = model.metadata.entry(DESIGNER).unwrap();
+    assert!(designer.preserve);
+    assert_eq!(designer.dtype.as_deref(), Some("xs:string"));
+
+    assert_eq!(model.objects[0].metadata.get("PartNumber"), Some("1234-A"));
+    assert!(model.objects[1].metadata.is_empty());
+}
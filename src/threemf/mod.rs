@@ -1,10 +1,24 @@
+pub use beamlattice::{Ball, BallMode, Beam, BeamLattice, BeamSet, CapMode};
+pub use build::{Build, Component, Item};
+pub use material::{BaseMaterial, BaseMaterials, Color, ColorGroup};
 pub use mesh::Mesh;
 pub use mesh::NormalizeLocation;
+pub use metadata::{Metadata, MetadataValue};
 pub use model::Model;
-pub use object::Object;
+pub use object::{Object, ObjectType};
+pub use slice::{MeshResolution, Slice, SliceRef, SliceStack};
+pub use validate::{validate, validate_model, Finding, Severity};
 
+pub mod beamlattice;
+pub mod build;
+pub mod material;
 pub mod mesh;
+pub mod metadata;
 pub mod model;
 pub mod object;
+pub mod package;
+pub mod slice;
 mod test;
+pub mod validate;
+pub mod write;
 pub mod xml_parse;
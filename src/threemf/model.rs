@@ -1,20 +1,65 @@
-use std::io::{BufReader, Cursor, Read, Seek};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek};
 use std::path::Path;
 
-use quick_xml::de::from_reader;
 use serde::Deserialize;
 use zip::ZipArchive;
 
 use crate::common::Unit;
 use crate::error::Error;
-use crate::threemf::xml_parse::Resources;
-use crate::threemf::Object;
+use crate::geometry::{Transform, Triangle};
+use crate::threemf::material::{BaseMaterials, Color, ColorGroup};
+use crate::threemf::package::{
+    directory, resolve, AttachedTo, Attachment, Package, Relationships, MODEL_CONTENT_TYPE,
+    MODEL_RELATIONSHIP, THUMBNAIL_RELATIONSHIP,
+};
+use crate::threemf::xml_parse::{parse_model, ModelFile, Resource};
+use crate::threemf::{Build, Item, Mesh, Metadata, Object, ObjectType, SliceStack};
 
-#[derive(Debug, Deserialize, PartialEq)]
+// Components nested deeper than this are taken to be a loop
+const MAX_COMPONENT_DEPTH: usize = 64;
+
+// Gives the id to use for a property group, from its id in the part it is in (None for the
+// model's own)
+type GroupMap<'a> = dyn FnMut(Option<&str>, usize) -> usize + 'a;
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(from = "ModelFile")]
 pub struct Model {
     pub unit: Unit,
-    #[serde(rename = "resources", with = "Resources", default)]
+    pub metadata: Metadata,
     pub objects: Vec<Object>,
+    pub materials: Vec<BaseMaterials>,
+    pub colors: Vec<ColorGroup>,
+    // Pre-sliced objects (slice extension)
+    pub slices: Vec<SliceStack>,
+    pub build: Build,
+    // The package's other model parts by name, when objects are spread over several of them
+    // (production extension)
+    pub parts: BTreeMap<String, Model>,
+    // Other parts of the package, like thumbnails and textures
+    pub attachments: Vec<Attachment>,
+}
+
+impl From<ModelFile> for Model {
+    fn from(file: ModelFile) -> Self {
+        let mut model = Model {
+            unit: file.unit,
+            metadata: file.metadata,
+            build: file.build,
+            ..Model::empty()
+        };
+        for resource in file.resources {
+            match resource {
+                Resource::Object(object) => model.objects.push(*object),
+                Resource::BaseMaterials(materials) => model.materials.push(materials),
+                Resource::ColorGroup(colors) => model.colors.push(colors),
+                Resource::SliceStack(stack) => model.slices.push(stack),
+                Resource::Unsupported => {}
+            }
+        }
+        model
+    }
 }
 
 impl Model {
@@ -30,12 +75,200 @@ impl Model {
 
     #[allow(dead_code)]
     pub fn num_vertices(&self) -> usize {
-        return self.objects.iter().map(|d| d.mesh.vertices.len()).sum();
+        self.objects.iter().map(|d| d.mesh.vertices.len()).sum()
     }
 
     #[allow(dead_code)]
     pub fn num_triangles(&self) -> usize {
-        return self.objects.iter().map(|d| d.mesh.triangles.len()).sum();
+        self.objects.iter().map(|d| d.mesh.triangles.len()).sum()
+    }
+
+    // The package's thumbnail, if it has one
+    pub fn thumbnail(&self) -> Option<&Attachment> {
+        self.attachments.iter().find(|a| {
+            a.attached_to == AttachedTo::Package && a.relationship == THUMBNAIL_RELATIONSHIP
+        })
+    }
+
+    // The model part objects in `path` are in. Paths that aren't one of the other parts are
+    // taken to be this one.
+    pub fn part(&self, path: Option<&str>) -> &Model {
+        path.and_then(|p| self.parts.get(p)).unwrap_or(self)
+    }
+
+    pub fn object(&self, path: Option<&str>, id: usize) -> Option<&Object> {
+        self.part(path).objects.iter().find(|o| o.id == id)
+    }
+
+    // An object as a single mesh, with its components in place. None if it, or any object it
+    // is made of, can't be found.
+    pub fn object_mesh(&self, path: Option<&str>, id: usize) -> Option<Mesh> {
+        let mut mesh = Mesh::default();
+        self.add_object(path, id, &Transform::identity(), 0, &mut mesh, &mut None)?;
+        Some(mesh)
+    }
+
+    // The mesh of a build item, where it is placed on the build plate
+    pub fn item_mesh(&self, item: &Item) -> Option<Mesh> {
+        let mut mesh = Mesh::default();
+        self.add_object(
+            item.path.as_deref(),
+            item.objectid,
+            &item.transform,
+            0,
+            &mut mesh,
+            &mut None,
+        )?;
+        Some(mesh)
+    }
+
+    // The mesh of a build item like `item_mesh`, with the property of every triangle spelled
+    // out: triangles without one take the default of the object they are in. Each property
+    // group id is passed through `group` along with the part the group is in (None for this
+    // one), so that groups from several parts can be brought together.
+    pub fn item_mesh_with(&self, item: &Item, group: &mut GroupMap) -> Option<Mesh> {
+        let mut mesh = Mesh::default();
+        self.add_object(
+            item.path.as_deref(),
+            item.objectid,
+            &item.transform,
+            0,
+            &mut mesh,
+            &mut Some(group),
+        )?;
+        Some(mesh)
+    }
+
+    fn add_object(
+        &self,
+        path: Option<&str>,
+        id: usize,
+        transform: &Transform,
+        depth: usize,
+        mesh: &mut Mesh,
+        group: &mut Option<&mut GroupMap>,
+    ) -> Option<()> {
+        // Objects can't contain themselves in a valid file. This stops one that does.
+        if depth > MAX_COMPONENT_DEPTH {
+            return None;
+        }
+        let object = self.object(path, id)?;
+        let mut placed = object.mesh.transform(transform);
+        if let Some(group) = group.as_deref_mut() {
+            let part = path.filter(|p| self.parts.contains_key(*p));
+            for t in placed.triangles.iter_mut() {
+                let (pid, properties) = match t.pid {
+                    Some(pid) => (Some(pid), t.properties()),
+                    None => (object.pid, object.pindex.map(|i| [i; 3])),
+                };
+                *t = Triangle::new(t.v1, t.v2, t.v3)
+                    .with_properties(pid.map(|pid| group(part, pid)), properties);
+            }
+        }
+        mesh.append(&placed);
+        for component in object.components.iter() {
+            // Components without a path are in the same part as the object
+            self.add_object(
+                component.path.as_deref().or(path),
+                component.objectid,
+                &component.transform.then(transform),
+                depth + 1,
+                mesh,
+                group,
+            )?;
+        }
+        Some(())
+    }
+
+    // Objects of the given types, e.g. ObjectType::PARTS for those that are printed as they are
+    pub fn objects_of<'a>(&'a self, types: &'a [ObjectType]) -> impl Iterator<Item = &'a Object> {
+        self.objects.iter().filter(|o| types.contains(&o.otype))
+    }
+
+    // Volume of the build items whose objects are of the given types, where they are placed
+    pub fn volume(&self, types: &[ObjectType]) -> f64 {
+        self.build
+            .items
+            .iter()
+            .filter(|item| {
+                self.object(item.path.as_deref(), item.objectid)
+                    .is_some_and(|o| types.contains(&o.otype))
+            })
+            .filter_map(|item| self.item_mesh(item))
+            .map(|mesh| mesh.volume())
+            .sum()
+    }
+
+    pub fn slice_stack(&self, id: usize) -> Option<&SliceStack> {
+        self.slices.iter().find(|s| s.id == id)
+    }
+
+    // Slices every object with a mesh into layers of the given height, replacing the slices it
+    // had. New stacks take ids after those of the other resources. Nothing changes if the
+    // height isn't positive and finite.
+    pub fn slice_objects(&mut self, layer_height: f64) -> Result<(), Error> {
+        if !(layer_height > 0.0 && layer_height.is_finite()) {
+            return Err(Error::InvalidLayerHeight(layer_height));
+        }
+        let mut next_id = self.next_resource_id();
+        for object in self.objects.iter_mut() {
+            if let Some(id) = object.slicestackid.take() {
+                self.slices.retain(|s| s.id != id);
+            }
+            if object.mesh.triangles.is_empty() {
+                continue;
+            }
+            self.slices
+                .push(SliceStack::from_mesh(next_id, &object.mesh, layer_height)?);
+            object.slicestackid = Some(next_id);
+            object.meshresolution = None;
+            next_id += 1;
+        }
+        Ok(())
+    }
+
+    // Turns the beam lattices of every object, in this part and the others, into triangles, so
+    // that cutting and analysis see them. The beams and balls are merged into one surface, and
+    // with the object's own triangles when those are closed (see `Mesh::tessellate_lattice`).
+    pub fn tessellate_lattices(&mut self, segments: usize) {
+        for object in self.objects.iter_mut() {
+            if object.mesh.beamlattice.is_some() {
+                object.mesh = object.mesh.tessellate_lattice(segments);
+            }
+        }
+        for part in self.parts.values_mut() {
+            part.tessellate_lattices(segments);
+        }
+    }
+
+    // An id no resource of this part has yet
+    pub fn next_resource_id(&self) -> usize {
+        let ids = self.objects.iter().map(|o| o.id);
+        let ids = ids.chain(self.materials.iter().map(|m| m.id));
+        let ids = ids.chain(self.colors.iter().map(|c| c.id));
+        let ids = ids.chain(self.slices.iter().map(|s| s.id));
+        ids.max().map_or(1, |id| id + 1)
+    }
+
+    // Colour of a property: a colour from a colour group, or a base material's display colour
+    pub fn color(&self, pid: usize, index: usize) -> Option<Color> {
+        if let Some(group) = self.colors.iter().find(|g| g.id == pid) {
+            return group.colors.get(index).copied();
+        }
+        let group = self.materials.iter().find(|g| g.id == pid)?;
+        group.materials.get(index).map(|m| m.color)
+    }
+
+    // Colour of each corner of one of an object's triangles, falling back on the object's
+    // default property
+    pub fn triangle_colors(&self, object: &Object, triangle: &Triangle) -> Option<[Color; 3]> {
+        let pid = triangle.pid.or(object.pid)?;
+        let properties = match triangle.properties() {
+            Some(properties) => properties,
+            None => [object.pindex?; 3],
+        };
+        let [a, b, c] = properties.map(|index| self.color(pid, index));
+        Some([a?, b?, c?])
     }
 }
 
@@ -45,32 +278,86 @@ impl Model {
         Model {
             objects: Vec::new(),
             unit: Unit::Unknown,
+            metadata: Metadata::default(),
+            materials: Vec::new(),
+            colors: Vec::new(),
+            slices: Vec::new(),
+            build: Build::default(),
+            parts: BTreeMap::new(),
+            attachments: Vec::new(),
         }
     }
 
     #[allow(dead_code)]
-    fn new<R: Read + Seek>(mut archive: ZipArchive<R>) -> Result<Self, Error> {
-        // Go through the files within the archive and grab the first .model
-        // TODO: There is a better way to do this
-        let mut model_file_name: String = String::new();
-        for name in archive.file_names() {
-            if name.contains(".model") {
-                model_file_name = String::from(name);
-            }
+    fn new<R: Read + Seek>(archive: ZipArchive<R>) -> Result<Self, Error> {
+        // The package's relationships point to the root model part
+        let mut package = Package::new(archive)?;
+        let root = package.root()?;
+        let mut model = Self::read_part(&mut package, &root)?;
+
+        // Then the other model parts (production extension). The root model should have
+        // relationships to them, but any it refers to are read.
+        let relationships = package.read_relationships(&root)?;
+        let base = directory(&root);
+        for path in model.other_parts(&root, &relationships) {
+            let part = Self::read_part(&mut package, &path)?;
+            model.parts.insert(path, part);
         }
 
-        // If we didn't find a model, return an error
-        if model_file_name.is_empty() {
-            return Err(Error::TooManyModels);
+        // Anything else attached to the package or to the root model
+        let attached = [
+            (package.relationships.clone(), AttachedTo::Package, "/"),
+            (relationships, AttachedTo::Model, base),
+        ];
+        for (relationships, attached_to, base) in attached {
+            for relationship in relationships.relationships.iter() {
+                if relationship.rtype == MODEL_RELATIONSHIP {
+                    continue;
+                }
+                // A broken attachment doesn't stop the model being read. It is left out, and
+                // validate reports it.
+                match package.read_attachment(relationship, attached_to, base) {
+                    Ok(attachment) => model.attachments.push(attachment),
+                    Err(Error::MissingPart(_)) | Err(Error::InvalidContentType(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
         }
 
-        // Grab our zip file
-        let zip_file = archive.by_name(model_file_name.as_str())?;
+        Ok(model)
+    }
 
-        // Parse the zipfile (using Serde and quick_xml)
-        let model: Model = from_reader(BufReader::new(zip_file))?;
+    // Names of the other model parts the root model, at `root`, has relationships to or refers to:
+    // from build items, components and slice stack references
+    pub(crate) fn other_parts(&self, root: &str, relationships: &Relationships) -> Vec<String> {
+        let base = directory(root);
+        let mut paths: Vec<String> = relationships
+            .of_type(MODEL_RELATIONSHIP)
+            .map(|r| resolve(base, &r.target))
+            .chain(self.build.items.iter().filter_map(|i| i.path.clone()))
+            .chain(
+                self.objects
+                    .iter()
+                    .flat_map(|o| o.components.iter().filter_map(|c| c.path.clone())),
+            )
+            .chain(
+                self.slices
+                    .iter()
+                    .flat_map(|s| s.refs.iter().map(|r| r.slicepath.clone())),
+            )
+            .filter(|path| path != root)
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
 
-        Ok(model)
+    pub(crate) fn read_part<R: Read + Seek>(
+        package: &mut Package<R>,
+        part: &str,
+    ) -> Result<Self, Error> {
+        package.check_content_type(part, MODEL_CONTENT_TYPE)?;
+        parse_model(&package.read_bytes(part)?).map_err(|e| e.in_part(part))
     }
 
     #[allow(dead_code)]
@@ -97,7 +384,14 @@ fn create_model() {
     assert_eq!(
         Model {
             objects: Vec::new(),
-            unit: Unit::Unknown
+            unit: Unit::Unknown,
+            metadata: Metadata::default(),
+            materials: Vec::new(),
+            colors: Vec::new(),
+            slices: Vec::new(),
+            build: Build::default(),
+            parts: BTreeMap::new(),
+            attachments: Vec::new(),
         },
         model
     )
@@ -1,11 +1,83 @@
-use crate::threemf::Mesh;
+use crate::threemf::build::Component;
+use crate::threemf::xml_parse::{Components, MetadataGroup};
+use crate::threemf::{Mesh, MeshResolution, Metadata};
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, PartialEq)]
+// What an object is for. Supports are only there to help print the rest, and other objects
+// aren't printed at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectType {
+    #[default]
+    Model,
+    Support,
+    SolidSupport,
+    Surface,
+    Other,
+}
+
+impl ObjectType {
+    // The types that make up what is being printed, which cuts and volumes are of unless asked
+    // otherwise. Surfaces needn't be closed, so they are left out and have to be asked for.
+    pub const PARTS: &'static [ObjectType] = &[ObjectType::Model];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectType::Model => "model",
+            ObjectType::Support => "support",
+            ObjectType::SolidSupport => "solidsupport",
+            ObjectType::Surface => "surface",
+            ObjectType::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Object {
     pub id: usize,
     pub name: String,
     #[serde(rename = "type", default)]
-    pub otype: String,
+    pub otype: ObjectType,
+    // Production extension
+    #[serde(rename = "p:UUID", default)]
+    pub uuid: Option<String>,
+    // Default property of the object's triangles (3MF materials extension), as a property group
+    // and an index into it
+    #[serde(default)]
+    pub pid: Option<usize>,
+    #[serde(default)]
+    pub pindex: Option<usize>,
+    // Slice extension
+    #[serde(rename = "s:slicestackid", alias = "slicestackid", default)]
+    pub slicestackid: Option<usize>,
+    #[serde(rename = "s:meshresolution", alias = "meshresolution", default)]
+    pub meshresolution: Option<MeshResolution>,
+    #[serde(rename = "metadatagroup", with = "MetadataGroup", default)]
+    pub metadata: Metadata,
+    // An object is made of a mesh, or of other objects
+    #[serde(default)]
     pub mesh: Mesh,
+    #[serde(with = "Components", default)]
+    pub components: Vec<Component>,
+}
+
+impl Object {
+    // An object with a different id and mesh that keeps the rest of this one's description,
+    // e.g. for a piece cut out of it
+    pub fn piece(&self, id: usize, mesh: Mesh) -> Object {
+        Object {
+            id,
+            name: self.name.clone(),
+            otype: self.otype,
+            uuid: None,
+            pid: self.pid,
+            pindex: self.pindex,
+            // The slices are of the whole object
+            slicestackid: None,
+            meshresolution: None,
+            metadata: self.metadata.clone(),
+            mesh,
+            components: Vec::new(),
+        }
+    }
 }
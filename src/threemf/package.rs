@@ -0,0 +1,431 @@
+use std::io::{BufReader, Read, Seek};
+
+use quick_xml::de::from_reader;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::error::Error;
+
+// Parts of the zip package a 3MF file is (Open Packaging Conventions)
+
+// Relationship type of a 3D model part
+pub const MODEL_RELATIONSHIP: &str = "http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel";
+pub const THUMBNAIL_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships/metadata/thumbnail";
+
+pub const MODEL_CONTENT_TYPE: &str = "application/vnd.ms-package.3dmanufacturing-3dmodel+xml";
+pub const RELATIONSHIPS_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-package.relationships+xml";
+
+// Where the content types of the parts are kept
+pub const CONTENT_TYPES: &str = "/[Content_Types].xml";
+// Where the package's own relationships are kept
+pub const PACKAGE_RELATIONSHIPS: &str = "/_rels/.rels";
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct Relationship {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Type")]
+    pub rtype: String,
+    // Part name, e.g. /3D/3dmodel.model, or relative to the part the relationship is from
+    #[serde(rename = "Target")]
+    pub target: String,
+}
+
+// A .rels part
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct Relationships {
+    #[serde(rename = "Relationship", default)]
+    pub relationships: Vec<Relationship>,
+}
+
+impl Relationships {
+    pub fn of_type<'a>(&'a self, rtype: &'a str) -> impl Iterator<Item = &'a Relationship> {
+        self.relationships.iter().filter(move |r| r.rtype == rtype)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct DefaultType {
+    #[serde(rename = "Extension")]
+    pub extension: String,
+    #[serde(rename = "ContentType")]
+    pub content_type: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct OverrideType {
+    #[serde(rename = "PartName")]
+    pub part: String,
+    #[serde(rename = "ContentType")]
+    pub content_type: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+enum TypeEntry {
+    Default(DefaultType),
+    Override(OverrideType),
+}
+
+// The [Content_Types].xml part: content types by extension, or for single parts
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ContentTypes {
+    pub defaults: Vec<DefaultType>,
+    pub overrides: Vec<OverrideType>,
+}
+
+impl ContentTypes {
+    // Part names and extensions are compared without case
+    pub fn content_type(&self, part: &str) -> Option<&str> {
+        if let Some(o) = self
+            .overrides
+            .iter()
+            .find(|o| o.part.eq_ignore_ascii_case(part))
+        {
+            return Some(o.content_type.as_str());
+        }
+        let name = &part[part.rfind('/').map_or(0, |k| k + 1)..];
+        let extension = &name[name.rfind('.')? + 1..];
+        self.defaults
+            .iter()
+            .find(|d| d.extension.eq_ignore_ascii_case(extension))
+            .map(|d| d.content_type.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentTypes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Types {
+            #[serde(rename = "$value", default)]
+            entries: Vec<TypeEntry>,
+        }
+
+        let mut types = ContentTypes::default();
+        for entry in Types::deserialize(deserializer)?.entries {
+            match entry {
+                TypeEntry::Default(d) => types.defaults.push(d),
+                TypeEntry::Override(o) => types.overrides.push(o),
+            }
+        }
+        Ok(types)
+    }
+}
+
+// What an attachment is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachedTo {
+    Package,
+    // The root model part
+    Model,
+}
+
+// A part of the package other than the models, e.g. a thumbnail or a texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub part: String,
+    pub content_type: String,
+    // Type of the relationship to the part
+    pub relationship: String,
+    pub attached_to: AttachedTo,
+    pub data: Vec<u8>,
+}
+
+// An open 3MF package
+pub struct Package<R> {
+    archive: ZipArchive<R>,
+    pub content_types: ContentTypes,
+    pub relationships: Relationships,
+}
+
+impl<R: Read + Seek> Package<R> {
+    pub fn new(archive: ZipArchive<R>) -> Result<Self, Error> {
+        let mut package = Package {
+            archive,
+            content_types: ContentTypes::default(),
+            relationships: Relationships::default(),
+        };
+        package.content_types = package.read_xml(CONTENT_TYPES)?;
+        package.relationships = package.read_relationships("/")?;
+        Ok(package)
+    }
+
+    // The 3D model the package is for. There must be exactly one.
+    pub fn root(&self) -> Result<String, Error> {
+        let mut models = self.relationships.of_type(MODEL_RELATIONSHIP);
+        let root = models.next().ok_or(Error::EmptyModel)?;
+        if models.next().is_some() {
+            return Err(Error::TooManyModels);
+        }
+        Ok(resolve("/", &root.target))
+    }
+
+    pub fn has_part(&self, part: &str) -> bool {
+        self.archive.file_names().any(|n| n == archive_name(part))
+    }
+
+    // Names of all the parts in the package, except for directories
+    pub fn part_names(&self) -> Vec<String> {
+        self.archive
+            .file_names()
+            .filter(|n| !n.ends_with('/'))
+            .map(|n| format!("/{}", n))
+            .collect()
+    }
+
+    // Relationships from a part, or from the package for "/". Parts don't need any.
+    pub fn read_relationships(&mut self, part: &str) -> Result<Relationships, Error> {
+        let name = relationships_name(part);
+        if !self.has_part(&name) {
+            if part == "/" {
+                return Err(Error::MissingPart(name));
+            }
+            return Ok(Relationships::default());
+        }
+        self.check_content_type(&name, RELATIONSHIPS_CONTENT_TYPE)?;
+        self.read_xml(&name)
+    }
+
+    pub fn check_content_type(&self, part: &str, expected: &str) -> Result<(), Error> {
+        match self.content_types.content_type(part) {
+            Some(found) if found == expected => Ok(()),
+            _ => Err(Error::InvalidContentType(part.to_string())),
+        }
+    }
+
+    pub fn read_xml<T: DeserializeOwned>(&mut self, part: &str) -> Result<T, Error> {
+        let file = self.open(part)?;
+        from_reader(BufReader::new(file)).map_err(|e| Error::from(e).in_part(part))
+    }
+
+    pub fn read_bytes(&mut self, part: &str) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.open(part)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // Reads a part the package or the root model has a relationship to
+    pub fn read_attachment(
+        &mut self,
+        relationship: &Relationship,
+        attached_to: AttachedTo,
+        base: &str,
+    ) -> Result<Attachment, Error> {
+        let part = resolve(base, &relationship.target);
+        let content_type = self
+            .content_types
+            .content_type(&part)
+            .ok_or_else(|| Error::InvalidContentType(part.clone()))?
+            .to_string();
+        Ok(Attachment {
+            data: self.read_bytes(&part)?,
+            part,
+            content_type,
+            relationship: relationship.rtype.clone(),
+            attached_to,
+        })
+    }
+
+    fn open(&mut self, part: &str) -> Result<zip::read::ZipFile<'_>, Error> {
+        match self.archive.by_name(archive_name(part)) {
+            Ok(file) => Ok(file),
+            Err(ZipError::FileNotFound) => Err(Error::MissingPart(part.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// Name of a part in the zip archive. Part names start at the package's root, archive names
+// leave the slash out.
+pub fn archive_name(part: &str) -> &str {
+    part.trim_start_matches('/')
+}
+
+// Where the relationships of a part are kept, e.g. /3D/_rels/3dmodel.model.rels. Those of the
+// package itself are the relationships of "/".
+pub fn relationships_name(part: &str) -> String {
+    let k = part.rfind('/').unwrap_or(0);
+    format!("{}/_rels/{}.rels", &part[..k], &part[k + 1..])
+}
+
+// The directory of a part, for resolving relative targets of its relationships
+pub fn directory(part: &str) -> &str {
+    &part[..part.rfind('/').map_or(0, |k| k + 1)]
+}
+
+// A relationship target as a part name. Targets without a leading slash are relative to `base`.
+pub fn resolve(base: &str, target: &str) -> String {
+    if target.starts_with('/') {
+        return target.to_string();
+    }
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+#[test]
+fn test_package() {
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let rels = |targets: &[(&str, &str)]| {
+        let entries: Vec<String> = targets
+            .iter()
+            .enumerate()
+            .map(|(k, (rtype, target))| {
+                format!(
+                    r#"<Relationship Type="{}" Target="{}" Id="rel{}"/>"#,
+                    rtype, target, k
+                )
+            })
+            .collect();
+        format!(
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}</Relationships>"#,
+            entries.concat()
+        )
+    };
+    let types = |model: &str| {
+        format!(
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="{}"/><Default Extension="model" ContentType="{}"/><Override PartName="/Metadata/thumbnail.PNG" ContentType="image/png"/></Types>"#,
+            RELATIONSHIPS_CONTENT_TYPE, model
+        )
+    };
+    let cube = {
+        let mut data = Vec::new();
+        let mut zip =
+            ZipArchive::new(std::fs::File::open("data/centered_cube_2x2x2.3mf").unwrap()).unwrap();
+        zip.by_name("3D/3dmodel.model")
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    };
+    let archive = |files: &[(&str, &[u8])]| {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    };
+    let package = |files: &[(&str, &[u8])]| crate::threemf::Model::from_raw_data(&archive(files));
+
+    // The model is found through the relationships, relative targets and all
+    let model = package(&[
+        ("[Content_Types].xml", types(MODEL_CONTENT_TYPE).as_bytes()),
+        (
+            "_rels/.rels",
+            rels(&[
+                (MODEL_RELATIONSHIP, "Models/cube.model"),
+                (THUMBNAIL_RELATIONSHIP, "/Metadata/thumbnail.png"),
+            ])
+            .as_bytes(),
+        ),
+        ("Models/cube.model", &cube),
+        ("Metadata/thumbnail.png", b"not really a png"),
+    ])
+    .unwrap();
+    assert_eq!(model.num_objects(), 1);
+    let thumbnail = model.thumbnail().unwrap();
+    assert_eq!(thumbnail.content_type, "image/png");
+    assert_eq!(thumbnail.data, b"not really a png");
+    // and written back
+    let written = crate::threemf::Model::from_raw_data(&model.to_raw_data().unwrap()).unwrap();
+    assert_eq!(written, model);
+
+    // Attachments that are missing, or have no content type, are left out but don't stop the
+    // model being read
+    let (content_types, relationships) = (
+        types(MODEL_CONTENT_TYPE),
+        rels(&[
+            (MODEL_RELATIONSHIP, "/3D/3dmodel.model"),
+            (THUMBNAIL_RELATIONSHIP, "/Metadata/thumbnail.png"),
+            ("http://example.com/notes", "/Metadata/notes.txt"),
+        ]),
+    );
+    let files: &[(&str, &[u8])] = &[
+        ("[Content_Types].xml", content_types.as_bytes()),
+        ("_rels/.rels", relationships.as_bytes()),
+        ("3D/3dmodel.model", &cube),
+        ("Metadata/notes.txt", b"no content type"),
+    ];
+    let model = package(files).unwrap();
+    assert!(model.attachments.is_empty());
+    let findings = crate::threemf::validate(&archive(files));
+    let errors: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("/Metadata/thumbnail.png, which is missing")));
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("/Metadata/notes.txt has no content type")));
+
+    let no_model = package(&[
+        ("[Content_Types].xml", types(MODEL_CONTENT_TYPE).as_bytes()),
+        ("_rels/.rels", rels(&[]).as_bytes()),
+        ("3D/3dmodel.model", &cube),
+    ]);
+    assert!(matches!(no_model, Err(Error::EmptyModel)));
+
+    let two_models = package(&[
+        ("[Content_Types].xml", types(MODEL_CONTENT_TYPE).as_bytes()),
+        (
+            "_rels/.rels",
+            rels(&[
+                (MODEL_RELATIONSHIP, "/3D/a.model"),
+                (MODEL_RELATIONSHIP, "/3D/b.model"),
+            ])
+            .as_bytes(),
+        ),
+    ]);
+    assert!(matches!(two_models, Err(Error::TooManyModels)));
+
+    let missing = package(&[
+        ("[Content_Types].xml", types(MODEL_CONTENT_TYPE).as_bytes()),
+        (
+            "_rels/.rels",
+            rels(&[(MODEL_RELATIONSHIP, "/3D/3dmodel.model")]).as_bytes(),
+        ),
+    ]);
+    assert!(matches!(missing, Err(Error::MissingPart(p)) if p == "/3D/3dmodel.model"));
+
+    let no_types = package(&[
+        (
+            "_rels/.rels",
+            rels(&[(MODEL_RELATIONSHIP, "/3D/3dmodel.model")]).as_bytes(),
+        ),
+        ("3D/3dmodel.model", &cube),
+    ]);
+    assert!(matches!(no_types, Err(Error::MissingPart(p)) if p == CONTENT_TYPES));
+
+    let wrong_type = package(&[
+        ("[Content_Types].xml", types("text/xml").as_bytes()),
+        (
+            "_rels/.rels",
+            rels(&[(MODEL_RELATIONSHIP, "/3D/3dmodel.model")]).as_bytes(),
+        ),
+        ("3D/3dmodel.model", &cube),
+    ]);
+    assert!(matches!(wrong_type, Err(Error::InvalidContentType(p)) if p == "/3D/3dmodel.model"));
+
+    assert_eq!(resolve("/3D/", "../Metadata/a.png"), "/Metadata/a.png");
+    assert_eq!(relationships_name("/"), "/_rels/.rels");
+    assert_eq!(
+        relationships_name("/3D/3dmodel.model"),
+        "/3D/_rels/3dmodel.model.rels"
+    );
+}
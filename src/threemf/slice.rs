@@ -0,0 +1,278 @@
+use crate::cut::{cap_section, split_mesh};
+use crate::error::Error;
+use crate::geometry::{polygon, Plane, Point, Vector};
+use crate::threemf::Mesh;
+use serde::{Deserialize, Deserializer};
+
+// Resources from the 3MF slice extension: an object's outline at each layer, so that printers
+// don't need to slice its mesh again. Slices are in the object's coordinates, stacked upwards
+// from zbottom, and each one covers the layer from the previous slice's ztop to its own.
+
+// Generated slices are taken in the middle of their layer, away from the faces that often
+// lie on layer boundaries
+const SECTION_HEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Slice {
+    pub ztop: f64,
+    #[serde(
+        rename = "s:vertices",
+        alias = "vertices",
+        deserialize_with = "slice_vertices",
+        default
+    )]
+    pub vertices: Vec<[f64; 2]>,
+    // Indices into the vertices, from the polygon's start along each of its segments. Closed
+    // polygons end where they start.
+    #[serde(
+        rename = "s:polygon",
+        alias = "polygon",
+        deserialize_with = "polygons",
+        default
+    )]
+    pub polygons: Vec<Vec<usize>>,
+}
+
+impl Slice {
+    pub fn is_closed(&self) -> bool {
+        self.polygons
+            .iter()
+            .all(|p| p.len() > 1 && p.first() == p.last())
+    }
+
+    // Area enclosed by the polygons. Outer polygons are counter-clockwise and holes clockwise.
+    pub fn area(&self) -> f64 {
+        self.polygons
+            .iter()
+            .map(|p| polygon::signed_area(&self.vertices, p))
+            .sum::<f64>()
+            / 2.0
+    }
+}
+
+// Slices kept in another model part (production extension)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SliceRef {
+    pub slicestackid: usize,
+    pub slicepath: String,
+}
+
+// Whether an object's mesh is the one its slices were made from, or a rough stand-in for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeshResolution {
+    FullRes,
+    LowRes,
+}
+
+impl MeshResolution {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeshResolution::FullRes => "fullres",
+            MeshResolution::LowRes => "lowres",
+        }
+    }
+}
+
+// A <slicestack>. It holds either slices or references to other stacks.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SliceStack {
+    pub id: usize,
+    #[serde(default)]
+    pub zbottom: f64,
+    #[serde(rename = "s:slice", alias = "slice", default)]
+    pub slices: Vec<Slice>,
+    #[serde(rename = "s:sliceref", alias = "sliceref", default)]
+    pub refs: Vec<SliceRef>,
+}
+
+impl SliceStack {
+    // Slices a mesh into layers of the given height, from its lowest point up to past its
+    // highest. The height must be positive and finite.
+    pub fn from_mesh(id: usize, mesh: &Mesh, layer_height: f64) -> Result<SliceStack, Error> {
+        if !(layer_height > 0.0 && layer_height.is_finite()) {
+            return Err(Error::InvalidLayerHeight(layer_height));
+        }
+        let mut stack = SliceStack {
+            id,
+            zbottom: 0.0,
+            slices: Vec::new(),
+            refs: Vec::new(),
+        };
+        if mesh.triangles.is_empty() {
+            return Ok(stack);
+        }
+
+        let (min, max) = mesh.bounds();
+        stack.zbottom = min.z;
+        let layers = ((max.z - min.z) / layer_height).ceil().max(1.0) as usize;
+        for k in 0..layers {
+            let ztop = min.z + (k + 1) as f64 * layer_height;
+            let z = ztop - (1.0 - SECTION_HEIGHT) * layer_height;
+            stack.slices.push(slice_mesh(mesh, z, ztop));
+        }
+        Ok(stack)
+    }
+}
+
+// The outline of a mesh at height z, as the slice of a layer ending at ztop
+fn slice_mesh(mesh: &Mesh, z: f64, ztop: f64) -> Slice {
+    let plane = Plane::new(Vector::Z, Point::new(0.0, 0.0, z));
+    let split = split_mesh(mesh, &plane);
+    // The cap below the plane faces up, so its loops run counter-clockwise seen from above
+    let section = cap_section(&split, &split.below, plane);
+
+    let mut slice = Slice {
+        ztop,
+        vertices: section.points.iter().map(|p| [p.x, p.y]).collect(),
+        polygons: Vec::new(),
+    };
+    for ring in section.loops.iter() {
+        let mut polygon = ring.clone();
+        polygon.push(ring[0]);
+        slice.polygons.push(polygon);
+    }
+    slice
+}
+
+#[derive(Deserialize)]
+struct Vertex2 {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct Vertices2 {
+    #[serde(rename = "s:vertex", alias = "vertex", default)]
+    vertex: Vec<Vertex2>,
+}
+
+fn slice_vertices<'de, D>(deserializer: D) -> Result<Vec<[f64; 2]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let vertices = Vertices2::deserialize(deserializer)?;
+    Ok(vertices.vertex.into_iter().map(|v| [v.x, v.y]).collect())
+}
+
+#[derive(Deserialize)]
+struct Segment {
+    v2: usize,
+}
+
+#[derive(Deserialize)]
+struct PolygonElement {
+    startv: usize,
+    #[serde(rename = "s:segment", alias = "segment", default)]
+    segments: Vec<Segment>,
+}
+
+fn polygons<'de, D>(deserializer: D) -> Result<Vec<Vec<usize>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let elements = Vec::<PolygonElement>::deserialize(deserializer)?;
+    Ok(elements
+        .into_iter()
+        .map(|p| {
+            std::iter::once(p.startv)
+                .chain(p.segments.into_iter().map(|s| s.v2))
+                .collect()
+        })
+        .collect())
+}
+
+#[test]
+fn test_slice_stack() {
+    use crate::load::load_model;
+    use crate::threemf::{validate, Model};
+
+    let mut model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let stack = SliceStack::from_mesh(9, &model.objects[0].mesh, 0.3).unwrap();
+    assert_eq!(stack.zbottom, -1.0);
+    // 2mm in layers of 0.3mm, the last of which sticks out of the top
+    assert_eq!(stack.slices.len(), 7);
+    assert!((stack.slices[6].ztop - 1.1).abs() < 1e-9);
+    for slice in stack.slices.iter() {
+        assert!(slice.is_closed());
+        assert!((slice.area() - 4.0).abs() < 1e-9);
+    }
+
+    model.slices.push(stack);
+    model.objects[0].slicestackid = Some(9);
+    let read = Model::from_raw_data(&model.to_raw_data().unwrap()).unwrap();
+    assert_eq!(read, model);
+    assert_eq!(read.slice_stack(9), Some(&model.slices[0]));
+
+    // Slicing again replaces the stack
+    let mut model = read;
+    model.slice_objects(0.5).unwrap();
+    assert_eq!(model.slices.len(), 1);
+    assert_eq!(model.objects[0].slicestackid, Some(model.slices[0].id));
+    assert_eq!(model.slices[0].slices.len(), 4);
+
+    // Layers that aren't a positive number high are refused, and the model is left as it was
+    for height in [0.0, -0.5, f64::NAN, f64::INFINITY] {
+        let before = model.clone();
+        assert!(matches!(
+            model.slice_objects(height),
+            Err(Error::InvalidLayerHeight(_))
+        ));
+        assert_eq!(model, before);
+    }
+
+    // Slices written by other programs
+    let xml = r#"<model xmlns:s="http://schemas.microsoft.com/3dmanufacturing/slice/2015/07">
+        <resources>
+            <s:slicestack id="1" zbottom="0.5">
+                <s:slice ztop="0.6">
+                    <s:vertices>
+                        <s:vertex x="0" y="0"/><s:vertex x="2" y="0"/><s:vertex x="0" y="2"/>
+                    </s:vertices>
+                    <s:polygon startv="0"><s:segment v2="1"/><s:segment v2="2"/><s:segment v2="0"/></s:polygon>
+                </s:slice>
+                <s:slice ztop="0.7"/>
+            </s:slicestack>
+            <s:slicestack id="2"><s:sliceref slicestackid="4" slicepath="/3D/slices.model"/></s:slicestack>
+        </resources>
+        <build/>
+    </model>"#;
+    let model: Model = quick_xml::de::from_str(xml).unwrap();
+    assert_eq!(model.slices.len(), 2);
+    let slice = &model.slices[0].slices[0];
+    assert_eq!(slice.polygons, vec![vec![0, 1, 2, 0]]);
+    assert!((slice.area() - 2.0).abs() < 1e-9);
+    assert!(model.slices[0].slices[1].polygons.is_empty());
+    assert_eq!(model.slices[1].refs[0].slicestackid, 4);
+
+    // Slices kept in another part are read and written along with it
+    let mut model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    let mut part = Model {
+        unit: model.unit,
+        ..Model::empty()
+    };
+    part.slices
+        .push(SliceStack::from_mesh(4, &model.objects[0].mesh, 0.5).unwrap());
+    model.parts.insert("/3D/slices.model".to_string(), part);
+    model.slices.push(SliceStack {
+        id: 2,
+        zbottom: -1.0,
+        slices: Vec::new(),
+        refs: vec![SliceRef {
+            slicestackid: 4,
+            slicepath: "/3D/slices.model".to_string(),
+        }],
+    });
+    model.objects[0].slicestackid = Some(2);
+    let data = model.to_raw_data().unwrap();
+    let read = Model::from_raw_data(&data).unwrap();
+    assert_eq!(read, model);
+    assert!(!validate(&data).iter().any(|f| f.is_error()));
+
+    // and a reference to a stack that isn't there is reported
+    model.slices[0].refs[0].slicestackid = 5;
+    let findings = validate(&model.to_raw_data().unwrap());
+    assert!(findings
+        .iter()
+        .any(|f| f.is_error() && f.message.contains("slice stack 5, which is missing")));
+}
@@ -8,11 +8,10 @@ mod tests {
                 println!("Objects: {}", d.num_objects());
                 println!("Vertices: {}", d.num_vertices());
                 println!("Triangles: {}", d.num_triangles());
-                assert!(true)
             }
             Err(e) => {
                 println!("{:#?}", e);
-                assert!(false)
+                panic!("{:?}", e)
             }
         }
     }
@@ -46,11 +45,10 @@ mod tests {
                 println!("Objects: {}", d.num_objects());
                 println!("Vertices: {}", d.num_vertices());
                 println!("Triangles: {}", d.num_triangles());
-                assert!(true)
             }
             Err(e) => {
                 println!("{:#?}", e);
-                assert!(false)
+                panic!("{:?}", e)
             }
         }
     }
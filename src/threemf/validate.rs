@@ -0,0 +1,713 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::Cursor;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+use crate::geometry::Transform;
+use crate::threemf::package::{directory, resolve, Package, CONTENT_TYPES, MODEL_CONTENT_TYPE};
+use crate::threemf::write::{
+    BALLS_NAMESPACE, BEAM_LATTICE_NAMESPACE, CORE_NAMESPACE, MATERIAL_NAMESPACE,
+    PRODUCTION_NAMESPACE, ROOT_MODEL, SLICE_NAMESPACE,
+};
+use crate::threemf::{Model, Object, ObjectType};
+
+// Conformance checks of a 3MF file against the rules of the core specification, beyond what
+// is needed to read it: errors are files the specification doesn't allow, warnings are allowed
+// but likely to print differently from what was meant.
+
+// Extensions that can be required of a consumer, as this crate reads them
+const SUPPORTED_NAMESPACES: [&str; 6] = [
+    CORE_NAMESPACE,
+    MATERIAL_NAMESPACE,
+    PRODUCTION_NAMESPACE,
+    SLICE_NAMESPACE,
+    BEAM_LATTICE_NAMESPACE,
+    BALLS_NAMESPACE,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    // Part of the package it is in, e.g. /3D/3dmodel.model, or "/" for the package itself
+    pub part: String,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.severity.name(),
+            self.part,
+            self.message
+        )
+    }
+}
+
+// Findings as they are made, in the part being checked
+struct Findings {
+    part: String,
+    findings: Vec<Finding>,
+}
+
+impl Findings {
+    fn new() -> Self {
+        Findings {
+            part: "/".to_string(),
+            findings: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, severity: Severity, message: String) {
+        self.findings.push(Finding {
+            severity,
+            part: self.part.clone(),
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.add(Severity::Error, message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.add(Severity::Warning, message);
+    }
+}
+
+// Checks a 3MF file: the package, the namespaces of its model parts, and the models in them.
+// Parts that can't be read are reported and left out of the checks of the model.
+pub fn validate(data: &[u8]) -> Vec<Finding> {
+    let mut findings = Findings::new();
+    let archive = match ZipArchive::new(Cursor::new(data)) {
+        Ok(archive) => archive,
+        Err(e) => {
+            findings.error(format!("not a zip archive: {}", e));
+            return findings.findings;
+        }
+    };
+    let mut package = match Package::new(archive) {
+        Ok(package) => package,
+        Err(e) => {
+            findings.error(e.to_string());
+            return findings.findings;
+        }
+    };
+
+    // Every part needs a content type, and there must be a root model to start from
+    for part in package.part_names() {
+        if part != CONTENT_TYPES && package.content_types.content_type(&part).is_none() {
+            findings.error(format!("part {} has no content type", part));
+        }
+    }
+    let root = match package.root() {
+        Ok(root) => root,
+        Err(e) => {
+            findings.error(e.to_string());
+            return findings.findings;
+        }
+    };
+    let relationships = match package.read_relationships(&root) {
+        Ok(relationships) => relationships,
+        Err(e) => {
+            findings.error(e.to_string());
+            return findings.findings;
+        }
+    };
+    let targets = [
+        ("/", &package.relationships),
+        (directory(&root), &relationships),
+    ];
+    for (base, relationships) in targets {
+        for relationship in relationships.relationships.iter() {
+            let target = resolve(base, &relationship.target);
+            if !package.has_part(&target) {
+                findings.error(format!(
+                    "relationship {} is to {}, which is missing",
+                    relationship.id, target
+                ));
+            }
+        }
+    }
+
+    let mut model = match read_part(&mut package, &root, &mut findings) {
+        Some(model) => model,
+        None => return findings.findings,
+    };
+    findings.part = "/".to_string();
+    for path in model.other_parts(&root, &relationships) {
+        if !package.has_part(&path) {
+            // Reported with the references to it
+            continue;
+        }
+        if let Some(part) = read_part(&mut package, &path, &mut findings) {
+            model.parts.insert(path, part);
+        }
+    }
+    check_model(&model, &root, &mut findings);
+    findings.findings
+}
+
+// Checks the contents of a model and its other parts, taking the model to be the root model
+// as it is written
+pub fn validate_model(model: &Model) -> Vec<Finding> {
+    let mut findings = Findings::new();
+    check_model(model, ROOT_MODEL, &mut findings);
+    findings.findings
+}
+
+fn read_part<R: std::io::Read + std::io::Seek>(
+    package: &mut Package<R>,
+    part: &str,
+    findings: &mut Findings,
+) -> Option<Model> {
+    findings.part = part.to_string();
+    if package.content_types.content_type(part) != Some(MODEL_CONTENT_TYPE) {
+        findings.error(format!("content type is not {}", MODEL_CONTENT_TYPE));
+    }
+    let data = match package.read_bytes(part) {
+        Ok(data) => data,
+        Err(e) => {
+            findings.error(e.to_string());
+            return None;
+        }
+    };
+    check_namespaces(&data, findings);
+    match Model::read_part(package, part) {
+        Ok(model) => Some(model),
+        Err(e) => {
+            findings.error(e.to_string());
+            None
+        }
+    }
+}
+
+// The model element must be in the core namespace, extensions required of a consumer must be
+// declared and supported, and every prefix used must be declared
+fn check_namespaces(data: &[u8], findings: &mut Findings) {
+    let mut reader = Reader::from_bytes(data);
+    let mut declared: BTreeMap<String, String> = BTreeMap::new();
+    let mut undeclared: BTreeSet<String> = BTreeSet::new();
+    let mut root = true;
+    loop {
+        let e = match reader.read_event_unbuffered() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => e,
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            // Reported when the part is read
+            Err(_) => return,
+        };
+        let mut names = vec![e.name().to_vec()];
+        let mut required = None;
+        for a in e.attributes().with_checks(false).flatten() {
+            let value = || String::from_utf8_lossy(&a.value).into_owned();
+            match a.key {
+                b"xmlns" => {
+                    declared.insert(String::new(), value());
+                }
+                key if key.starts_with(b"xmlns:") => {
+                    let prefix = String::from_utf8_lossy(&key[6..]).into_owned();
+                    declared.insert(prefix, value());
+                }
+                b"requiredextensions" if root => required = Some(value()),
+                key => names.push(key.to_vec()),
+            }
+        }
+
+        if root {
+            root = false;
+            if e.name() != b"model" {
+                let name = String::from_utf8_lossy(e.name()).into_owned();
+                findings.error(format!("the root element is {}, not model", name));
+                return;
+            }
+            match declared.get("") {
+                Some(namespace) if namespace == CORE_NAMESPACE => {}
+                Some(namespace) => findings.error(format!(
+                    "the model is in namespace {}, not the core namespace {}",
+                    namespace, CORE_NAMESPACE
+                )),
+                None => findings.error("the model has no namespace".to_string()),
+            }
+            for prefix in required.iter().flat_map(|r| r.split_whitespace()) {
+                match declared.get(prefix) {
+                    Some(namespace) if SUPPORTED_NAMESPACES.contains(&namespace.as_str()) => {}
+                    Some(namespace) => findings.error(format!(
+                        "required extension {} ({}) is not supported",
+                        prefix, namespace
+                    )),
+                    None => findings.error(format!(
+                        "required extension {} has no namespace declared",
+                        prefix
+                    )),
+                }
+            }
+        }
+
+        for name in names {
+            let colon = match name.iter().position(|&c| c == b':') {
+                Some(colon) => colon,
+                None => continue,
+            };
+            let prefix = String::from_utf8_lossy(&name[..colon]).into_owned();
+            if prefix != "xml" && !declared.contains_key(&prefix) && undeclared.insert(prefix) {
+                findings.error(format!(
+                    "prefix {} is used without a namespace declared for it",
+                    String::from_utf8_lossy(&name[..colon])
+                ));
+            }
+        }
+    }
+}
+
+fn check_model(model: &Model, root: &str, findings: &mut Findings) {
+    let parts = std::iter::once((root, model))
+        .chain(model.parts.iter().map(|(path, part)| (path.as_str(), part)));
+    for (path, part) in parts {
+        findings.part = path.to_string();
+        check_resource_ids(part, findings);
+        for object in part.objects.iter() {
+            check_object(object, findings);
+            for (k, component) in object.components.iter().enumerate() {
+                let what = format!("component {} of object {}", k + 1, object.id);
+                let component_path = component.path.as_deref().or(Some(path));
+                check_reference(
+                    model,
+                    root,
+                    component_path,
+                    component.objectid,
+                    &what,
+                    findings,
+                );
+                check_transform(&component.transform, &what, findings);
+            }
+        }
+        check_component_loops(model, root, path, part, findings);
+        for stack in part.slices.iter() {
+            for (k, reference) in stack.refs.iter().enumerate() {
+                let found = model
+                    .parts
+                    .get(&reference.slicepath)
+                    .map(|p| p.slice_stack(reference.slicestackid).is_some());
+                let what = format!("slice reference {} of slice stack {}", k + 1, stack.id);
+                match found {
+                    None => findings.error(format!(
+                        "{} is in part {}, which is missing",
+                        what, reference.slicepath
+                    )),
+                    Some(false) => findings.error(format!(
+                        "{} is slice stack {}, which is missing",
+                        what, reference.slicestackid
+                    )),
+                    Some(true) => {}
+                }
+            }
+        }
+    }
+
+    findings.part = root.to_string();
+    for (k, item) in model.build.items.iter().enumerate() {
+        let what = format!("build item {}", k + 1);
+        let object = check_reference(
+            model,
+            root,
+            item.path.as_deref(),
+            item.objectid,
+            &what,
+            findings,
+        );
+        if let Some(object) = object {
+            if object.otype != ObjectType::Model {
+                findings.error(format!(
+                    "{} is object {}, of type {}; only model objects can be built",
+                    what,
+                    object.id,
+                    object.otype.name()
+                ));
+            }
+        }
+        check_transform(&item.transform, &what, findings);
+    }
+}
+
+// Resource ids start from 1, and no two resources of a part can have the same one
+fn check_resource_ids(part: &Model, findings: &mut Findings) {
+    let ids = part.objects.iter().map(|o| o.id);
+    let ids = ids.chain(part.materials.iter().map(|m| m.id));
+    let ids = ids.chain(part.colors.iter().map(|c| c.id));
+    let ids = ids.chain(part.slices.iter().map(|s| s.id));
+    let mut seen = BTreeSet::new();
+    let mut repeated = BTreeSet::new();
+    for id in ids {
+        if id == 0 {
+            findings.error("resource id 0 is not positive".to_string());
+        }
+        if !seen.insert(id) && repeated.insert(id) {
+            findings.error(format!("resource id {} is used more than once", id));
+        }
+    }
+}
+
+fn check_object(object: &Object, findings: &mut Findings) {
+    let mesh = &object.mesh;
+    if !object.components.is_empty() && !mesh.vertices.is_empty() {
+        findings.error(format!(
+            "object {} has both a mesh and components",
+            object.id
+        ));
+    }
+
+    // Geometry can't be worked out from coordinates that aren't numbers
+    let mut infinite = mesh
+        .vertices
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| ![p.x, p.y, p.z].iter().all(|v| v.is_finite()));
+    if let Some((first, _)) = infinite.next() {
+        findings.error(format!(
+            "{} vertices of object {} are not finite, the first is vertex {}",
+            infinite.count() + 1,
+            object.id,
+            first
+        ));
+    }
+
+    // Bad triangles are counted, as a broken mesh can have very many
+    let mut out_of_range = Vec::new();
+    let mut repeated = Vec::new();
+    let mut flat = Vec::new();
+    for (k, triangle) in mesh.triangles.iter().enumerate() {
+        let [a, b, c] = triangle.indices();
+        if [a, b, c].iter().any(|&v| v >= mesh.vertices.len()) {
+            out_of_range.push(k);
+        } else if a == b || b == c || c == a {
+            repeated.push(k);
+        } else if mesh.triangle_area(triangle) == 0.0 {
+            flat.push(k);
+        }
+    }
+    let bad = [
+        (
+            Severity::Error,
+            out_of_range,
+            "use vertices past the end of the list",
+        ),
+        (
+            Severity::Error,
+            repeated,
+            "use the same vertex more than once",
+        ),
+        (Severity::Warning, flat, "have no area"),
+    ];
+    for (severity, triangles, problem) in bad {
+        if let Some(first) = triangles.first() {
+            findings.add(
+                severity,
+                format!(
+                    "{} triangles of object {} {}, the first is triangle {}",
+                    triangles.len(),
+                    object.id,
+                    problem,
+                    first
+                ),
+            );
+        }
+    }
+
+    // Beams and balls at missing vertices are left out when the lattice is tessellated
+    if let Some(lattice) = &mesh.beamlattice {
+        let beams = lattice.beams.iter().enumerate();
+        let beams: Vec<_> = beams
+            .filter(|(_, b)| b.v1.max(b.v2) >= mesh.vertices.len())
+            .map(|(k, _)| k)
+            .collect();
+        let balls = lattice.balls.iter().enumerate();
+        let balls: Vec<_> = balls
+            .filter(|(_, b)| b.vindex >= mesh.vertices.len())
+            .map(|(k, _)| k)
+            .collect();
+        for (bad, what) in [(beams, "beam"), (balls, "ball")] {
+            if let Some(first) = bad.first() {
+                findings.error(format!(
+                    "{} {}s of object {} use vertices past the end of the list, the first is {} {}",
+                    bad.len(),
+                    what,
+                    object.id,
+                    what,
+                    first
+                ));
+            }
+        }
+    }
+}
+
+// The object a build item or component refers to, which must be there. Objects in other parts
+// are looked up in them; a part that isn't there is an error of its own.
+fn check_reference<'a>(
+    model: &'a Model,
+    root: &str,
+    path: Option<&str>,
+    id: usize,
+    what: &str,
+    findings: &mut Findings,
+) -> Option<&'a Object> {
+    let path = path.filter(|&p| p != root);
+    if let Some(p) = path {
+        if !model.parts.contains_key(p) {
+            findings.error(format!("{} is in part {}, which is missing", what, p));
+            return None;
+        }
+    }
+    let object = model.object(path, id);
+    if object.is_none() {
+        findings.error(format!("{} is object {}, which is missing", what, id));
+    }
+    object
+}
+
+fn check_transform(transform: &Transform, what: &str, findings: &mut Findings) {
+    let t = transform.translation;
+    let finite = transform.matrix.iter().flatten().all(|v| v.is_finite());
+    if !finite || ![t.x, t.y, t.z].iter().all(|v| v.is_finite()) {
+        findings.error(format!("transform of {} is not finite", what));
+    } else if transform.inverse().is_none() {
+        findings.error(format!("transform of {} is singular", what));
+    } else if transform.determinant() < 0.0 {
+        findings.warning(format!(
+            "transform of {} mirrors it, turning its triangles inside out",
+            what
+        ));
+    }
+}
+
+// An object can't be made of itself, however many components away
+fn check_component_loops(
+    model: &Model,
+    root: &str,
+    path: &str,
+    part: &Model,
+    findings: &mut Findings,
+) {
+    // Objects known not to be in a loop
+    let mut done: BTreeSet<(String, usize)> = BTreeSet::new();
+    for object in part.objects.iter() {
+        let mut stack = Vec::new();
+        if in_loop(
+            model,
+            root,
+            (path.to_string(), object.id),
+            &mut stack,
+            &mut done,
+        ) {
+            findings.error(format!("object {} is made of itself", object.id));
+        }
+    }
+}
+
+fn in_loop(
+    model: &Model,
+    root: &str,
+    key: (String, usize),
+    stack: &mut Vec<(String, usize)>,
+    done: &mut BTreeSet<(String, usize)>,
+) -> bool {
+    if done.contains(&key) {
+        return false;
+    }
+    if stack.contains(&key) {
+        return true;
+    }
+    let path = Some(key.0.as_str()).filter(|&p| p != root);
+    let object = match model.object(path, key.1) {
+        Some(object) => object,
+        None => return false,
+    };
+    stack.push(key.clone());
+    for component in object.components.iter() {
+        let next = component.path.clone().unwrap_or_else(|| key.0.clone());
+        if in_loop(model, root, (next, component.objectid), stack, done) {
+            return true;
+        }
+    }
+    stack.pop();
+    done.insert(key);
+    false
+}
+
+#[test]
+fn test_validate() {
+    use crate::geometry::{Point, Triangle, Vector};
+    use crate::threemf::{Build, Component, Item, Mesh};
+
+    // Every file that comes with the crate can be sent to print
+    for entry in std::fs::read_dir("data").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "3mf") {
+            let findings = validate(&std::fs::read(&path).unwrap());
+            assert!(
+                !findings.iter().any(|f| f.is_error()),
+                "{}: {:?}",
+                path.display(),
+                findings
+            );
+        }
+    }
+
+    let mut model = Model::from_file("data/centered_cube_2x2x2.3mf").unwrap();
+    assert!(validate_model(&model).is_empty());
+    let cube = model.objects[0].clone();
+
+    // A second object with the same id, made of bad triangles
+    let mut broken = cube.piece(cube.id, cube.mesh.clone());
+    let n = broken.mesh.vertices.len();
+    broken.mesh.vertices.push(Point::new(0.0, 0.0, 0.0));
+    broken.mesh.triangles.extend([
+        Triangle::new(0, 1, n + 5),
+        Triangle::new(0, 1, n + 6),
+        Triangle::new(0, 0, 1),
+        Triangle::new(n, n, n),
+    ]);
+    model.objects.push(broken);
+    // and one that is made of itself, through another
+    let assembly = |id: usize, of: usize| Object {
+        components: vec![Component {
+            objectid: of,
+            ..Component::default()
+        }],
+        ..cube.piece(id, Mesh::default())
+    };
+    model.objects.push(assembly(10, 11));
+    model.objects.push(assembly(11, 10));
+    model.objects[0].otype = ObjectType::Support;
+    let flat = Transform {
+        matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]],
+        translation: Vector::zero(),
+    };
+    model.build = Build {
+        uuid: None,
+        items: vec![
+            Item {
+                objectid: cube.id,
+                transform: flat,
+                ..Item::default()
+            },
+            Item {
+                objectid: 99,
+                ..Item::default()
+            },
+        ],
+    };
+
+    let messages: Vec<String> = validate_model(&model)
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+    let id = cube.id;
+    let root = ROOT_MODEL;
+    assert_eq!(
+        messages,
+        vec![
+            format!("error: {}: resource id {} is used more than once", root, id),
+            format!(
+                "error: {}: 2 triangles of object {} use vertices past the end of the list, \
+                 the first is triangle 12",
+                root, id
+            ),
+            format!(
+                "error: {}: 2 triangles of object {} use the same vertex more than once, \
+                 the first is triangle 14",
+                root, id
+            ),
+            format!("error: {}: object 10 is made of itself", root),
+            format!("error: {}: object 11 is made of itself", root),
+            format!(
+                "error: {}: build item 1 is object {}, of type support; only model objects \
+                 can be built",
+                root, id
+            ),
+            format!("error: {}: transform of build item 1 is singular", root),
+            format!(
+                "error: {}: build item 2 is object 99, which is missing",
+                root
+            ),
+        ]
+    );
+
+    // A file with vertex indices out of range can't be read any further
+    let findings = validate(&model.to_raw_data().unwrap());
+    assert_eq!(findings.len(), 1);
+    assert!(findings[0].is_error() && findings[0].message.contains("out of range"));
+
+    // Coordinates that aren't numbers can be read, but nothing can be made of them
+    let mut infinite = Model::from_file("data/centered_cube_2x2x2.3mf").unwrap();
+    infinite.objects[0].mesh.vertices[2].x = f64::NAN;
+    infinite.objects[0].mesh.vertices[5].z = f64::INFINITY;
+    let findings = validate(&infinite.to_raw_data().unwrap());
+    let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+    assert_eq!(
+        messages,
+        vec![format!(
+            "error: {}: 2 vertices of object {} are not finite, the first is vertex 2",
+            root, id
+        )]
+    );
+
+    let mut undeclared = Model::from_file("data/centered_cube_2x2x2.3mf").unwrap();
+    undeclared.objects[0].uuid = Some("b0c2a9c4-8d3c-4b7e-9d2a-000000000001".to_string());
+    let xml = {
+        let mut xml = Vec::new();
+        undeclared.write_xml(&mut xml).unwrap();
+        String::from_utf8(xml).unwrap()
+    };
+    let mut findings = Findings::new();
+    let declared = xml.replacen(
+        " xmlns:p=",
+        " requiredextensions=\"p q\" xmlns:q=\"urn:unknown\" xmlns:p=",
+        1,
+    );
+    check_namespaces(declared.as_bytes(), &mut findings);
+    let stripped = xml.replacen(&format!(" xmlns:p=\"{}\"", PRODUCTION_NAMESPACE), "", 1);
+    check_namespaces(stripped.as_bytes(), &mut findings);
+    check_namespaces(b"<model xmlns=\"urn:other\"/>", &mut findings);
+    let messages: Vec<String> = findings
+        .findings
+        .iter()
+        .map(|f| f.message.clone())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            "required extension q (urn:unknown) is not supported".to_string(),
+            "prefix p is used without a namespace declared for it".to_string(),
+            format!(
+                "the model is in namespace urn:other, not the core namespace {}",
+                CORE_NAMESPACE
+            ),
+        ]
+    );
+}
@@ -0,0 +1,568 @@
+use std::io::{Cursor, Seek, Write};
+use std::path::Path;
+
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::error::Error;
+use crate::geometry::Transform;
+use crate::threemf::package::{
+    archive_name, relationships_name, AttachedTo, CONTENT_TYPES, MODEL_CONTENT_TYPE,
+    MODEL_RELATIONSHIP, PACKAGE_RELATIONSHIPS, RELATIONSHIPS_CONTENT_TYPE,
+};
+use crate::threemf::{BallMode, BeamLattice, Metadata, Model, Object, ObjectType, SliceStack};
+
+pub const CORE_NAMESPACE: &str = "http://schemas.microsoft.com/3dmanufacturing/core/2015/02";
+pub const MATERIAL_NAMESPACE: &str =
+    "http://schemas.microsoft.com/3dmanufacturing/material/2015/02";
+pub const PRODUCTION_NAMESPACE: &str =
+    "http://schemas.microsoft.com/3dmanufacturing/production/2015/06";
+pub const SLICE_NAMESPACE: &str = "http://schemas.microsoft.com/3dmanufacturing/slice/2015/07";
+pub const BEAM_LATTICE_NAMESPACE: &str =
+    "http://schemas.microsoft.com/3dmanufacturing/beamlattice/2017/02";
+pub const BALLS_NAMESPACE: &str =
+    "http://schemas.microsoft.com/3dmanufacturing/beamlattice/balls/2020/07";
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships";
+const CONTENT_TYPES_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/content-types";
+// Where the root model part is written
+pub const ROOT_MODEL: &str = "/3D/3dmodel.model";
+
+impl Model {
+    // Writes the model as a 3MF package, along with its other model parts
+    pub fn to_writer<W: Write + Seek>(&self, writer: W) -> Result<(), Error> {
+        let mut zip = ZipWriter::new(writer);
+        let options = FileOptions::default();
+
+        zip.start_file(archive_name(CONTENT_TYPES), options)?;
+        self.write_content_types(&mut zip)?;
+
+        // Relationships of the package and of the root model, to the parts and attachments
+        let attached = |attached_to| {
+            self.attachments
+                .iter()
+                .filter(move |a| a.attached_to == attached_to)
+                .map(|a| (a.relationship.as_str(), a.part.as_str()))
+        };
+        let package: Vec<(&str, &str)> = std::iter::once((MODEL_RELATIONSHIP, ROOT_MODEL))
+            .chain(attached(AttachedTo::Package))
+            .collect();
+        zip.start_file(archive_name(PACKAGE_RELATIONSHIPS), options)?;
+        write_relationships(&mut zip, &package)?;
+        let root: Vec<(&str, &str)> = self
+            .parts
+            .keys()
+            .map(|k| (MODEL_RELATIONSHIP, k.as_str()))
+            .chain(attached(AttachedTo::Model))
+            .collect();
+        if !root.is_empty() {
+            zip.start_file(archive_name(&relationships_name(ROOT_MODEL)), options)?;
+            write_relationships(&mut zip, &root)?;
+        }
+
+        zip.start_file(archive_name(ROOT_MODEL), options)?;
+        self.write_xml(&mut zip)?;
+        for (name, part) in self.parts.iter() {
+            zip.start_file(archive_name(name), options)?;
+            part.write_xml(&mut zip)?;
+        }
+        for attachment in self.attachments.iter() {
+            zip.start_file(archive_name(&attachment.part), options)?;
+            zip.write_all(&attachment.data)?;
+        }
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    pub fn to_raw_data(&self) -> Result<Vec<u8>, Error> {
+        let mut data = Cursor::new(Vec::new());
+        self.to_writer(&mut data)?;
+        Ok(data.into_inner())
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = std::fs::File::create(path)?;
+        self.to_writer(file)
+    }
+
+    // Models and relationships by extension, and each attachment on its own
+    fn write_content_types<W: Write>(&self, inner: W) -> Result<(), Error> {
+        let mut writer = Writer::new_with_indent(inner, b'\t', 1);
+        writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+        let mut types = BytesStart::borrowed_name(b"Types");
+        types.push_attribute(("xmlns", CONTENT_TYPES_NAMESPACE));
+        writer.write_event(Event::Start(types))?;
+        for (extension, content_type) in [
+            ("rels", RELATIONSHIPS_CONTENT_TYPE),
+            ("model", MODEL_CONTENT_TYPE),
+        ] {
+            let mut default = BytesStart::borrowed_name(b"Default");
+            default.push_attribute(("Extension", extension));
+            default.push_attribute(("ContentType", content_type));
+            writer.write_event(Event::Empty(default))?;
+        }
+        for attachment in self.attachments.iter() {
+            let mut part = BytesStart::borrowed_name(b"Override");
+            part.push_attribute(("PartName", attachment.part.as_str()));
+            part.push_attribute(("ContentType", attachment.content_type.as_str()));
+            writer.write_event(Event::Empty(part))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"Types")))?;
+        Ok(())
+    }
+
+    // Writes a model part of the package on its own
+    pub fn write_xml<W: Write>(&self, inner: W) -> Result<(), Error> {
+        let mut writer = Writer::new_with_indent(inner, b'\t', 1);
+        writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+        let mut model = BytesStart::borrowed_name(b"model");
+        model.push_attribute(("xmlns", CORE_NAMESPACE));
+        if !self.colors.is_empty() {
+            model.push_attribute(("xmlns:m", MATERIAL_NAMESPACE));
+        }
+        if !self.slices.is_empty() || self.objects.iter().any(|o| o.slicestackid.is_some()) {
+            model.push_attribute(("xmlns:s", SLICE_NAMESPACE));
+        }
+        let lattices = self
+            .objects
+            .iter()
+            .filter_map(|o| o.mesh.beamlattice.as_ref());
+        if lattices.clone().next().is_some() {
+            model.push_attribute(("xmlns:b", BEAM_LATTICE_NAMESPACE));
+        }
+        if lattices.clone().any(uses_balls) {
+            model.push_attribute(("xmlns:b2", BALLS_NAMESPACE));
+        }
+        if self.uses_production() {
+            model.push_attribute(("xmlns:p", PRODUCTION_NAMESPACE));
+        }
+        // Objects in other parts can't be found without the production extension, and objects
+        // that are only a beam lattice have nothing to print without the lattice extension
+        let mut required = Vec::new();
+        if !self.parts.is_empty() {
+            required.push("p");
+        }
+        if self
+            .objects
+            .iter()
+            .any(|o| o.mesh.beamlattice.is_some() && o.mesh.triangles.is_empty())
+        {
+            required.push("b");
+        }
+        if !required.is_empty() {
+            model.push_attribute(("requiredextensions", required.join(" ").as_str()));
+        }
+        if let Some(unit) = self.unit.name() {
+            model.push_attribute(("unit", unit));
+        }
+        writer.write_event(Event::Start(model))?;
+        write_metadata(&mut writer, &self.metadata)?;
+
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"resources")))?;
+        // Property groups first, as objects can only use the ones defined before them
+        for group in self.materials.iter() {
+            let mut element = BytesStart::borrowed_name(b"basematerials");
+            element.push_attribute(("id", group.id.to_string().as_str()));
+            writer.write_event(Event::Start(element))?;
+            for material in group.materials.iter() {
+                let mut base = BytesStart::borrowed_name(b"base");
+                base.push_attribute(("name", material.name.as_str()));
+                base.push_attribute(("displaycolor", material.color.to_string().as_str()));
+                writer.write_event(Event::Empty(base))?;
+            }
+            writer.write_event(Event::End(BytesEnd::borrowed(b"basematerials")))?;
+        }
+        for group in self.colors.iter() {
+            let mut element = BytesStart::borrowed_name(b"m:colorgroup");
+            element.push_attribute(("id", group.id.to_string().as_str()));
+            writer.write_event(Event::Start(element))?;
+            for color in group.colors.iter() {
+                let mut entry = BytesStart::borrowed_name(b"m:color");
+                entry.push_attribute(("color", color.to_string().as_str()));
+                writer.write_event(Event::Empty(entry))?;
+            }
+            writer.write_event(Event::End(BytesEnd::borrowed(b"m:colorgroup")))?;
+        }
+        for stack in self.slices.iter() {
+            write_slice_stack(&mut writer, stack)?;
+        }
+        for object in self.objects.iter() {
+            write_object(&mut writer, object)?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"resources")))?;
+
+        let mut build = BytesStart::borrowed_name(b"build");
+        if let Some(uuid) = self.build.uuid.as_deref() {
+            build.push_attribute(("p:UUID", uuid));
+        }
+        writer.write_event(Event::Start(build))?;
+        for item in self.build.items.iter() {
+            let mut element = BytesStart::borrowed_name(b"item");
+            push_reference(
+                &mut element,
+                item.objectid,
+                item.path.as_deref(),
+                item.uuid.as_deref(),
+                &item.transform,
+            );
+            if let Some(partnumber) = item.partnumber.as_deref() {
+                element.push_attribute(("partnumber", partnumber));
+            }
+            writer.write_event(Event::Empty(element))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"build")))?;
+
+        writer.write_event(Event::End(BytesEnd::borrowed(b"model")))?;
+        Ok(())
+    }
+
+    fn uses_production(&self) -> bool {
+        let items = self.build.items.iter();
+        let components = self.objects.iter().flat_map(|o| o.components.iter());
+        self.build.uuid.is_some()
+            || !self.parts.is_empty()
+            || items
+                .map(|i| (&i.path, &i.uuid))
+                .chain(components.map(|c| (&c.path, &c.uuid)))
+                .any(|(path, uuid)| path.is_some() || uuid.is_some())
+            || self.objects.iter().any(|o| o.uuid.is_some())
+    }
+}
+
+// Relationships as (type, target) pairs
+fn write_relationships<W: Write>(inner: W, relationships: &[(&str, &str)]) -> Result<(), Error> {
+    let mut writer = Writer::new_with_indent(inner, b'\t', 1);
+    writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+    let mut element = BytesStart::borrowed_name(b"Relationships");
+    element.push_attribute(("xmlns", RELATIONSHIPS_NAMESPACE));
+    writer.write_event(Event::Start(element))?;
+    for (k, (rtype, target)) in relationships.iter().enumerate() {
+        let mut relationship = BytesStart::borrowed_name(b"Relationship");
+        relationship.push_attribute(("Type", *rtype));
+        relationship.push_attribute(("Target", *target));
+        relationship.push_attribute(("Id", format!("rel{}", k).as_str()));
+        writer.write_event(Event::Empty(relationship))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"Relationships")))?;
+    Ok(())
+}
+
+// Attributes of a build item or a component
+fn push_reference(
+    element: &mut BytesStart,
+    objectid: usize,
+    path: Option<&str>,
+    uuid: Option<&str>,
+    transform: &Transform,
+) {
+    element.push_attribute(("objectid", objectid.to_string().as_str()));
+    if *transform != Transform::identity() {
+        element.push_attribute(("transform", transform.to_attribute().as_str()));
+    }
+    if let Some(path) = path {
+        element.push_attribute(("p:path", path));
+    }
+    if let Some(uuid) = uuid {
+        element.push_attribute(("p:UUID", uuid));
+    }
+}
+
+fn write_metadata<W: Write>(writer: &mut Writer<W>, metadata: &Metadata) -> Result<(), Error> {
+    for (name, entry) in metadata.iter() {
+        let mut element = BytesStart::borrowed_name(b"metadata");
+        element.push_attribute(("name", name));
+        if entry.preserve {
+            element.push_attribute(("preserve", "1"));
+        }
+        if let Some(dtype) = entry.dtype.as_deref() {
+            element.push_attribute(("type", dtype));
+        }
+        writer.write_event(Event::Start(element))?;
+        writer.write_event(Event::Text(BytesText::from_plain_str(&entry.value)))?;
+        writer.write_event(Event::End(BytesEnd::borrowed(b"metadata")))?;
+    }
+    Ok(())
+}
+
+fn write_object<W: Write>(writer: &mut Writer<W>, object: &Object) -> Result<(), Error> {
+    let mut element = BytesStart::borrowed_name(b"object");
+    element.push_attribute(("id", object.id.to_string().as_str()));
+    element.push_attribute(("name", object.name.as_str()));
+    if object.otype != ObjectType::Model {
+        element.push_attribute(("type", object.otype.name()));
+    }
+    if let Some(uuid) = object.uuid.as_deref() {
+        element.push_attribute(("p:UUID", uuid));
+    }
+    for (name, value) in [("pid", object.pid), ("pindex", object.pindex)] {
+        if let Some(value) = value {
+            element.push_attribute((name, value.to_string().as_str()));
+        }
+    }
+    if let Some(id) = object.slicestackid {
+        element.push_attribute(("s:slicestackid", id.to_string().as_str()));
+    }
+    if let Some(resolution) = object.meshresolution {
+        element.push_attribute(("s:meshresolution", resolution.name()));
+    }
+    writer.write_event(Event::Start(element))?;
+
+    if !object.metadata.is_empty() {
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"metadatagroup")))?;
+        write_metadata(writer, &object.metadata)?;
+        writer.write_event(Event::End(BytesEnd::borrowed(b"metadatagroup")))?;
+    }
+
+    if !object.components.is_empty() {
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"components")))?;
+        for component in object.components.iter() {
+            let mut element = BytesStart::borrowed_name(b"component");
+            push_reference(
+                &mut element,
+                component.objectid,
+                component.path.as_deref(),
+                component.uuid.as_deref(),
+                &component.transform,
+            );
+            writer.write_event(Event::Empty(element))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"components")))?;
+        writer.write_event(Event::End(BytesEnd::borrowed(b"object")))?;
+        return Ok(());
+    }
+
+    let mesh = &object.mesh;
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"mesh")))?;
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"vertices")))?;
+    for p in mesh.vertices.iter() {
+        let mut vertex = BytesStart::borrowed_name(b"vertex");
+        vertex.push_attribute(("x", p.x.to_string().as_str()));
+        vertex.push_attribute(("y", p.y.to_string().as_str()));
+        vertex.push_attribute(("z", p.z.to_string().as_str()));
+        writer.write_event(Event::Empty(vertex))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"vertices")))?;
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"triangles")))?;
+    for t in mesh.triangles.iter() {
+        let mut triangle = BytesStart::borrowed_name(b"triangle");
+        triangle.push_attribute(("v1", t.v1.to_string().as_str()));
+        triangle.push_attribute(("v2", t.v2.to_string().as_str()));
+        triangle.push_attribute(("v3", t.v3.to_string().as_str()));
+        let properties = [("pid", t.pid), ("p1", t.p1), ("p2", t.p2), ("p3", t.p3)];
+        for (name, value) in properties {
+            if let Some(value) = value {
+                triangle.push_attribute((name, value.to_string().as_str()));
+            }
+        }
+        writer.write_event(Event::Empty(triangle))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"triangles")))?;
+    if let Some(lattice) = mesh.beamlattice.as_ref() {
+        write_beam_lattice(writer, lattice)?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"mesh")))?;
+
+    writer.write_event(Event::End(BytesEnd::borrowed(b"object")))?;
+    Ok(())
+}
+
+fn uses_balls(lattice: &BeamLattice) -> bool {
+    lattice.ballmode != BallMode::None
+        || lattice.ballradius.is_some()
+        || !lattice.balls.is_empty()
+        || lattice.beamsets.iter().any(|s| !s.balls.is_empty())
+}
+
+fn write_beam_lattice<W: Write>(
+    writer: &mut Writer<W>,
+    lattice: &BeamLattice,
+) -> Result<(), Error> {
+    let mut element = BytesStart::borrowed_name(b"b:beamlattice");
+    element.push_attribute(("minlength", lattice.minlength.to_string().as_str()));
+    element.push_attribute(("radius", lattice.radius.to_string().as_str()));
+    element.push_attribute(("cap", lattice.cap.name()));
+    if lattice.ballmode != BallMode::None {
+        element.push_attribute(("b2:ballmode", lattice.ballmode.name()));
+    }
+    if let Some(radius) = lattice.ballradius {
+        element.push_attribute(("b2:ballradius", radius.to_string().as_str()));
+    }
+    if let Some(mode) = lattice.clippingmode.as_deref() {
+        element.push_attribute(("clippingmode", mode));
+    }
+    let references = [
+        ("clippingmesh", lattice.clippingmesh),
+        ("representationmesh", lattice.representationmesh),
+        ("pid", lattice.pid),
+        ("pindex", lattice.pindex),
+    ];
+    for (name, value) in references {
+        if let Some(value) = value {
+            element.push_attribute((name, value.to_string().as_str()));
+        }
+    }
+    writer.write_event(Event::Start(element))?;
+
+    writer.write_event(Event::Start(BytesStart::borrowed_name(b"b:beams")))?;
+    for beam in lattice.beams.iter() {
+        let mut element = BytesStart::borrowed_name(b"b:beam");
+        element.push_attribute(("v1", beam.v1.to_string().as_str()));
+        element.push_attribute(("v2", beam.v2.to_string().as_str()));
+        for (name, value) in [("r1", beam.r1), ("r2", beam.r2)] {
+            if let Some(value) = value {
+                element.push_attribute((name, value.to_string().as_str()));
+            }
+        }
+        for (name, value) in [("cap1", beam.cap1), ("cap2", beam.cap2)] {
+            if let Some(value) = value {
+                element.push_attribute((name, value.name()));
+            }
+        }
+        for (name, value) in [("pid", beam.pid), ("p1", beam.p1), ("p2", beam.p2)] {
+            if let Some(value) = value {
+                element.push_attribute((name, value.to_string().as_str()));
+            }
+        }
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"b:beams")))?;
+
+    if !lattice.balls.is_empty() {
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"b2:balls")))?;
+        for ball in lattice.balls.iter() {
+            let mut element = BytesStart::borrowed_name(b"b2:ball");
+            element.push_attribute(("vindex", ball.vindex.to_string().as_str()));
+            if let Some(r) = ball.r {
+                element.push_attribute(("r", r.to_string().as_str()));
+            }
+            for (name, value) in [("pid", ball.pid), ("p", ball.p)] {
+                if let Some(value) = value {
+                    element.push_attribute((name, value.to_string().as_str()));
+                }
+            }
+            writer.write_event(Event::Empty(element))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"b2:balls")))?;
+    }
+
+    if !lattice.beamsets.is_empty() {
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"b:beamsets")))?;
+        for set in lattice.beamsets.iter() {
+            let mut element = BytesStart::borrowed_name(b"b:beamset");
+            if let Some(name) = set.name.as_deref() {
+                element.push_attribute(("name", name));
+            }
+            if let Some(identifier) = set.identifier.as_deref() {
+                element.push_attribute(("identifier", identifier));
+            }
+            writer.write_event(Event::Start(element))?;
+            let refs = set.beams.iter().map(|k| (&b"b:ref"[..], k));
+            let ball_refs = set.balls.iter().map(|k| (&b"b2:ballref"[..], k));
+            for (name, index) in refs.chain(ball_refs) {
+                let mut element = BytesStart::borrowed_name(name);
+                element.push_attribute(("index", index.to_string().as_str()));
+                writer.write_event(Event::Empty(element))?;
+            }
+            writer.write_event(Event::End(BytesEnd::borrowed(b"b:beamset")))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"b:beamsets")))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::borrowed(b"b:beamlattice")))?;
+    Ok(())
+}
+
+fn write_slice_stack<W: Write>(writer: &mut Writer<W>, stack: &SliceStack) -> Result<(), Error> {
+    let mut element = BytesStart::borrowed_name(b"s:slicestack");
+    element.push_attribute(("id", stack.id.to_string().as_str()));
+    element.push_attribute(("zbottom", stack.zbottom.to_string().as_str()));
+    writer.write_event(Event::Start(element))?;
+    for slice in stack.slices.iter() {
+        let mut element = BytesStart::borrowed_name(b"s:slice");
+        element.push_attribute(("ztop", slice.ztop.to_string().as_str()));
+        // Slices above the top of an object are empty
+        if slice.vertices.is_empty() {
+            writer.write_event(Event::Empty(element))?;
+            continue;
+        }
+        writer.write_event(Event::Start(element))?;
+        writer.write_event(Event::Start(BytesStart::borrowed_name(b"s:vertices")))?;
+        for [x, y] in slice.vertices.iter() {
+            let mut vertex = BytesStart::borrowed_name(b"s:vertex");
+            vertex.push_attribute(("x", x.to_string().as_str()));
+            vertex.push_attribute(("y", y.to_string().as_str()));
+            writer.write_event(Event::Empty(vertex))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"s:vertices")))?;
+        for polygon in slice.polygons.iter() {
+            let (start, segments) = match polygon.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            let mut element = BytesStart::borrowed_name(b"s:polygon");
+            element.push_attribute(("startv", start.to_string().as_str()));
+            writer.write_event(Event::Start(element))?;
+            for v in segments.iter() {
+                let mut segment = BytesStart::borrowed_name(b"s:segment");
+                segment.push_attribute(("v2", v.to_string().as_str()));
+                writer.write_event(Event::Empty(segment))?;
+            }
+            writer.write_event(Event::End(BytesEnd::borrowed(b"s:polygon")))?;
+        }
+        writer.write_event(Event::End(BytesEnd::borrowed(b"s:slice")))?;
+    }
+    for reference in stack.refs.iter() {
+        let mut element = BytesStart::borrowed_name(b"s:sliceref");
+        element.push_attribute(("slicestackid", reference.slicestackid.to_string().as_str()));
+        element.push_attribute(("slicepath", reference.slicepath.as_str()));
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::borrowed(b"s:slicestack")))?;
+    Ok(())
+}
+
+#[test]
+fn test_write_model() {
+    use crate::cut::cut_model;
+    use crate::geometry::{Plane, Point, Vector};
+    use crate::load::load_model;
+    use crate::threemf::metadata::{DESIGNER, TITLE};
+    use crate::threemf::MetadataValue;
+
+    let mut model = load_model("data/centered_cube_2x2x2.3mf").unwrap();
+    model
+        .metadata
+        .insert(TITLE, MetadataValue::new("Cube <2x2x2>"));
+    model.metadata.insert(
+        DESIGNER,
+        MetadataValue {
+            value: "J. Smith & Co".to_string(),
+            preserve: true,
+            dtype: Some("xs:string".to_string()),
+        },
+    );
+    model.objects[0]
+        .metadata
+        .insert("PartNumber", MetadataValue::new("1234-A"));
+
+    let data = model.to_raw_data().unwrap();
+    assert_eq!(Model::from_raw_data(&data).unwrap(), model);
+
+    // Cut pieces keep the metadata of the model and of the object they came from
+    let cut = cut_model(&model, &Plane::new(Vector::Z, Point::zero()));
+    assert_eq!(cut.num_objects(), 2);
+    let data = cut.to_raw_data().unwrap();
+    let cut = Model::from_raw_data(&data).unwrap();
+    assert_eq!(cut.metadata, model.metadata);
+    assert_eq!(cut.objects[0].id, 1);
+    assert_eq!(cut.objects[1].id, 2);
+    for object in cut.objects.iter() {
+        assert_eq!(object.metadata.get("PartNumber"), Some("1234-A"));
+        assert!((object.mesh.volume() - 4.0).abs() < 1e-9);
+    }
+}
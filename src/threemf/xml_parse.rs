@@ -1,7 +1,23 @@
+use crate::common::Unit;
+use crate::error::{Error, TextPosition, XmlError};
+use crate::geometry::{Point, Transform, Triangle};
+use crate::threemf::build::Build;
+use crate::threemf::material::{BaseMaterials, ColorGroup};
+use crate::threemf::write::{
+    BALLS_NAMESPACE, BEAM_LATTICE_NAMESPACE, MATERIAL_NAMESPACE, PRODUCTION_NAMESPACE,
+    SLICE_NAMESPACE,
+};
+use crate::threemf::{Metadata, Model, Object, SliceStack};
+use quick_xml::de::DeError;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::{Reader, Writer};
 use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Vertices<T> {
+    #[serde(default)]
     pub vertex: T,
 }
 
@@ -9,7 +25,7 @@ pub struct Vertices<T> {
 impl<T> Vertices<T> {
     pub fn deserialize<'de, D>(deserializer: D) -> Result<T, D::Error>
     where
-        T: Deserialize<'de>,
+        T: Deserialize<'de> + Default,
         D: Deserializer<'de>,
     {
         let wrapper = <Self as Deserialize>::deserialize(deserializer)?;
@@ -19,6 +35,7 @@ impl<T> Vertices<T> {
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Triangles<T> {
+    #[serde(default)]
     pub triangle: T,
 }
 
@@ -26,7 +43,7 @@ pub struct Triangles<T> {
 impl<T> Triangles<T> {
     pub fn deserialize<'de, D>(deserializer: D) -> Result<T, D::Error>
     where
-        T: Deserialize<'de>,
+        T: Deserialize<'de> + Default,
         D: Deserializer<'de>,
     {
         let wrapper = <Self as Deserialize>::deserialize(deserializer)?;
@@ -36,18 +53,833 @@ impl<T> Triangles<T> {
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Resources<T> {
-    pub object: T,
+    #[serde(rename = "$value", default)]
+    pub resource: T,
 }
 
 // TODO: Put this into a wrapper macro
-// Model is defined outside of this file, so we need to pub deserialize
 impl<T> Resources<T> {
     pub fn deserialize<'de, D>(deserializer: D) -> Result<T, D::Error>
     where
-        T: Deserialize<'de>,
+        T: Deserialize<'de> + Default,
         D: Deserializer<'de>,
     {
         let wrapper = <Self as Deserialize>::deserialize(deserializer)?;
-        Ok(wrapper.object)
+        Ok(wrapper.resource)
     }
 }
+
+// An element of <resources>. They can come in any order.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    // Boxed, as objects are much larger than the other resources
+    Object(Box<Object>),
+    BaseMaterials(BaseMaterials),
+    #[serde(rename = "m:colorgroup", alias = "colorgroup")]
+    ColorGroup(ColorGroup),
+    #[serde(rename = "s:slicestack", alias = "slicestack")]
+    SliceStack(SliceStack),
+    #[serde(other)]
+    Unsupported,
+}
+
+// The model part of a package as it is laid out in the file
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ModelFile {
+    #[serde(default)]
+    pub unit: Unit,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(with = "Resources", default)]
+    pub resources: Vec<Resource>,
+    #[serde(default)]
+    pub build: Build,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct MetadataGroup<T> {
+    #[serde(default)]
+    pub metadata: T,
+}
+
+// TODO: Put this into a wrapper macro
+impl<T> MetadataGroup<T> {
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        let wrapper = <Self as Deserialize>::deserialize(deserializer)?;
+        Ok(wrapper.metadata)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Components<T> {
+    #[serde(default)]
+    pub component: T,
+}
+
+// TODO: Put this into a wrapper macro
+impl<T> Components<T> {
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        let wrapper = <Self as Deserialize>::deserialize(deserializer)?;
+        Ok(wrapper.component)
+    }
+}
+
+// A transform attribute, see Transform::parse
+pub fn transform<'de, D>(deserializer: D) -> Result<Transform, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    Transform::parse(&text)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid transform {:?}", text)))
+}
+
+// The prefixes the serde names use for the extension namespaces. serde only sees names, so
+// prefixed names in a file that binds other prefixes to these namespaces are given these
+// before it reads them.
+const PREFIXES: [(&str, &[u8]); 5] = [
+    (MATERIAL_NAMESPACE, b"m"),
+    (PRODUCTION_NAMESPACE, b"p"),
+    (SLICE_NAMESPACE, b"s"),
+    (BEAM_LATTICE_NAMESPACE, b"b"),
+    (BALLS_NAMESPACE, b"b2"),
+];
+
+// Reads a model part. Mesh vertices and triangles, which are nearly all of a large part, are
+// pulled straight out of the XML into their lists. Each resource and the build is passed on
+// to serde without them, so that its errors can be placed, and the meshes are put back into
+// their objects afterwards. Prefixed names are matched by the namespace they are bound to (see
+// PREFIXES); a prefix that isn't bound to anything is taken as it is.
+pub fn parse_model(data: &[u8]) -> Result<Model, Error> {
+    ModelParser::new(data).parse()
+}
+
+// An open element, numbered from 1 among its siblings of the same name
+struct Open<'a> {
+    name: &'a [u8],
+    index: usize,
+    // How many children of each name it has had so far
+    children: Vec<(&'a [u8], usize)>,
+    // How many namespace prefixes it declares
+    declared: usize,
+    // The name serde is given for it, when that isn't the one in the file
+    renamed: Option<Vec<u8>>,
+}
+
+// Part of the model being collected for serde
+struct Chunk {
+    writer: Writer<Vec<u8>>,
+    depth: usize,
+    position: usize,
+    path: String,
+}
+
+// Something wrong with an element's attributes
+struct AttributeError {
+    message: String,
+    attribute: Option<&'static str>,
+}
+
+struct ModelParser<'a> {
+    data: &'a [u8],
+    reader: Reader<&'a [u8]>,
+    open: Vec<Open<'a>>,
+    // Namespace prefixes in scope and what they are bound to, innermost last
+    namespaces: Vec<(Vec<u8>, Vec<u8>)>,
+    // Where the event being handled starts
+    position: usize,
+}
+
+impl<'a> ModelParser<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ModelParser {
+            data,
+            reader: Reader::from_bytes(data),
+            open: Vec::new(),
+            namespaces: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<Model, Error> {
+        // The model element and its metadata
+        let mut rest = Writer::new(Vec::new());
+        let mut chunk: Option<Chunk> = None;
+        let mut resources: Vec<Resource> = Vec::new();
+        let mut build: Option<Build> = None;
+        let mut mesh: (Vec<Point>, Vec<Triangle>) = Default::default();
+
+        loop {
+            let event = self.next()?;
+            match &event {
+                Event::Start(e) | Event::Empty(e) if self.is_mesh_list(e.name()) => {
+                    let empty = matches!(event, Event::Empty(_));
+                    self.enter(e.name());
+                    if e.name() == b"vertices" {
+                        mesh.0 = self.read_vertices(empty)?;
+                    } else {
+                        mesh.1 = self.read_triangles(empty, mesh.0.len())?;
+                    }
+                    self.leave();
+                    continue;
+                }
+                Event::Start(e) | Event::Empty(e) => {
+                    self.enter(e.name());
+                    self.declare(e);
+                    let depth = self.open.len();
+                    let in_resources = depth == 3 && self.open[1].name == b"resources";
+                    if chunk.is_none() && (in_resources || (depth == 2 && e.name() == b"build")) {
+                        chunk = Some(Chunk {
+                            writer: Writer::new(Vec::new()),
+                            depth,
+                            position: self.position,
+                            path: self.path(),
+                        });
+                        mesh = Default::default();
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+
+            let event = self.rename(event);
+            let closing = matches!(event, Event::End(_) | Event::Empty(_));
+            match chunk.as_mut() {
+                Some(c) => c.writer.write_event(event)?,
+                None => rest.write_event(event)?,
+            }
+            if !closing {
+                continue;
+            }
+            if let Some(c) = chunk.take_if(|c| c.depth == self.open.len()) {
+                let data = c.writer.into_inner();
+                let located = |e: DeError| self.chunk_error(e, c.position, &c.path);
+                if c.depth == 2 {
+                    build = Some(quick_xml::de::from_reader(data.as_slice()).map_err(located)?);
+                } else {
+                    let mut resource: Resource =
+                        quick_xml::de::from_reader(data.as_slice()).map_err(located)?;
+                    if let Resource::Object(object) = &mut resource {
+                        let (vertices, triangles) = std::mem::take(&mut mesh);
+                        object.mesh.vertices = vertices;
+                        object.mesh.triangles = triangles;
+                    }
+                    resources.push(resource);
+                }
+            }
+            self.leave();
+        }
+
+        let mut file: ModelFile = quick_xml::de::from_reader(rest.into_inner().as_slice())
+            .map_err(|e| self.chunk_error(e, 0, "model"))?;
+        file.resources = resources;
+        file.build = build.unwrap_or_default();
+        Ok(Model::from(file))
+    }
+
+    fn next(&mut self) -> Result<Event<'a>, Error> {
+        self.position = self.reader.buffer_position();
+        match self.reader.read_event_unbuffered() {
+            Ok(event) => Ok(event),
+            Err(e) => {
+                self.position = self.reader.buffer_position();
+                Err(self.error(e.to_string(), None))
+            }
+        }
+    }
+
+    // Vertex and triangle lists of a mesh
+    fn is_mesh_list(&self, name: &[u8]) -> bool {
+        (name == b"vertices" || name == b"triangles")
+            && self.open.last().is_some_and(|o| o.name == b"mesh")
+    }
+
+    fn enter(&mut self, name: &[u8]) {
+        // The name as part of the data, so that it can be kept without copying it
+        let at = self.data[self.position..]
+            .iter()
+            .position(|&c| c == b'<')
+            .map_or(self.data.len(), |k| self.position + k + 1);
+        let name: &'a [u8] = &self.data[at..(at + name.len()).min(self.data.len())];
+
+        let index = match self.open.last_mut() {
+            Some(parent) => sibling_index(&mut parent.children, name),
+            None => 1,
+        };
+        self.open.push(Open {
+            name,
+            index,
+            children: Vec::new(),
+            declared: 0,
+            renamed: None,
+        });
+    }
+
+    fn leave(&mut self) {
+        if let Some(open) = self.open.pop() {
+            let kept = self.namespaces.len() - open.declared;
+            self.namespaces.truncate(kept);
+        }
+    }
+
+    // Brings the namespace prefixes the element just entered declares into scope
+    fn declare(&mut self, e: &BytesStart) {
+        let before = self.namespaces.len();
+        for a in e.attributes().with_checks(false).flatten() {
+            if let Some(prefix) = a.key.strip_prefix(b"xmlns:") {
+                self.namespaces
+                    .push((prefix.to_vec(), a.value.into_owned()));
+            }
+        }
+        let renamed = self.prefixed(e.name());
+        if let Some(open) = self.open.last_mut() {
+            open.declared = self.namespaces.len() - before;
+            open.renamed = renamed;
+        }
+    }
+
+    // A prefixed name with the prefix serde expects for its namespace, if that is another one.
+    // Names with one of those prefixes bound to some other namespace are given a prefix serde
+    // won't recognise.
+    fn prefixed(&self, name: &[u8]) -> Option<Vec<u8>> {
+        let colon = name.iter().position(|&c| c == b':')?;
+        let prefix = &name[..colon];
+        let (_, namespace) = self.namespaces.iter().rev().find(|(p, _)| p == prefix)?;
+        let expected = PREFIXES
+            .iter()
+            .find(|(n, _)| n.as_bytes() == namespace.as_slice())
+            .map(|&(_, p)| p);
+        match expected {
+            Some(expected) if expected == prefix => None,
+            Some(expected) => Some([expected, &name[colon..]].concat()),
+            None if PREFIXES.iter().any(|&(_, p)| p == prefix) => {
+                Some([b"unknown-", name].concat())
+            }
+            None => None,
+        }
+    }
+
+    // The event with the names serde expects, see `prefixed`. Mostly it is left as it is.
+    fn rename(&self, event: Event<'a>) -> Event<'a> {
+        let start = |e: BytesStart<'a>| {
+            let element = self.open.last().and_then(|o| o.renamed.clone());
+            if element.is_none() && !e.attributes_raw().contains(&b':') {
+                return e;
+            }
+            let mut attributes = e.attributes();
+            let attributes = attributes.with_checks(false).flatten();
+            let renamed: Vec<(Attribute, Option<Vec<u8>>)> = attributes
+                .map(|a| {
+                    let key = self.prefixed(a.key);
+                    (a, key)
+                })
+                .collect();
+            if element.is_none() && renamed.iter().all(|(_, key)| key.is_none()) {
+                return e;
+            }
+            let mut start = BytesStart::owned_name(element.unwrap_or_else(|| e.name().to_vec()));
+            for (a, key) in renamed {
+                start.push_attribute(Attribute {
+                    key: key.as_deref().unwrap_or(a.key),
+                    value: a.value,
+                });
+            }
+            start
+        };
+        match event {
+            Event::Start(e) => Event::Start(start(e)),
+            Event::Empty(e) => Event::Empty(start(e)),
+            Event::End(e) => match self.open.last().and_then(|o| o.renamed.clone()) {
+                Some(name) => Event::End(BytesEnd::owned(name)),
+                None => Event::End(e),
+            },
+            event => event,
+        }
+    }
+
+    fn path(&self) -> String {
+        let steps: Vec<String> = self
+            .open
+            .iter()
+            .map(|o| step(&String::from_utf8_lossy(o.name), o.index))
+            .collect();
+        steps.join("/")
+    }
+
+    fn error(&self, message: String, attribute: Option<&str>) -> Error {
+        Error::InvalidXML(Box::new(XmlError {
+            message,
+            part: None,
+            position: Some(TextPosition::find(self.data, self.position)),
+            path: Some(self.path()),
+            attribute: attribute.map(|a| a.to_string()),
+        }))
+    }
+
+    // An error in the `index`th element of the list being read
+    fn element_error(&self, element: &str, index: usize, e: AttributeError) -> Error {
+        let mut error = self.error(e.message, e.attribute);
+        if let Error::InvalidXML(xml) = &mut error {
+            xml.path = Some(format!("{}/{}", self.path(), step(element, index)));
+        }
+        error
+    }
+
+    // serde doesn't say where in a resource or the build its mistakes are, so they are placed at
+    // the start of it, or at the first attribute in it with the value they quote, if they quote
+    // one (as in `invalid colour "#GG0000"`)
+    fn chunk_error(&self, e: DeError, position: usize, path: &str) -> Error {
+        let message = e.to_string();
+        let found = quoted(&message).and_then(|value| self.find_value(position, path, value));
+        let (position, path, attribute) = match found {
+            Some((position, path, attribute)) => (position, path, Some(attribute)),
+            None => (position, path.to_string(), None),
+        };
+        Error::InvalidXML(Box::new(XmlError {
+            message,
+            part: None,
+            position: Some(TextPosition::find(self.data, position)),
+            path: Some(path),
+            attribute,
+        }))
+    }
+
+    // The first attribute with `value` in the element starting at `position`, whose path is
+    // `path`, as where its element starts, that element's path and the attribute's name
+    fn find_value(
+        &self,
+        position: usize,
+        path: &str,
+        value: &str,
+    ) -> Option<(usize, String, String)> {
+        // The path of an open element, and how many children of each name it has had
+        type Opened = (String, Vec<(Vec<u8>, usize)>);
+        let mut open: Vec<Opened> = Vec::new();
+        let mut reader = Reader::from_bytes(&self.data[position..]);
+        loop {
+            let start = position + reader.buffer_position();
+            let (e, empty) = match reader.read_event_unbuffered().ok()? {
+                Event::Start(e) => (e, false),
+                Event::Empty(e) => (e, true),
+                Event::End(_) => {
+                    open.pop();
+                    if open.is_empty() {
+                        return None;
+                    }
+                    continue;
+                }
+                Event::Eof => return None,
+                _ => continue,
+            };
+            let element = match open.last_mut() {
+                Some((parent, children)) => {
+                    let index = sibling_index(children, e.name().to_vec());
+                    let name = String::from_utf8_lossy(e.name());
+                    format!("{}/{}", parent, step(&name, index))
+                }
+                None => path.to_string(),
+            };
+            let mut attributes = e.attributes();
+            let found = attributes.with_checks(false).flatten().find(|a| {
+                a.unescaped_value()
+                    .is_ok_and(|v| v.as_ref() == value.as_bytes())
+            });
+            if let Some(a) = found {
+                let at = self.data[start..]
+                    .iter()
+                    .position(|&c| c == b'<')
+                    .map_or(start, |k| start + k);
+                return Some((at, element, String::from_utf8_lossy(a.key).into_owned()));
+            }
+            if !empty {
+                open.push((element, Vec::new()));
+            } else if open.is_empty() {
+                return None;
+            }
+        }
+    }
+
+    fn read_vertices(&mut self, empty: bool) -> Result<Vec<Point>, Error> {
+        if empty {
+            return Ok(Vec::new());
+        }
+        let start = self.reader.buffer_position();
+        let mut vertices = Vec::with_capacity(count_elements(&self.data[start..], b"vertex"));
+        self.read_list(b"vertex", |e| {
+            vertices.push(parse_vertex(e)?);
+            Ok(())
+        })?;
+        Ok(vertices)
+    }
+
+    // Triangles must only use the vertices before them
+    fn read_triangles(&mut self, empty: bool, vertices: usize) -> Result<Vec<Triangle>, Error> {
+        if empty {
+            return Ok(Vec::new());
+        }
+        let start = self.reader.buffer_position();
+        let mut triangles = Vec::with_capacity(count_elements(&self.data[start..], b"triangle"));
+        self.read_list(b"triangle", |e| {
+            let triangle = parse_triangle(e)?;
+            for (attribute, v) in TRIANGLE_ATTRIBUTES.into_iter().zip(triangle.indices()) {
+                if v >= vertices {
+                    return Err(AttributeError {
+                        message: format!(
+                            "vertex index {} is out of range for {} vertices",
+                            v, vertices
+                        ),
+                        attribute: Some(attribute),
+                    });
+                }
+            }
+            triangles.push(triangle);
+            Ok(())
+        })?;
+        Ok(triangles)
+    }
+
+    // Calls `read` on each `element` up to the end of the list being read
+    fn read_list<F>(&mut self, element: &[u8], mut read: F) -> Result<(), Error>
+    where
+        F: FnMut(&BytesStart) -> Result<(), AttributeError>,
+    {
+        let name = String::from_utf8_lossy(element).into_owned();
+        let mut index = 0;
+        loop {
+            match self.next()? {
+                Event::Start(e) | Event::Empty(e) if e.name() == element => {
+                    index += 1;
+                    read(&e).map_err(|err| self.element_error(&name, index, err))?;
+                }
+                Event::End(e) if e.name() == element => {}
+                Event::End(_) => return Ok(()),
+                Event::Eof => return Err(self.error("unexpected end of file".to_string(), None)),
+                _ => {}
+            }
+        }
+    }
+}
+
+// The number of a child called `name` among its siblings of that name, counting it in
+fn sibling_index<N: PartialEq>(children: &mut Vec<(N, usize)>, name: N) -> usize {
+    match children.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, count)) => {
+            *count += 1;
+            *count
+        }
+        None => {
+            children.push((name, 1));
+            1
+        }
+    }
+}
+
+// The value a message quotes, e.g. #GG0000 in `invalid colour "#GG0000"`
+fn quoted(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once('"')?;
+    let (value, _) = rest.split_once('"')?;
+    Some(value)
+}
+
+// A step of an element path
+fn step(name: &str, index: usize) -> String {
+    if index > 1 {
+        format!("{}[{}]", name, index)
+    } else {
+        name.to_string()
+    }
+}
+
+// How many `element`s there are before the end of the list they are in, to size it with
+fn count_elements(data: &[u8], element: &[u8]) -> usize {
+    let mut count = 0;
+    let mut k = 0;
+    while let Some(offset) = data[k..].iter().position(|&c| c == b'<') {
+        k += offset + 1;
+        match data[k..].strip_prefix(b"/") {
+            // The end of anything but an element is the end of the list
+            Some(tag) if tag_name(tag) != element => break,
+            Some(_) => {}
+            None if tag_name(&data[k..]) == element => count += 1,
+            None => {}
+        }
+    }
+    count
+}
+
+// The name at the start of a tag
+fn tag_name(tag: &[u8]) -> &[u8] {
+    let end = tag
+        .iter()
+        .position(|&c| c.is_ascii_whitespace() || c == b'/' || c == b'>');
+    &tag[..end.unwrap_or(tag.len())]
+}
+
+fn parse_vertex(e: &BytesStart) -> Result<Point, AttributeError> {
+    let mut xyz = [None; 3];
+    for a in e.attributes().with_checks(false) {
+        let a = a.map_err(attribute_error)?;
+        let (k, name) = match a.key {
+            b"x" => (0, "x"),
+            b"y" => (1, "y"),
+            b"z" => (2, "z"),
+            _ => continue,
+        };
+        xyz[k] = Some(number(&a.value, name)?);
+    }
+    let [x, y, z] = xyz;
+    Ok(Point::new(
+        required(x, "x")?,
+        required(y, "y")?,
+        required(z, "z")?,
+    ))
+}
+
+const TRIANGLE_ATTRIBUTES: [&str; 7] = ["v1", "v2", "v3", "pid", "p1", "p2", "p3"];
+
+fn parse_triangle(e: &BytesStart) -> Result<Triangle, AttributeError> {
+    let mut values = [None; 7];
+    for a in e.attributes().with_checks(false) {
+        let a = a.map_err(attribute_error)?;
+        let k = match TRIANGLE_ATTRIBUTES
+            .iter()
+            .position(|&name| name.as_bytes() == a.key)
+        {
+            Some(k) => k,
+            None => continue,
+        };
+        values[k] = Some(number(&a.value, TRIANGLE_ATTRIBUTES[k])?);
+    }
+    let [v1, v2, v3, pid, p1, p2, p3] = values;
+    Ok(Triangle {
+        pid,
+        p1,
+        p2,
+        p3,
+        ..Triangle::new(
+            required(v1, "v1")?,
+            required(v2, "v2")?,
+            required(v3, "v3")?,
+        )
+    })
+}
+
+fn number<T: FromStr>(value: &[u8], attribute: &'static str) -> Result<T, AttributeError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| AttributeError {
+            message: format!("invalid number {:?}", String::from_utf8_lossy(value)),
+            attribute: Some(attribute),
+        })
+}
+
+fn required<T>(value: Option<T>, attribute: &'static str) -> Result<T, AttributeError> {
+    value.ok_or_else(|| AttributeError {
+        message: "missing attribute".to_string(),
+        attribute: Some(attribute),
+    })
+}
+
+fn attribute_error<E: std::fmt::Display>(e: E) -> AttributeError {
+    AttributeError {
+        message: e.to_string(),
+        attribute: None,
+    }
+}
+
+#[test]
+fn test_parse_model() {
+    use crate::threemf::package::Package;
+    use zip::ZipArchive;
+
+    // Meshes come out the same as through serde, for every file there is
+    let mut files: Vec<_> = std::fs::read_dir("data")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "3mf"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty());
+    for path in files {
+        let archive = ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut package = Package::new(archive).unwrap();
+        let root = package.root().unwrap();
+        let data = package.read_bytes(&root).unwrap();
+        let expected: Model = quick_xml::de::from_reader(data.as_slice()).unwrap();
+        let model = parse_model(&data).unwrap();
+        assert_eq!(model, expected, "{}", path.display());
+        for object in model.objects.iter() {
+            let mesh = &object.mesh;
+            assert_eq!(mesh.vertices.capacity(), mesh.vertices.len());
+            assert_eq!(mesh.triangles.capacity(), mesh.triangles.len());
+        }
+    }
+
+    // Self-closing lists
+    let xml = br#"<model><resources><object id="1" name="Empty"><mesh><vertices/><triangles/></mesh></object></resources><build/></model>"#;
+    assert!(parse_model(xml).unwrap().objects[0]
+        .mesh
+        .vertices
+        .is_empty());
+
+    // Errors say where they are
+    let error = |xml: &str| match parse_model(xml.as_bytes()) {
+        Err(Error::InvalidXML(e)) => e,
+        other => panic!("{:?}", other.map(|_| ())),
+    };
+    let object = |id: &str, triangles: &str| {
+        format!(
+            "<object id=\"{}\" name=\"Part\">\n<mesh>\n<vertices>\n<vertex x=\"0\" y=\"0\" z=\"0\"/>\n<vertex x=\"1\" y=\"0\" z=\"0\"/>\n<vertex x=\"0\" y=\"1\" z=\"0\"/>\n</vertices>\n<triangles>\n{}\n</triangles>\n</mesh>\n</object>\n",
+            id, triangles
+        )
+    };
+    let model = |objects: &[String]| {
+        format!(
+            "<model>\n<resources>\n{}</resources>\n<build/>\n</model>",
+            objects.concat()
+        )
+    };
+    let good = r#"<triangle v1="0" v2="1" v3="2"/>"#;
+
+    let e = error(&model(&[
+        object("1", good),
+        object("2", good),
+        object(
+            "3",
+            &format!("{}\n{}", good, r#"<triangle v1="0" v2="3" v3="2"/>"#),
+        ),
+    ]));
+    assert_eq!(
+        e.path.as_deref(),
+        Some("model/resources/object[3]/mesh/triangles/triangle[2]")
+    );
+    assert_eq!(e.attribute.as_deref(), Some("v2"));
+    let position = e.position.unwrap();
+    assert_eq!((position.line, position.column), (36, 1));
+    assert!(e.message.contains("out of range"));
+
+    let e = error(&model(&[object(
+        "1",
+        r#"<triangle v1="0" v2="1" v3="two"/>"#,
+    )]));
+    assert_eq!(e.attribute.as_deref(), Some("v3"));
+    assert_eq!(
+        e.path.as_deref(),
+        Some("model/resources/object/mesh/triangles/triangle")
+    );
+
+    // Mistakes serde finds are placed at their resource
+    let e = error(&model(&[object("1", good), object("x", good)]));
+    assert_eq!(e.path.as_deref(), Some("model/resources/object[2]"));
+    assert_eq!(e.position.unwrap().line, 15);
+    assert_eq!(e.attribute, None);
+
+    // unless they quote a value, which is looked for in it
+    let e = error(&format!(
+        "<model xmlns:m=\"{}\">\n<resources>\n{}<m:colorgroup id=\"2\">\n<m:color color=\"#FF0000\"/>\n<m:color color=\"#GG0000\"/>\n</m:colorgroup>\n</resources>\n<build/>\n</model>",
+        MATERIAL_NAMESPACE,
+        object("1", good)
+    ));
+    assert_eq!(
+        e.path.as_deref(),
+        Some("model/resources/m:colorgroup/m:color[2]")
+    );
+    assert_eq!(e.attribute.as_deref(), Some("color"));
+    assert_eq!(e.position.unwrap().line, 17);
+    assert_eq!(
+        e.to_string(),
+        "invalid colour \"#GG0000\" in attribute color of \
+         model/resources/m:colorgroup/m:color[2] at line 17, column 1 (byte 359)"
+    );
+
+    let e = error(&model(&[object("1", good)]).replace(
+        "<build/>",
+        "<build>\n<item objectid=\"1\"/>\n<item objectid=\"1\" transform=\"1 0 0\"/>\n</build>",
+    ));
+    assert_eq!(e.path.as_deref(), Some("model/build/item[2]"));
+    assert_eq!(e.attribute.as_deref(), Some("transform"));
+    assert_eq!(e.position.unwrap().line, 18);
+
+    // and the part is named when the model is read from a package
+    let mut broken = crate::threemf::Model::empty();
+    broken.objects.push(crate::threemf::Object {
+        id: 1,
+        name: "Part".to_string(),
+        otype: Default::default(),
+        uuid: None,
+        pid: None,
+        pindex: None,
+        slicestackid: None,
+        meshresolution: None,
+        metadata: Default::default(),
+        mesh: crate::threemf::Mesh::new(
+            vec![Point::new(0.0, 0.0, 0.0)],
+            vec![Triangle::new(0, 0, 7)],
+        ),
+        components: Vec::new(),
+    });
+    match Model::from_raw_data(&broken.to_raw_data().unwrap()) {
+        Err(Error::InvalidXML(e)) => {
+            assert_eq!(e.part.as_deref(), Some("/3D/3dmodel.model"));
+            assert_eq!(e.attribute.as_deref(), Some("v3"));
+            assert!(e.to_string().contains("/3D/3dmodel.model"));
+        }
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_namespace_prefixes() {
+    use crate::threemf::write::CORE_NAMESPACE;
+    use crate::threemf::Color;
+
+    // Extensions bound to prefixes of the file's own choosing, one of them only on the element
+    // that uses it
+    let xml = format!(
+        r##"<model xmlns="{}" xmlns:pr="{}" xmlns:m="{}">
+<resources>
+<mat:colorgroup id="1" xmlns:mat="{}"><mat:color color="#FF0000"/></mat:colorgroup>
+<m:colorgroup id="2"><m:color color="#00FF00"/></m:colorgroup>
+<object id="3" name="Part" pr:UUID="0b2c5a3e-6f40-4d8e-9c1a-000000000003"/>
+</resources>
+<build><item objectid="3" pr:UUID="0b2c5a3e-6f40-4d8e-9c1a-0000000000a0"/></build>
+</model>"##,
+        CORE_NAMESPACE,
+        PRODUCTION_NAMESPACE,
+        "http://example.com/not-materials",
+        MATERIAL_NAMESPACE
+    );
+    let model = parse_model(xml.as_bytes()).unwrap();
+
+    // m isn't the materials extension here, so its group isn't read as one
+    assert_eq!(model.colors.len(), 1);
+    assert_eq!(model.colors[0].id, 1);
+    assert_eq!(model.colors[0].colors, vec![Color::new(255, 0, 0, 255)]);
+    assert_eq!(
+        model.objects[0].uuid.as_deref(),
+        Some("0b2c5a3e-6f40-4d8e-9c1a-000000000003")
+    );
+    assert_eq!(
+        model.build.items[0].uuid.as_deref(),
+        Some("0b2c5a3e-6f40-4d8e-9c1a-0000000000a0")
+    );
+
+    // and the prefix goes out of scope with the element that declared it
+    let xml = xml.replacen(
+        "<m:colorgroup id=\"2\"><m:color",
+        "<mat:colorgroup id=\"2\"><mat:color",
+        1,
+    );
+    let xml = xml.replacen("</m:colorgroup>", "</mat:colorgroup>", 1);
+    assert_eq!(parse_model(xml.as_bytes()).unwrap().colors.len(), 1);
+}
@@ -1,5 +1,5 @@
 import * as THREE from 'three';
-import {slice_model, load_model, get_centroid} from "../pkg/index_bg.js";
+import {slice_model, load_model, get_centroid, get_overhangs} from "../pkg/index_bg.js";
 import { OrbitControls } from "three/examples/jsm/controls/OrbitControls";
 import {VertexNormalsHelper} from "three/examples/jsm/helpers/VertexNormalsHelper";
 
@@ -114,6 +114,7 @@ function update_model_display() {
     const material = new THREE.MeshStandardMaterial({color: 0x555555, side: THREE.DoubleSide, metallicness: 0.5})
     material.flatShading = true;
     const mesh = new THREE.Mesh(geometry, material);
+    model_mesh = mesh;
 
     let out = JSON.parse(get_centroid(dropped_data));
     controls.target.set(out.x, out.z, out.y)
@@ -156,6 +157,38 @@ function slice_data() {
 
 }
 
+const face_colors = {
+    bed: new THREE.Color(0x3377ff),
+    supported: new THREE.Color(0x555555),
+    overhang: new THREE.Color(0xff3333),
+    degenerate: new THREE.Color(0xffff00),
+};
+
+function show_overhangs() {
+    if (!model_mesh) {
+        console.error("Model has not been loaded!");
+        return;
+    }
+
+    let analysis = JSON.parse(get_overhangs(dropped_data, 45.0));
+
+    // Colours are per face, so every triangle needs its own vertices
+    const geometry = model_mesh.geometry.index ? model_mesh.geometry.toNonIndexed() : model_mesh.geometry;
+    let colors = [];
+    for (let face_class of analysis.classes) {
+        const color = face_colors[face_class];
+        for (let i = 0; i < 3; i++) {
+            colors.push(color.r, color.g, color.b);
+        }
+    }
+    geometry.setAttribute('color', new THREE.Float32BufferAttribute(colors, 3));
+
+    model_mesh.geometry = geometry;
+    model_mesh.material.color.set(0xffffff);
+    model_mesh.material.vertexColors = true;
+    model_mesh.material.needsUpdate = true;
+}
+
 function add_slices_to_select(ids) {
     let select = document.querySelector("#slice_list_select")
 
@@ -185,9 +218,11 @@ document.querySelector("#slice_list_select").addEventListener('click', (e) => {
 let dropped_data = new Uint8Array(0);
 let model = {};
 let slices = {};
+let model_mesh = null;
 
 document.querySelector("#load_data_button").onclick = load_data
 document.querySelector("#slice_model_button").onclick = slice_data
+document.querySelector("#overhang_button").onclick = show_overhangs
 
 
 ;['dragenter', 'dragover', 'dragleave', 'drop'].forEach(eventName => {
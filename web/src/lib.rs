@@ -3,7 +3,8 @@ use wasm_bindgen::prelude::*;
 use web_sys::console;
 
 use serde_json::to_string;
-use slicing::geometry::Plane;
+use slicing::analysis::analyze_overhangs;
+use slicing::geometry::{Plane, Vector};
 use slicing::threemf::model::Model;
 
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
@@ -89,3 +90,19 @@ pub fn get_centroid(data: &[u8]) -> Result<String, JsValue> {
 
     Ok(json)
 }
+
+#[wasm_bindgen]
+pub fn get_overhangs(data: &[u8], critical_angle: f64) -> Result<String, JsValue> {
+    let model = Model::from_raw_data(data).unwrap();
+
+    let analysis = analyze_overhangs(&model.objects[0].mesh, Vector::Z, critical_angle);
+    log(format!(
+        "Overhangs: {} triangles, {} area",
+        analysis.overhangs.len(),
+        analysis.overhang_area
+    )
+    .as_str());
+
+    let json = serde_json::to_string(&analysis).unwrap();
+    Ok(json)
+}